@@ -1,62 +1,43 @@
 use std::{
-    collections::HashMap,
+    sync::Arc,
     time::{Duration, Instant},
 };
 
-// use cornerstone::{
-//     node::composite::{Composite, Parallel, Sequence},
-//     Context, DataProxy, NodeStatus, TreeNode,
-// };
+use cornerstone::{
+    clock::Clock, factory::Factory, parser::xml::create_bt_tree_from_xml_str, NodeStatus, TreeNode,
+};
 
-// struct SleepNode {
-//     name: String,
-//     end_ts: Instant,
-//     data_proxy: DataProxy,
-// }
+const XML: &str = r#"
+<root BTCPP_format="4" main_tree_to_execute="main">
+    <BehaviorTree ID="main">
+        <Parallel success_count="1">
+            <Sleep msec="3000"/>
+            <Sleep msec="5000"/>
+        </Parallel>
+    </BehaviorTree>
+</root>"#;
 
-// impl TreeNode for SleepNode {
-//     fn tick(&mut self, ctx: &mut cornerstone::Context) -> NodeStatus {
-//         let current_ts = Instant::now();
+fn main() {
+    let factory = Factory::default();
 
-//         if current_ts <= self.end_ts {
-//             println!("sleep: {}", self.name);
+    let mut root = create_bt_tree_from_xml_str(&factory, XML).unwrap().unwrap();
 
-//             NodeStatus::Running
-//         } else {
-//             println!("finish: {}", self.name);
-//             NodeStatus::Success
-//         }
-//     }
-// }
+    // run the tree ten times faster than wall time
+    root.set_clock(Arc::new(Clock::new(10.0)));
 
-fn main() {
-    // let mut ctx = Context::default();
-
-    // let sleep_node_1 = SleepNode {
-    //     name: "alice".to_string(),
-    //     end_ts: Instant::now() + Duration::from_secs(3),
-    //     data_proxy: DataProxy::new(HashMap::new()),
-    // };
-
-    // let sleep_node_2 = SleepNode {
-    //     name: "bob".to_string(),
-    //     end_ts: Instant::now() + Duration::from_secs(5),
-    //     data_proxy: DataProxy::new(HashMap::new()),
-    // };
-
-    // let mut root = Parallel::new(Some(1), None);
-    // root.add_child(Box::new(sleep_node_1));
-    // root.add_child(Box::new(sleep_node_2));
-
-    // loop {
-    //     let res = root.tick(&mut ctx);
-
-    //     if res != NodeStatus::Running {
-    //         println!("finish run sleep node: res= {res:?}");
-    //         break;
-    //     } else {
-    //         println!("need wait for finish");
-    //         std::thread::sleep(Duration::from_millis(200));
-    //     }
-    // }
+    let start = Instant::now();
+
+    loop {
+        let res = root.tick();
+
+        if res != NodeStatus::Running {
+            println!(
+                "finish run sleep node: res= {res:?} elapsed= {:?}",
+                start.elapsed()
+            );
+            break;
+        } else {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
 }
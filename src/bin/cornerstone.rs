@@ -0,0 +1,155 @@
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::Duration,
+};
+
+use clap::{Parser, Subcommand};
+use cornerstone::{
+    factory::{boxify_action, Factory},
+    parser::{
+        validation::{DryRunStub, ValidationIssue},
+        xml::read_xml_file,
+    },
+    prelude::*,
+};
+
+#[derive(Parser)]
+#[command(version, about = "Validate, visualize and run behavior tree files")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Check the tree, every action must be built in or listed in the manifest
+    Validate {
+        file: PathBuf,
+        /// JSON array of action node names provided by the host program
+        #[arg(long)]
+        manifest: Option<PathBuf>,
+    },
+    /// Print the tree as a graphviz digraph
+    Dot { file: PathBuf },
+    /// Print the tree as a mermaid flowchart
+    Mermaid { file: PathBuf },
+    /// Tick the tree until it completes, unknown actions succeed immediately
+    Run {
+        file: PathBuf,
+        #[arg(long, default_value_t = 50)]
+        tick_ms: u64,
+        #[arg(long)]
+        max_ticks: Option<usize>,
+    },
+}
+
+fn register_stubs<'a>(factory: &mut Factory, names: impl IntoIterator<Item = &'a str>) {
+    for name in names {
+        let pat = format!("^{}$", regex::escape(name));
+
+        factory.register_action_node_type(
+            pat.as_str().try_into().unwrap(),
+            boxify_action(|_, _| Ok(DryRunStub)),
+        );
+    }
+}
+
+// default factory, with stubs for every action it doesn't know
+fn stub_factory(xml: &str) -> Result<Factory, BtError> {
+    let mut factory = Factory::default();
+
+    let report = validate_bt_tree_from_xml_str(&factory, xml)?;
+    let unknown: Vec<_> = report
+        .issues
+        .iter()
+        .filter_map(|issue| match issue {
+            ValidationIssue::UnknownNode { type_name, .. } => Some(type_name.as_str()),
+            _ => None,
+        })
+        .collect();
+    register_stubs(&mut factory, unknown);
+
+    Ok(factory)
+}
+
+// built from the file, so errors point into included files
+fn build(file: &Path) -> Result<TreeNodeWrapper, Box<dyn Error>> {
+    let factory = stub_factory(&read_xml_file(file)?)?;
+
+    create_bt_tree_from_xml_file(&factory, file)?.ok_or_else(|| "no tree found".into())
+}
+
+fn main() -> Result<ExitCode, Box<dyn Error>> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .init();
+
+    let cli = Cli::parse();
+
+    match cli.command {
+        Command::Validate { file, manifest } => {
+            let xml = read_xml_file(file)?;
+
+            let mut factory = Factory::default();
+            if let Some(manifest) = manifest {
+                let names: Vec<String> = serde_json::from_str(&std::fs::read_to_string(manifest)?)?;
+                register_stubs(&mut factory, names.iter().map(|n| n.as_str()));
+            }
+
+            let report = validate_bt_tree_from_xml_str(&factory, &xml)?;
+            for issue in &report.issues {
+                println!("{issue}");
+            }
+            println!(
+                "nodes= {} issues= {}",
+                report.node_count,
+                report.issues.len()
+            );
+
+            if !report.is_ok() {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Command::Dot { file } => {
+            let node = build(&file)?;
+            println!("{}", node.dot_info());
+        }
+        Command::Mermaid { file } => {
+            let node = build(&file)?;
+            print!("{}", node.mermaid_info());
+        }
+        Command::Run {
+            file,
+            tick_ms,
+            max_ticks,
+        } => {
+            let mut node = build(&file)?;
+
+            let mut ticks = 0;
+            let status = loop {
+                let status = node.tick();
+                ticks += 1;
+
+                if status.is_completed()
+                    || status == NodeStatus::Skipped
+                    || max_ticks.is_some_and(|max| ticks >= max)
+                {
+                    break status;
+                }
+
+                std::thread::sleep(Duration::from_millis(tick_ms));
+            };
+
+            print!("{node}");
+            println!("status= {status:?} ticks= {ticks}");
+
+            if status != NodeStatus::Success {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
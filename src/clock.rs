@@ -0,0 +1,112 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+
+static GLOBAL_CLOCK: Lazy<Arc<Clock>> = Lazy::new(|| Arc::new(Clock::default()));
+
+#[derive(Debug)]
+struct ClockState {
+    time_scale: f64,
+    // real time since the clock was created when `virtual_anchor` was taken
+    real_anchor: Duration,
+    virtual_anchor: Duration,
+    // real time of a manual clock, `None` follows the wall clock
+    manual_real: Option<Duration>,
+}
+
+/// Time source used by all time based nodes (Sleep, Delay, Timeout, ...).
+///
+/// The clock measures virtual time, which advances `time_scale` times as fast as
+/// wall time, so simulations and soak tests can run trees faster (or slower)
+/// without touching port values.
+#[derive(Debug)]
+pub struct Clock {
+    created: Instant,
+    state: Mutex<ClockState>,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+impl Clock {
+    pub fn new(time_scale: f64) -> Self {
+        Self::with_real_time(time_scale, None)
+    }
+
+    /// Clock whose real time only moves with [`Clock::advance`], e.g. to step
+    /// a tree through its timeouts in tests without sleeping.
+    pub fn manual(time_scale: f64) -> Self {
+        Self::with_real_time(time_scale, Some(Duration::ZERO))
+    }
+
+    fn with_real_time(time_scale: f64, manual_real: Option<Duration>) -> Self {
+        let clock = Self {
+            created: Instant::now(),
+            state: Mutex::new(ClockState {
+                time_scale: 1.0,
+                real_anchor: manual_real.unwrap_or_default(),
+                virtual_anchor: Duration::ZERO,
+                manual_real,
+            }),
+        };
+        clock.set_time_scale(time_scale);
+
+        clock
+    }
+
+    fn real_now(&self, state: &ClockState) -> Duration {
+        state.manual_real.unwrap_or_else(|| self.created.elapsed())
+    }
+
+    /// The process wide clock, used by nodes unless the tree was given its own.
+    pub fn global() -> Arc<Clock> {
+        GLOBAL_CLOCK.clone()
+    }
+
+    pub fn time_scale(&self) -> f64 {
+        self.state.lock().time_scale
+    }
+
+    pub fn set_time_scale(&self, time_scale: f64) {
+        if !time_scale.is_finite() || time_scale <= 0.0 {
+            tracing::warn!("ignore invalid time scale: {time_scale}");
+            return;
+        }
+
+        let mut state = self.state.lock();
+
+        let real_now = self.real_now(&state);
+        let elapsed = (real_now - state.real_anchor).mul_f64(state.time_scale);
+        state.virtual_anchor += elapsed;
+        state.real_anchor = real_now;
+        state.time_scale = time_scale;
+    }
+
+    /// Move the real time of a [`Clock::manual`] clock forward, its virtual
+    /// time advances `time_scale` times as much.
+    pub fn advance(&self, real: Duration) {
+        match &mut self.state.lock().manual_real {
+            Some(manual_real) => *manual_real += real,
+            None => tracing::warn!("ignore advance of a wall time clock: {real:?}"),
+        }
+    }
+
+    /// Virtual time elapsed since the clock was created.
+    pub fn now(&self) -> Duration {
+        let state = self.state.lock();
+
+        state.virtual_anchor + (self.real_now(&state) - state.real_anchor).mul_f64(state.time_scale)
+    }
+
+    /// Convert a virtual duration into the wall time it takes on this clock.
+    pub fn to_real(&self, duration: Duration) -> Duration {
+        duration.div_f64(self.time_scale())
+    }
+}
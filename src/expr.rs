@@ -0,0 +1,507 @@
+//! Small expression language over blackboard entries, e.g.
+//! `battery > 20 && mode == 'auto'`.
+//!
+//! Supports number, string (`'..'` or `".."`) and boolean literals, blackboard
+//! keys as bare identifiers or `{key}`, `! -` prefixes, `* / % + -`,
+//! comparisons, `== !=`, `&& ||` and parentheses. Strings holding numbers
+//! compare and compute as numbers, since most blackboard values are strings.
+//!
+//! Scripts are `;` separated statements, assignments either create an entry
+//! (`count := 0`) or update an existing one (`count = count + 1`).
+
+use serde_json::Value;
+
+use crate::BtError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Value),
+    Var(String),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Not,
+    Neg,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+impl BinaryOp {
+    // higher binds tighter
+    fn precedence(self) -> u8 {
+        match self {
+            Self::Or => 1,
+            Self::And => 2,
+            Self::Eq | Self::Ne => 3,
+            Self::Lt | Self::Le | Self::Gt | Self::Ge => 4,
+            Self::Add | Self::Sub => 5,
+            Self::Mul | Self::Div | Self::Rem => 6,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Str(String),
+    Ident(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+}
+
+const OPERATORS: [&str; 18] = [
+    "&&", "||", "==", "!=", "<=", ">=", ":=", "<", ">", "!", "+", "-", "*", "/", "%", "=", ";", "&",
+];
+
+fn tokenize(s: &str) -> Result<Vec<Token>, BtError> {
+    let mut tokens = vec![];
+    let chars: Vec<char> = s.chars().collect();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '(' {
+            tokens.push(Token::LParen);
+            i += 1;
+        } else if c == ')' {
+            tokens.push(Token::RParen);
+            i += 1;
+        } else if c == '\'' || c == '"' {
+            let end = chars[i + 1..]
+                .iter()
+                .position(|ch| *ch == c)
+                .ok_or_else(|| BtError::Raw(format!("unterminated string in: {s}")))?;
+
+            tokens.push(Token::Str(chars[i + 1..i + 1 + end].iter().collect()));
+            i += end + 2;
+        } else if c == '{' {
+            let end = chars[i + 1..]
+                .iter()
+                .position(|ch| *ch == '}')
+                .ok_or_else(|| BtError::Raw(format!("unterminated key in: {s}")))?;
+
+            tokens.push(Token::Ident(chars[i + 1..i + 1 + end].iter().collect()));
+            i += end + 2;
+        } else if c.is_ascii_digit()
+            || (c == '.' && chars.get(i + 1).is_some_and(|d| d.is_ascii_digit()))
+        {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+
+            let literal: String = chars[start..i].iter().collect();
+            let number = literal
+                .parse()
+                .map_err(|_| BtError::Raw(format!("invalid number: {literal}")))?;
+            tokens.push(Token::Number(number));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len()
+                && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '.')
+            {
+                i += 1;
+            }
+
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else {
+            let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+            let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) else {
+                return Err(BtError::Raw(format!("unexpected character: {c} in: {s}")));
+            };
+
+            if *op == "&" {
+                return Err(BtError::Raw(format!("unexpected operator: {op} in: {s}")));
+            }
+
+            tokens.push(Token::Op(op));
+            i += op.len();
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn binary_op(&self) -> Option<BinaryOp> {
+        let Some(Token::Op(op)) = self.peek() else {
+            return None;
+        };
+
+        Some(match *op {
+            "||" => BinaryOp::Or,
+            "&&" => BinaryOp::And,
+            "==" => BinaryOp::Eq,
+            "!=" => BinaryOp::Ne,
+            "<" => BinaryOp::Lt,
+            "<=" => BinaryOp::Le,
+            ">" => BinaryOp::Gt,
+            ">=" => BinaryOp::Ge,
+            "+" => BinaryOp::Add,
+            "-" => BinaryOp::Sub,
+            "*" => BinaryOp::Mul,
+            "/" => BinaryOp::Div,
+            "%" => BinaryOp::Rem,
+            _ => return None,
+        })
+    }
+
+    fn expr(&mut self, min_precedence: u8) -> Result<Expr, BtError> {
+        let mut lhs = self.unary()?;
+
+        while let Some(op) = self.binary_op() {
+            if op.precedence() < min_precedence {
+                break;
+            }
+
+            self.pos += 1;
+            let rhs = self.expr(op.precedence() + 1)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, BtError> {
+        match self.next() {
+            Some(Token::Op("!")) => Ok(Expr::Unary(UnaryOp::Not, Box::new(self.unary()?))),
+            Some(Token::Op("-")) => Ok(Expr::Unary(UnaryOp::Neg, Box::new(self.unary()?))),
+            Some(Token::LParen) => {
+                let expr = self.expr(0)?;
+
+                match self.next() {
+                    Some(Token::RParen) => Ok(expr),
+                    _ => Err(BtError::Raw("missing closing parenthesis".to_string())),
+                }
+            }
+            Some(Token::Number(n)) => Ok(Expr::Literal(number_value(n))),
+            Some(Token::Str(s)) => Ok(Expr::Literal(Value::String(s))),
+            Some(Token::Ident(ident)) => Ok(match ident.as_str() {
+                "true" => Expr::Literal(Value::Bool(true)),
+                "false" => Expr::Literal(Value::Bool(false)),
+                _ => Expr::Var(ident),
+            }),
+            token => Err(BtError::Raw(format!("unexpected token: {token:?}"))),
+        }
+    }
+}
+
+/// Parse `s` into an expression tree.
+pub fn parse(s: &str) -> Result<Expr, BtError> {
+    let mut parser = Parser {
+        tokens: tokenize(s)?,
+        pos: 0,
+    };
+
+    let expr = parser.expr(0)?;
+    if let Some(token) = parser.peek() {
+        return Err(BtError::Raw(format!("unexpected token: {token:?} in: {s}")));
+    }
+
+    Ok(expr)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    /// `key := expr` creates the entry, `key = expr` requires it to exist
+    Assign {
+        key: String,
+        expr: Expr,
+        create: bool,
+    },
+    Expr(Expr),
+}
+
+/// Parsed script, see the module docs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Script(Vec<Statement>);
+
+/// Parse `;` separated statements.
+pub fn parse_script(s: &str) -> Result<Script, BtError> {
+    let mut parser = Parser {
+        tokens: tokenize(s)?,
+        pos: 0,
+    };
+
+    let mut statements = vec![];
+    while parser.peek().is_some() {
+        if parser.peek() == Some(&Token::Op(";")) {
+            parser.pos += 1;
+            continue;
+        }
+
+        let assign = match (parser.peek(), parser.tokens.get(parser.pos + 1)) {
+            (Some(Token::Ident(key)), Some(Token::Op(op @ (":=" | "=")))) => {
+                Some((key.clone(), *op == ":="))
+            }
+            _ => None,
+        };
+
+        let statement = match assign {
+            Some((key, create)) => {
+                parser.pos += 2;
+                Statement::Assign {
+                    key,
+                    expr: parser.expr(0)?,
+                    create,
+                }
+            }
+            None => Statement::Expr(parser.expr(0)?),
+        };
+        statements.push(statement);
+
+        match parser.next() {
+            None | Some(Token::Op(";")) => {}
+            Some(token) => {
+                return Err(BtError::Raw(format!("unexpected token: {token:?} in: {s}")));
+            }
+        }
+    }
+
+    Ok(Script(statements))
+}
+
+impl Script {
+    /// Run the statements in order, later statements see earlier assignments.
+    /// Returns the assigned entries, nothing is returned when a statement fails.
+    pub fn execute(
+        &self,
+        lookup: &dyn Fn(&str) -> Option<Value>,
+    ) -> Result<Vec<(String, Value)>, BtError> {
+        let mut assigned: Vec<(String, Value)> = vec![];
+
+        for statement in &self.0 {
+            let scoped_lookup = |key: &str| {
+                assigned
+                    .iter()
+                    .rev()
+                    .find(|(k, _)| k == key)
+                    .map(|(_, v)| v.clone())
+                    .or_else(|| lookup(key))
+            };
+
+            match statement {
+                Statement::Assign { key, expr, create } => {
+                    if !create && scoped_lookup(key).is_none() {
+                        return Err(BtError::Raw(format!(
+                            "assignment to undefined key: {key}, create it with :="
+                        )));
+                    }
+
+                    let value = expr.eval(&scoped_lookup)?;
+                    assigned.retain(|(k, _)| k != key);
+                    assigned.push((key.clone(), value));
+                }
+                Statement::Expr(expr) => {
+                    expr.eval(&scoped_lookup)?;
+                }
+            }
+        }
+
+        Ok(assigned)
+    }
+}
+
+fn number_value(n: f64) -> Value {
+    if n.fract() == 0.0 && n.abs() < i64::MAX as f64 {
+        Value::from(n as i64)
+    } else {
+        Value::from(n)
+    }
+}
+
+fn as_number(v: &Value) -> Option<f64> {
+    match v {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn as_bool(v: &Value) -> Result<bool, BtError> {
+    match v {
+        Value::Bool(b) => Ok(*b),
+        Value::String(s) if s == "true" || s == "false" => Ok(s == "true"),
+        _ => Err(BtError::Raw(format!("expected a boolean: {v}"))),
+    }
+}
+
+fn values_eq(lhs: &Value, rhs: &Value) -> bool {
+    match (as_number(lhs), as_number(rhs)) {
+        (Some(l), Some(r)) => l == r,
+        _ => match (lhs, rhs) {
+            (Value::Bool(b), v) | (v, Value::Bool(b)) => as_bool(v).is_ok_and(|v| v == *b),
+            _ => lhs == rhs,
+        },
+    }
+}
+
+impl Expr {
+    /// Evaluate with `lookup` resolving variables, unknown variables are an error.
+    pub fn eval(&self, lookup: &dyn Fn(&str) -> Option<Value>) -> Result<Value, BtError> {
+        match self {
+            Expr::Literal(v) => Ok(v.clone()),
+            Expr::Var(key) => {
+                lookup(key).ok_or_else(|| BtError::Raw(format!("unknown variable: {key}")))
+            }
+            Expr::Unary(UnaryOp::Not, expr) => Ok(Value::Bool(!as_bool(&expr.eval(lookup)?)?)),
+            Expr::Unary(UnaryOp::Neg, expr) => {
+                let v = expr.eval(lookup)?;
+                let n =
+                    as_number(&v).ok_or_else(|| BtError::Raw(format!("expected a number: {v}")))?;
+
+                Ok(number_value(-n))
+            }
+            Expr::Binary(BinaryOp::And, lhs, rhs) => Ok(Value::Bool(
+                as_bool(&lhs.eval(lookup)?)? && as_bool(&rhs.eval(lookup)?)?,
+            )),
+            Expr::Binary(BinaryOp::Or, lhs, rhs) => Ok(Value::Bool(
+                as_bool(&lhs.eval(lookup)?)? || as_bool(&rhs.eval(lookup)?)?,
+            )),
+            Expr::Binary(op, lhs, rhs) => {
+                let lhs = lhs.eval(lookup)?;
+                let rhs = rhs.eval(lookup)?;
+
+                Self::binary(*op, &lhs, &rhs)
+            }
+        }
+    }
+
+    fn binary(op: BinaryOp, lhs: &Value, rhs: &Value) -> Result<Value, BtError> {
+        match op {
+            BinaryOp::Eq => return Ok(Value::Bool(values_eq(lhs, rhs))),
+            BinaryOp::Ne => return Ok(Value::Bool(!values_eq(lhs, rhs))),
+            _ => {}
+        }
+
+        let numbers = as_number(lhs).zip(as_number(rhs));
+
+        if let Some((l, r)) = numbers {
+            return Ok(match op {
+                BinaryOp::Lt => Value::Bool(l < r),
+                BinaryOp::Le => Value::Bool(l <= r),
+                BinaryOp::Gt => Value::Bool(l > r),
+                BinaryOp::Ge => Value::Bool(l >= r),
+                BinaryOp::Add => number_value(l + r),
+                BinaryOp::Sub => number_value(l - r),
+                BinaryOp::Mul => number_value(l * r),
+                BinaryOp::Div if r == 0.0 => {
+                    return Err(BtError::Raw("division by zero".to_string()))
+                }
+                BinaryOp::Div => number_value(l / r),
+                BinaryOp::Rem if r == 0.0 => {
+                    return Err(BtError::Raw("division by zero".to_string()))
+                }
+                BinaryOp::Rem => number_value(l % r),
+                BinaryOp::Eq | BinaryOp::Ne | BinaryOp::And | BinaryOp::Or => unreachable!(),
+            });
+        }
+
+        match (op, lhs, rhs) {
+            (BinaryOp::Add, Value::String(l), Value::String(r)) => {
+                Ok(Value::String(format!("{l}{r}")))
+            }
+            (BinaryOp::Lt, Value::String(l), Value::String(r)) => Ok(Value::Bool(l < r)),
+            (BinaryOp::Le, Value::String(l), Value::String(r)) => Ok(Value::Bool(l <= r)),
+            (BinaryOp::Gt, Value::String(l), Value::String(r)) => Ok(Value::Bool(l > r)),
+            (BinaryOp::Ge, Value::String(l), Value::String(r)) => Ok(Value::Bool(l >= r)),
+            _ => Err(BtError::Raw(format!(
+                "invalid operands: {lhs} {op:?} {rhs}"
+            ))),
+        }
+    }
+
+    /// Evaluate to a boolean, for conditions.
+    pub fn eval_bool(&self, lookup: &dyn Fn(&str) -> Option<Value>) -> Result<bool, BtError> {
+        as_bool(&self.eval(lookup)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    fn eval(s: &str) -> Result<Value, BtError> {
+        let vars = json!({"battery": "25", "mode": "auto", "armed": true, "count": 3});
+
+        parse(s)?.eval(&|key| vars.get(key).cloned())
+    }
+
+    #[test]
+    fn test_eval() {
+        assert_eq!(eval("battery > 20 && mode == 'auto'").unwrap(), json!(true));
+        assert_eq!(eval("{battery} <= 20 || !armed").unwrap(), json!(false));
+        assert_eq!(eval("1 + 2 * 3 - -1").unwrap(), json!(8));
+        assert_eq!(eval("(1 + 2) * count / 2").unwrap(), json!(4.5));
+        assert_eq!(eval("mode + \"_mode\"").unwrap(), json!("auto_mode"));
+        assert_eq!(eval("count == '3'").unwrap(), json!(true));
+
+        assert!(eval("missing > 1").is_err());
+        assert!(eval("battery > ").is_err());
+        assert!(eval("(1 + 2").is_err());
+        assert!(eval("count = 1").is_err());
+        assert!(eval("1 / 0").is_err());
+    }
+
+    #[test]
+    fn test_script() {
+        let vars = json!({"count": "1", "battery": 40});
+        let lookup = |key: &str| vars.get(key).cloned();
+
+        let script =
+            parse_script("count = count + 1; ok := battery > 30; total := count * 10;").unwrap();
+        assert_eq!(
+            script.execute(&lookup).unwrap(),
+            vec![
+                ("count".to_string(), json!(2)),
+                ("ok".to_string(), json!(true)),
+                ("total".to_string(), json!(20)),
+            ]
+        );
+
+        assert!(parse_script("missing = 1")
+            .unwrap()
+            .execute(&lookup)
+            .is_err());
+        assert!(parse_script("count := 1 2").is_err());
+    }
+}
@@ -1,77 +1,248 @@
 use std::{
+    borrow::Cow,
     collections::{HashMap, HashSet},
     ops::Deref,
+    path::Path,
+    sync::Arc,
 };
 
+use parking_lot::RwLock;
 use regex::Regex;
+use serde::Deserialize;
 
-use crate::node::DataProxy;
+use crate::node::{Blackboard, DataProxy};
 use crate::{
     node::{
-        action::{ActionNodeImpl, ActionWrapper, SetBlackboard},
-        composite::{CompositeNodeImpl, CompositeWrapper, Parallel, Selector, Sequence},
+        action::{
+            ActionNodeImpl, ActionWrapper, PopFromQueue, PublishEvent, PushToQueue, QueueSize,
+            Script, SetBlackboard, Sleep, UnsetBlackboard, WaitForEvent, SCRIPT_CODE,
+        },
+        composite::{
+            CompositeNodeImpl, CompositeWrapper, ManualSelector, Parallel, ParallelAll,
+            RecoveryNode, Selector, Sequence, Switch, WeightedRandomSelector,
+            PARALLEL_CONFLICT_POLICY, PARALLEL_COPY_ON_WRITE, SEQUENCE_RESTART_ON_FAILURE,
+        },
+        condition::{ConditionNodeImpl, ConditionWrapper},
         decorator::{
-            DecoratorNodeImpl, DecoratorWrapper, ForceFailure, ForceSuccess, Inverter, Repeat,
-            Retry, SubTree,
+            DecoratorNodeImpl, DecoratorWrapper, Delay, ForceFailure, ForceSuccess, Guard,
+            Inverter, LoopNode, Precondition, Repeat, Retry, StatusToBlackboard, SubTree,
+            TickBudget, Timeout,
         },
+        port::{PortDirection, PortsList},
+    },
+    parser::xml::{
+        push_attribute, ParseOptions, TreeDocument, NODE_BACKOFF_MSEC, NODE_FAILURE_IF, NODE_NAME,
+        NODE_RETRIES, NODE_SKIP_IF, NODE_SUCCESS_IF, NODE_TAGS, NODE_WHILE, SUBTREE_AUTOREMAP,
+        SUBTREE_PRIVATE,
     },
     BtError, NodeWrapper, TreeNodeWrapper,
 };
 
-type Decoratortcs = HashMap<
-    String,
-    Box<dyn Fn(DataProxy, Attrs, TreeNodeWrapper) -> OuterResult<DecoratorWrapper>>,
->;
-type ActionTcs =
-    HashMap<ActionRegex, Box<dyn Fn(&str, DataProxy, Attrs) -> OuterResult<ActionWrapper>>>;
+// document elements of `Factory::generate_schema`, node types follow
+const SCHEMA_HEAD: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+  <xs:element name="root">
+    <xs:complexType>
+      <xs:choice minOccurs="0" maxOccurs="unbounded">
+        <xs:element ref="BehaviorTree"/>
+        <xs:element ref="TreeNodesModel"/>
+        <xs:element ref="include"/>
+      </xs:choice>
+      <xs:attribute name="BTCPP_format" type="xs:string"/>
+      <xs:attribute name="main_tree_to_execute" type="xs:string"/>
+    </xs:complexType>
+  </xs:element>
+  <xs:element name="BehaviorTree">
+    <xs:complexType>
+      <xs:group ref="node"/>
+      <xs:attribute name="ID" type="xs:string" use="required"/>
+      <xs:attribute name="_description" type="xs:string"/>
+    </xs:complexType>
+  </xs:element>
+  <xs:element name="include">
+    <xs:complexType>
+      <xs:attribute name="path" type="xs:string" use="required"/>
+    </xs:complexType>
+  </xs:element>
+  <xs:element name="TreeNodesModel">
+    <xs:complexType>
+      <xs:sequence>
+        <xs:any minOccurs="0" maxOccurs="unbounded" processContents="skip"/>
+      </xs:sequence>
+    </xs:complexType>
+  </xs:element>
+"#;
+
+// attributes the parser accepts on every node
+const SCHEMA_NODE_ATTRIBUTES: [&str; 11] = [
+    SUBTREE_PRIVATE,
+    SUBTREE_AUTOREMAP,
+    NODE_TAGS,
+    NODE_RETRIES,
+    NODE_BACKOFF_MSEC,
+    NODE_FAILURE_IF,
+    NODE_SUCCESS_IF,
+    NODE_SKIP_IF,
+    NODE_WHILE,
+    "_description",
+    "_comment",
+];
+
+type Decoratortcs = HashMap<String, DecoratorCons>;
+type ActionTcs = HashMap<ActionRegex, BoxActionCons>;
+type ConditionTcs = HashMap<ActionRegex, BoxConditionCons>;
+
+/// Bounds enforced while building a tree, protecting against huge or endlessly
+/// nested (e.g. self referencing SubTree) documents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildLimits {
+    pub max_depth: usize,
+    pub max_nodes: usize,
+}
+
+impl Default for BuildLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: 64,
+            // node uids are u16
+            max_nodes: u16::MAX as usize,
+        }
+    }
+}
 
 pub struct Factory {
-    composite_tcs: HashMap<String, Box<dyn Fn(DataProxy, Attrs) -> CompositeWrapper>>,
+    composite_tcs: HashMap<String, CompositeCons>,
     decorator_tcs: Decoratortcs,
     action_node_tcs: ActionTcs,
+    condition_node_tcs: ConditionTcs,
+    build_limits: BuildLimits,
+    config_vars: HashMap<String, String>,
+    parallel_build: bool,
+    aliases: HashMap<String, String>,
+    port_defaults: HashMap<String, Attrs>,
+    text_ports: HashMap<String, String>,
+    // registered BehaviorTrees by ID, with the document defining them
+    trees: HashMap<String, Arc<TreeDocument>>,
+}
+
+/// Deployment specific tweaks applied on top of the registered node types,
+/// see [`Factory::apply_manifest`].
+///
+/// ```toml
+/// [aliases]
+/// Wait = "Sleep"
+///
+/// [defaults.Wait]
+/// msec = "100"
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+pub struct FactoryManifest {
+    /// XML element name to an already registered node type
+    #[serde(default)]
+    pub aliases: HashMap<String, String>,
+    /// Port values used when an element doesn't set them, keyed by element name
+    #[serde(default)]
+    pub defaults: HashMap<String, HashMap<String, String>>,
+}
+
+impl FactoryManifest {
+    pub fn from_json_str(s: &str) -> Result<Self, BtError> {
+        serde_json::from_str(s).map_err(|e| BtError::Raw(format!("invalid manifest: err= {e}")))
+    }
+
+    #[cfg(feature = "toml")]
+    pub fn from_toml_str(s: &str) -> Result<Self, BtError> {
+        toml::from_str(s).map_err(|e| BtError::Raw(format!("invalid manifest: err= {e}")))
+    }
 }
 
 type Attrs = HashMap<String, String>;
 type OuterError = Box<dyn std::error::Error + Send + Sync>;
 type OuterResult<T> = std::result::Result<T, OuterError>;
 
-fn boxify_composite<T, F>(cons: F) -> Box<dyn Fn(DataProxy, Attrs) -> CompositeWrapper>
+fn boxify_composite<T, F>(cons: F) -> CompositeCons
 where
-    F: 'static + Fn(&Attrs) -> T,
+    F: 'static + Send + Sync + Fn(&Attrs) -> T,
     T: 'static + CompositeNodeImpl,
 {
-    Box::new(move |data_proxy, attrs| {
-        let node_wrapper = Box::new(cons(&attrs));
+    NodeCons::new(
+        Box::new(move |data_proxy, attrs| {
+            let node_wrapper = Box::new(cons(&attrs));
 
-        CompositeWrapper::new(data_proxy, node_wrapper)
-    })
+            CompositeWrapper::new(data_proxy, node_wrapper)
+        }),
+        T::provided_ports(),
+    )
 }
 
-fn boxify_decorator<T, F>(
-    cons: F,
-) -> Box<dyn Fn(DataProxy, Attrs, TreeNodeWrapper) -> OuterResult<DecoratorWrapper>>
+fn boxify_decorator<T, F>(cons: F) -> DecoratorCons
 where
-    F: 'static + Fn(&Attrs) -> OuterResult<T>,
+    F: 'static + Send + Sync + Fn(&Attrs) -> OuterResult<T>,
     T: 'static + DecoratorNodeImpl,
 {
-    Box::new(move |data_proxy, attrs, inner_node| {
-        let node_wrapper = Box::new(cons(&attrs)?);
-        Ok(DecoratorWrapper::new(data_proxy, node_wrapper, inner_node))
-    })
+    NodeCons::new(
+        Box::new(move |data_proxy, attrs, inner_node| {
+            let node_wrapper = Box::new(cons(&attrs)?);
+            Ok(DecoratorWrapper::new(data_proxy, node_wrapper, inner_node))
+        }),
+        T::provided_ports(),
+    )
+}
+
+/// Constructor of a node type together with the ports it declares.
+pub struct NodeCons<F: ?Sized> {
+    build: Box<F>,
+    ports: Option<PortsList>,
+}
+
+impl<F: ?Sized> NodeCons<F> {
+    fn new(build: Box<F>, ports: Option<PortsList>) -> Self {
+        Self { build, ports }
+    }
+
+    pub fn provided_ports(&self) -> Option<&PortsList> {
+        self.ports.as_ref()
+    }
 }
 
-type BoxActionCons = Box<dyn Fn(&str, DataProxy, Attrs) -> OuterResult<ActionWrapper>>;
+type CompositeCons = NodeCons<dyn Fn(DataProxy, Attrs) -> CompositeWrapper + Send + Sync>;
+type DecoratorCons = NodeCons<
+    dyn Fn(DataProxy, Attrs, TreeNodeWrapper) -> OuterResult<DecoratorWrapper> + Send + Sync,
+>;
+type BoxActionCons =
+    NodeCons<dyn Fn(&str, DataProxy, Attrs) -> OuterResult<ActionWrapper> + Send + Sync>;
+
+type BoxConditionCons =
+    NodeCons<dyn Fn(&str, DataProxy, Attrs) -> OuterResult<ConditionWrapper> + Send + Sync>;
+
+pub fn boxify_condition<T, F>(cons: F) -> BoxConditionCons
+where
+    F: 'static + Send + Sync + Fn(&str, Attrs) -> OuterResult<T>,
+    T: 'static + ConditionNodeImpl,
+{
+    NodeCons::new(
+        Box::new(move |type_name, data_proxy, attrs| {
+            let node = Box::new(cons(type_name, attrs)?);
+
+            Ok(ConditionWrapper::new(data_proxy, node))
+        }),
+        T::provided_ports(),
+    )
+}
 
 pub fn boxify_action<T, F>(cons: F) -> BoxActionCons
 where
-    F: 'static + Fn(&str, Attrs) -> OuterResult<T>,
+    F: 'static + Send + Sync + Fn(&str, Attrs) -> OuterResult<T>,
     T: 'static + ActionNodeImpl,
 {
-    Box::new(move |type_name, data_proxy, attrs| {
-        let res = cons(type_name, attrs)?;
+    NodeCons::new(
+        Box::new(move |type_name, data_proxy, attrs| {
+            let res = cons(type_name, attrs)?;
 
-        Ok(ActionWrapper::new(data_proxy, Box::new(res)))
-    })
+            Ok(ActionWrapper::new(data_proxy, Box::new(res)))
+        }),
+        T::provided_ports(),
+    )
 }
 
 #[derive(Clone, Debug)]
@@ -104,6 +275,26 @@ impl Deref for ActionRegex {
     }
 }
 
+/// Separator of a namespace and a node type name, e.g. `nav.MoveTo`. The XML
+/// prefix form `<nav:MoveTo/>` resolves to the same type.
+pub const NAMESPACE_SEPARATOR: char = '.';
+
+impl ActionRegex {
+    /// Pattern matching exactly `namespace.type_name`, so node types of
+    /// different crates can share a name. Unanchored patterns like `MoveTo`
+    /// also match namespaced names, register plain names as `^MoveTo$`.
+    pub fn namespaced(namespace: &str, type_name: &str) -> Result<Self, BtError> {
+        format!(
+            "^{}{}{}$",
+            regex::escape(namespace),
+            regex::escape(&NAMESPACE_SEPARATOR.to_string()),
+            regex::escape(type_name)
+        )
+        .as_str()
+        .try_into()
+    }
+}
+
 impl PartialEq for ActionRegex {
     fn eq(&self, other: &Self) -> bool {
         self.regex.as_str() == other.regex.as_str()
@@ -120,28 +311,414 @@ impl std::hash::Hash for ActionRegex {
 
 impl Factory {
     pub fn composite_types(&self) -> HashSet<&str> {
-        self.composite_tcs.keys().map(|a| a.as_str()).collect()
+        self.composite_tcs
+            .keys()
+            .map(|a| a.as_str())
+            .chain(self.aliases_of(|t| self.composite_tcs.contains_key(t)))
+            .collect()
     }
 
     pub fn decorator_types(&self) -> HashSet<&str> {
-        self.decorator_tcs.keys().map(|a| a.as_str()).collect()
+        self.decorator_tcs
+            .keys()
+            .map(|a| a.as_str())
+            .chain(self.aliases_of(|t| self.decorator_tcs.contains_key(t)))
+            .collect()
     }
 
-    fn register_composite_type(
-        &mut self,
-        type_name: String,
-        constructor: Box<dyn Fn(DataProxy, Attrs) -> CompositeWrapper>,
-    ) {
+    fn aliases_of<'a>(
+        &'a self,
+        is_target: impl Fn(&str) -> bool + 'a,
+    ) -> impl Iterator<Item = &'a str> + 'a {
+        self.aliases
+            .iter()
+            .filter(move |(_, target)| is_target(target))
+            .map(|(alias, _)| alias.as_str())
+    }
+
+    fn resolve_alias<'a>(&'a self, type_name: &'a str) -> &'a str {
+        self.aliases
+            .get(type_name)
+            .map(|t| t.as_str())
+            .unwrap_or(type_name)
+    }
+
+    // element defaults first, then the defaults of the aliased type and
+    // finally the defaults declared by the type's input ports
+    fn with_port_defaults(&self, type_name: &str, mut attrs: Attrs) -> Attrs {
+        for name in [type_name, self.resolve_alias(type_name)] {
+            if let Some(defaults) = self.port_defaults.get(name) {
+                for (key, value) in defaults {
+                    attrs.entry(key.clone()).or_insert_with(|| value.clone());
+                }
+            }
+        }
+
+        for port in self.provided_ports(type_name).into_iter().flatten() {
+            if port.direction == PortDirection::Output {
+                continue;
+            }
+
+            if let Some(default) = &port.default {
+                attrs
+                    .entry(port.name.clone())
+                    .or_insert_with(|| default.clone());
+            }
+        }
+
+        attrs
+    }
+
+    /// Register the aliases and port defaults of `manifest`, aliases must point
+    /// at already registered types.
+    pub fn apply_manifest(&mut self, manifest: FactoryManifest) -> Result<(), BtError> {
+        for (alias, target) in &manifest.aliases {
+            let known = self.composite_tcs.contains_key(target)
+                || self.decorator_tcs.contains_key(target)
+                || self.is_action_registered(target)
+                || self.is_condition_registered(target);
+
+            if !known {
+                return Err(BtError::Raw(format!(
+                    "alias to unknown node type: alias= {alias} type= {target}"
+                )));
+            }
+        }
+
+        self.aliases.extend(manifest.aliases);
+        for (name, defaults) in manifest.defaults {
+            self.port_defaults.entry(name).or_default().extend(defaults);
+        }
+
+        Ok(())
+    }
+
+    // Groot2 category of a registered node type
+    fn model_category(&self, type_name: &str) -> Option<&'static str> {
+        let resolved = self.resolve_alias(type_name);
+
+        if self.composite_tcs.contains_key(resolved) {
+            Some("Control")
+        } else if self.decorator_tcs.contains_key(resolved) {
+            Some("Decorator")
+        } else if self.is_condition_registered(resolved) {
+            Some("Condition")
+        } else if self.is_action_registered(resolved) {
+            Some("Action")
+        } else {
+            None
+        }
+    }
+
+    /// `<TreeNodesModel>` block with the registered node types and their ports,
+    /// for the palette of Groot2. Action and condition patterns that aren't a
+    /// plain name are left out, aliases are listed like their target.
+    pub fn export_tree_nodes_model(&self) -> String {
+        let mut xml = String::from("<TreeNodesModel>\n");
+        for type_name in self.element_type_names() {
+            let type_name = type_name.as_ref();
+            // a SubTree is modeled by its tree, not as a node type
+            if type_name == "SubTree" {
+                continue;
+            }
+            let Some(category) = self.model_category(type_name) else {
+                continue;
+            };
+
+            xml.push_str("    <");
+            xml.push_str(category);
+            push_attribute(&mut xml, "ID", type_name);
+
+            let ports = self.provided_ports(type_name).filter(|p| !p.is_empty());
+            let Some(ports) = ports else {
+                xml.push_str("/>\n");
+                continue;
+            };
+            xml.push_str(">\n");
+
+            let defaults = self.with_port_defaults(type_name, Attrs::new());
+            for port in ports {
+                let element = match port.direction {
+                    PortDirection::Input => "input_port",
+                    PortDirection::Output => "output_port",
+                    PortDirection::InOut => "inout_port",
+                };
+
+                xml.push_str("        <");
+                xml.push_str(element);
+                push_attribute(&mut xml, "name", &port.name);
+                push_attribute(&mut xml, "type", &port.type_name);
+                if let Some(default) = defaults.get(&port.name) {
+                    push_attribute(&mut xml, "default", default);
+                }
+
+                if port.description.is_empty() {
+                    xml.push_str("/>\n");
+                } else {
+                    xml.push('>');
+                    xml.push_str(&quick_xml::escape::escape(port.description.as_str()));
+                    xml.push_str(&format!("</{element}>\n"));
+                }
+            }
+
+            xml.push_str(&format!("    </{category}>\n"));
+        }
+        xml.push_str("</TreeNodesModel>\n");
+
+        xml
+    }
+
+    /// XML schema of tree documents written with the registered node types, for
+    /// editors to validate and autocomplete tree files. Like
+    /// [`Factory::export_tree_nodes_model`], only plain action and condition
+    /// patterns are listed. Port values are `xs:string`, they may be blackboard
+    /// references.
+    pub fn generate_schema(&self) -> String {
+        let type_names = self.element_type_names();
+
+        let mut xsd = String::from(SCHEMA_HEAD);
+
+        xsd.push_str("  <xs:attributeGroup name=\"nodeAttributes\">\n");
+        for key in SCHEMA_NODE_ATTRIBUTES {
+            xsd.push_str("    <xs:attribute");
+            push_attribute(&mut xsd, "name", key);
+            xsd.push_str(" type=\"xs:string\"/>\n");
+        }
+        xsd.push_str("  </xs:attributeGroup>\n");
+
+        xsd.push_str("  <xs:group name=\"node\">\n    <xs:choice>\n");
+        for type_name in &type_names {
+            xsd.push_str("      <xs:element");
+            push_attribute(&mut xsd, "ref", type_name);
+            xsd.push_str("/>\n");
+        }
+        xsd.push_str("    </xs:choice>\n  </xs:group>\n");
+
+        for type_name in &type_names {
+            self.push_schema_element(&mut xsd, type_name);
+        }
+        xsd.push_str("</xs:schema>\n");
+
+        xsd
+    }
+
+    fn push_schema_element(&self, xsd: &mut String, type_name: &str) {
+        let Some(category) = self.model_category(type_name) else {
+            return;
+        };
+
+        xsd.push_str("  <xs:element");
+        push_attribute(xsd, "name", type_name);
+        xsd.push_str(">\n    <xs:complexType");
+        if self.text_port(type_name).is_some() {
+            xsd.push_str(" mixed=\"true\"");
+        }
+        xsd.push_str(">\n");
+
+        match category {
+            "Control" => xsd.push_str(
+                "      <xs:group ref=\"node\" minOccurs=\"0\" maxOccurs=\"unbounded\"/>\n",
+            ),
+            "Decorator" if type_name != "SubTree" => {
+                xsd.push_str("      <xs:group ref=\"node\"/>\n")
+            }
+            _ => {}
+        }
+
+        if type_name == "SubTree" {
+            xsd.push_str("      <xs:attribute name=\"ID\" type=\"xs:string\" use=\"required\"/>\n");
+        }
+
+        let ports = self.provided_ports(type_name);
+        for port in ports.into_iter().flatten() {
+            xsd.push_str("      <xs:attribute");
+            push_attribute(xsd, "name", &port.name);
+            xsd.push_str(" type=\"xs:string\"");
+
+            if port.description.is_empty() {
+                xsd.push_str("/>\n");
+            } else {
+                xsd.push_str(">\n        <xs:annotation><xs:documentation>");
+                xsd.push_str(&quick_xml::escape::escape(port.description.as_str()));
+                xsd.push_str("</xs:documentation></xs:annotation>\n      </xs:attribute>\n");
+            }
+        }
+
+        // `name` is an instance label unless the type declares it as port
+        if !ports.is_some_and(|ports| ports.iter().any(|port| port.name == NODE_NAME)) {
+            xsd.push_str("      <xs:attribute name=\"name\" type=\"xs:string\"/>\n");
+        }
+        xsd.push_str("      <xs:attributeGroup ref=\"nodeAttributes\"/>\n");
+        // types without declared ports accept any attribute
+        if ports.is_none() {
+            xsd.push_str("      <xs:anyAttribute processContents=\"skip\"/>\n");
+        }
+
+        xsd.push_str("    </xs:complexType>\n  </xs:element>\n");
+    }
+
+    // node types that can be written as elements: registered types with a
+    // plain name and aliases, sorted
+    fn element_type_names(&self) -> Vec<Cow<'_, str>> {
+        let mut type_names: Vec<Cow<str>> = self
+            .composite_tcs
+            .keys()
+            .chain(self.decorator_tcs.keys())
+            .map(|t| Cow::Borrowed(t.as_str()))
+            .chain(
+                self.action_node_tcs
+                    .keys()
+                    .chain(self.condition_node_tcs.keys())
+                    .filter_map(plain_type_name),
+            )
+            .chain(self.aliases.keys().map(|t| Cow::Borrowed(t.as_str())))
+            .collect();
+        type_names.sort_unstable();
+        type_names.dedup();
+
+        type_names
+    }
+
+    pub fn build_limits(&self) -> BuildLimits {
+        self.build_limits
+    }
+
+    pub fn set_build_limits(&mut self, build_limits: BuildLimits) {
+        self.build_limits = build_limits;
+    }
+
+    pub fn parallel_build(&self) -> bool {
+        self.parallel_build
+    }
+
+    /// Build the children of the main tree's root composite concurrently.
+    /// Uids are reassigned depth-first afterwards, so they stay deterministic.
+    pub fn set_parallel_build(&mut self, parallel_build: bool) {
+        self.parallel_build = parallel_build;
+    }
+
+    /// Value for `${name}` in port values, takes precedence over the environment.
+    pub fn set_config_var(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.config_vars.insert(name.into(), value.into());
+    }
+
+    pub fn config_vars(&self) -> &HashMap<String, String> {
+        &self.config_vars
+    }
+
+    /// Feed the text or CDATA body of `type_name` elements into `port`, e.g.
+    /// `<Script><![CDATA[ ... ]]></Script>` for multi-line code.
+    pub fn set_text_port(&mut self, type_name: impl Into<String>, port: impl Into<String>) {
+        self.text_ports.insert(type_name.into(), port.into());
+    }
+
+    pub fn text_port(&self, type_name: &str) -> Option<&str> {
+        [type_name, self.resolve_alias(type_name)]
+            .into_iter()
+            .find_map(|name| self.text_ports.get(name))
+            .map(String::as_str)
+    }
+
+    /// Register every BehaviorTree of `xml`, to be built with
+    /// [`Factory::create_tree`] and used as SubTree by any tree this factory
+    /// builds. SubTrees resolve within their own document first. A tree
+    /// registered again under the same ID replaces the earlier one.
+    pub fn register_tree_from_text(&mut self, xml: &str) -> Result<(), BtError> {
+        self.register_tree_document(TreeDocument::from_xml_str(xml)?);
+
+        Ok(())
+    }
+
+    /// Like [`Factory::register_tree_from_text`], includes resolve against the
+    /// directory of the file.
+    pub fn register_tree_from_file(&mut self, path: impl AsRef<Path>) -> Result<(), BtError> {
+        self.register_tree_document(TreeDocument::from_xml_file(path)?);
+
+        Ok(())
+    }
+
+    fn register_tree_document(&mut self, doc: TreeDocument) {
+        let doc = Arc::new(doc);
+
+        for id in doc.tree_ids() {
+            self.trees.insert(id.to_string(), doc.clone());
+        }
+    }
+
+    /// IDs of the registered BehaviorTrees, sorted.
+    pub fn registered_tree_ids(&self) -> Vec<&str> {
+        let mut ids: Vec<_> = self.trees.keys().map(String::as_str).collect();
+        ids.sort_unstable();
+
+        ids
+    }
+
+    pub(crate) fn registered_tree(&self, id: &str) -> Option<&TreeDocument> {
+        self.trees.get(id).map(Arc::as_ref)
+    }
+
+    /// Build a registered BehaviorTree with `bb` as its blackboard.
+    pub fn create_tree(
+        &self,
+        id: &str,
+        bb: Arc<RwLock<Blackboard>>,
+    ) -> Result<Option<TreeNodeWrapper>, BtError> {
+        let doc = self
+            .trees
+            .get(id)
+            .ok_or_else(|| BtError::Raw(format!("can't find tree: {id}")))?;
+
+        doc.create_tree_with_blackboard(self, id, bb, ParseOptions::default())
+    }
+
+    pub fn is_action_registered(&self, type_name: &str) -> bool {
+        let type_name = self.resolve_alias(type_name);
+
+        self.action_node_tcs
+            .keys()
+            .any(|type_regex| type_regex.is_match(type_name))
+    }
+
+    pub fn is_condition_registered(&self, type_name: &str) -> bool {
+        let type_name = self.resolve_alias(type_name);
+
+        self.condition_node_tcs
+            .keys()
+            .any(|type_regex| type_regex.is_match(type_name))
+    }
+
+    /// Ports declared by the node type, `None` when it accepts any attribute
+    /// or isn't registered.
+    pub fn provided_ports(&self, type_name: &str) -> Option<&PortsList> {
+        let type_name = self.resolve_alias(type_name);
+
+        if let Some(c) = self.composite_tcs.get(type_name) {
+            return c.provided_ports();
+        }
+
+        if let Some(c) = self.decorator_tcs.get(type_name) {
+            return c.provided_ports();
+        }
+
+        if let Some((_, c)) = self
+            .condition_node_tcs
+            .iter()
+            .find(|(type_regex, _)| type_regex.is_match(type_name))
+        {
+            return c.provided_ports();
+        }
+
+        self.action_node_tcs
+            .iter()
+            .find(|(type_regex, _)| type_regex.is_match(type_name))
+            .and_then(|(_, c)| c.provided_ports())
+    }
+
+    fn register_composite_type(&mut self, type_name: String, constructor: CompositeCons) {
         self.composite_tcs.insert(type_name, constructor);
     }
 
-    fn register_decorator_type(
-        &mut self,
-        type_name: String,
-        constructor: Box<
-            dyn Fn(DataProxy, Attrs, TreeNodeWrapper) -> OuterResult<DecoratorWrapper>,
-        >,
-    ) {
+    fn register_decorator_type(&mut self, type_name: String, constructor: DecoratorCons) {
         self.decorator_tcs.insert(type_name, constructor);
     }
 
@@ -152,19 +729,30 @@ impl Factory {
     ) {
         self.action_node_tcs.insert(type_name_pat, constructor);
     }
+    pub fn register_condition_node_type(
+        &mut self,
+        type_name_pat: ActionRegex,
+        constructor: BoxConditionCons,
+    ) {
+        self.condition_node_tcs.insert(type_name_pat, constructor);
+    }
+
     pub fn build_composite(
         &self,
         type_name: &str,
         mut data_proxy: DataProxy,
         attrs: Attrs,
     ) -> Option<CompositeWrapper> {
+        let attrs = self.with_port_defaults(type_name, attrs);
+        let type_name = self.resolve_alias(type_name);
+
         for (key, value) in attrs.clone() {
             data_proxy.add_input(key, value);
         }
 
         self.composite_tcs
             .get(type_name)
-            .map(|c| c(data_proxy, attrs))
+            .map(|c| (c.build)(data_proxy, attrs))
     }
 
     pub fn build_decorator(
@@ -174,13 +762,16 @@ impl Factory {
         attrs: Attrs,
         node: TreeNodeWrapper,
     ) -> Option<DecoratorWrapper> {
+        let attrs = self.with_port_defaults(type_name, attrs);
+        let type_name = self.resolve_alias(type_name);
+
         for (key, value) in attrs.clone() {
             data_proxy.add_input(key, value);
         }
 
         self.decorator_tcs
             .get(type_name)
-            .and_then(|c| match c(data_proxy, attrs, node) {
+            .and_then(|c| match (c.build)(data_proxy, attrs, node) {
                 Ok(v) => Some(v),
                 Err(e) => {
                     tracing::error!("create {type_name} meet failure: err= {e}");
@@ -195,13 +786,17 @@ impl Factory {
         mut data_proxy: DataProxy,
         attrs: Attrs,
     ) -> Option<TreeNodeWrapper> {
+        let attrs = self.with_port_defaults(type_name, attrs);
+        let type_name = self.resolve_alias(type_name);
+
         for (key, value) in attrs.clone() {
             data_proxy.add_input(key, value);
         }
 
         for (type_regex, constructor) in &self.action_node_tcs {
             if type_regex.is_match(type_name) {
-                let action_wrapper = match constructor(type_name, data_proxy, attrs.clone()) {
+                let action_wrapper = match (constructor.build)(type_name, data_proxy, attrs.clone())
+                {
                     Ok(n) => n,
                     Err(e) => {
                         tracing::error!("run action builder meet failure: err= {e}");
@@ -221,6 +816,55 @@ impl Factory {
         //         .map(|c| c(attrs))
         //         .map(|a| TreeNodeWrapper::new(NodeWrapper::Action(a)))
     }
+
+    pub fn build_condition(
+        &self,
+        type_name: &str,
+        mut data_proxy: DataProxy,
+        attrs: Attrs,
+    ) -> Option<TreeNodeWrapper> {
+        let attrs = self.with_port_defaults(type_name, attrs);
+        let type_name = self.resolve_alias(type_name);
+
+        for (key, value) in attrs.clone() {
+            data_proxy.add_input(key, value);
+        }
+
+        let (_, constructor) = self
+            .condition_node_tcs
+            .iter()
+            .find(|(type_regex, _)| type_regex.is_match(type_name))?;
+
+        match (constructor.build)(type_name, data_proxy, attrs) {
+            Ok(condition_wrapper) => Some(TreeNodeWrapper::new(NodeWrapper::Condition(
+                condition_wrapper,
+            ))),
+            Err(e) => {
+                tracing::error!("run condition builder meet failure: err= {e}");
+                None
+            }
+        }
+    }
+}
+
+// `^Name$`, `Name` or `^ns\.Name$`, patterns matching several names have no
+// model entry
+fn plain_type_name(type_regex: &ActionRegex) -> Option<Cow<'_, str>> {
+    let pattern = type_regex.as_str();
+    let pattern = pattern.strip_prefix('^').unwrap_or(pattern);
+    let pattern = pattern.strip_suffix('$').unwrap_or(pattern);
+
+    let is_plain = |name: &str| {
+        !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    };
+
+    if is_plain(pattern) {
+        return Some(Cow::Borrowed(pattern));
+    }
+
+    let (namespace, type_name) = pattern.split_once("\\.")?;
+    (is_plain(namespace) && is_plain(type_name))
+        .then(|| Cow::Owned(format!("{namespace}{NAMESPACE_SEPARATOR}{type_name}")))
 }
 
 impl Default for Factory {
@@ -229,11 +873,49 @@ impl Default for Factory {
             composite_tcs: HashMap::new(),
             decorator_tcs: HashMap::new(),
             action_node_tcs: HashMap::new(),
+            condition_node_tcs: HashMap::new(),
+            build_limits: BuildLimits::default(),
+            config_vars: HashMap::new(),
+            parallel_build: false,
+            aliases: HashMap::new(),
+            port_defaults: HashMap::new(),
+            text_ports: HashMap::new(),
+            trees: HashMap::new(),
         };
 
         fac.register_composite_type(
             "Sequence".to_string(),
-            boxify_composite(|_| Sequence::default()),
+            boxify_composite(|attrs| {
+                Sequence::new(
+                    attrs
+                        .get(SEQUENCE_RESTART_ON_FAILURE)
+                        .is_none_or(|v| v != "false"),
+                )
+            }),
+        );
+        fac.register_composite_type(
+            "SequenceWithMemory".to_string(),
+            boxify_composite(|_| Sequence::with_memory()),
+        );
+        fac.register_composite_type(
+            "Switch".to_string(),
+            boxify_composite(|_| Switch::default()),
+        );
+        fac.register_composite_type(
+            "ParallelAll".to_string(),
+            boxify_composite(|_| ParallelAll::default()),
+        );
+        fac.register_composite_type(
+            "ManualSelector".to_string(),
+            boxify_composite(|_| ManualSelector::default()),
+        );
+        fac.register_composite_type(
+            "WeightedRandomSelector".to_string(),
+            boxify_composite(|_| WeightedRandomSelector::default()),
+        );
+        fac.register_composite_type(
+            "RecoveryNode".to_string(),
+            boxify_composite(|_| RecoveryNode::default()),
         );
         fac.register_composite_type(
             "Fallback".to_string(),
@@ -241,7 +923,21 @@ impl Default for Factory {
         );
         fac.register_composite_type(
             "Parallel".to_string(),
-            boxify_composite(|_| Parallel::default()),
+            boxify_composite(|attrs| {
+                let copy_on_write = attrs
+                    .get(PARALLEL_COPY_ON_WRITE)
+                    .is_some_and(|v| v == "true");
+                let conflict_policy = attrs
+                    .get(PARALLEL_CONFLICT_POLICY)
+                    .and_then(|v| {
+                        v.parse()
+                            .map_err(|e| tracing::warn!("{e}, fallback to default"))
+                            .ok()
+                    })
+                    .unwrap_or_default();
+
+                Parallel::new(copy_on_write, conflict_policy)
+            }),
         );
 
         fac.register_decorator_type(
@@ -261,6 +957,34 @@ impl Default for Factory {
             "RetryUntilSuccessful".to_string(),
             boxify_decorator(|_| Ok(Retry::default())),
         );
+        fac.register_decorator_type(
+            "Delay".to_string(),
+            boxify_decorator(|_| Ok(Delay::default())),
+        );
+        fac.register_decorator_type(
+            "Timeout".to_string(),
+            boxify_decorator(|_| Ok(Timeout::default())),
+        );
+        fac.register_decorator_type(
+            "Precondition".to_string(),
+            boxify_decorator(|_| Ok(Precondition::default())),
+        );
+        fac.register_decorator_type(
+            "LoopNode".to_string(),
+            boxify_decorator(|_| Ok(LoopNode::default())),
+        );
+        fac.register_decorator_type(
+            "Guard".to_string(),
+            boxify_decorator(|_| Ok(Guard::default())),
+        );
+        fac.register_decorator_type(
+            "TickBudget".to_string(),
+            boxify_decorator(|_| Ok(TickBudget::default())),
+        );
+        fac.register_decorator_type(
+            "StatusToBlackboard".to_string(),
+            boxify_decorator(|_| Ok(StatusToBlackboard)),
+        );
         fac.register_decorator_type(
             "SubTree".to_string(),
             boxify_decorator(|attrs| {
@@ -276,6 +1000,51 @@ impl Default for Factory {
             "^SetBlackboard$".try_into().unwrap(),
             boxify_action(|_, _| Ok(SetBlackboard)),
         );
+        fac.register_action_node_type(
+            "^UnsetBlackboard$".try_into().unwrap(),
+            boxify_action(|_, _| Ok(UnsetBlackboard)),
+        );
+        fac.register_action_node_type(
+            "^PushToQueue$".try_into().unwrap(),
+            boxify_action(|_, _| Ok(PushToQueue)),
+        );
+        fac.register_action_node_type(
+            "^PopFromQueue$".try_into().unwrap(),
+            boxify_action(|_, _| Ok(PopFromQueue)),
+        );
+        fac.register_action_node_type(
+            "^QueueSize$".try_into().unwrap(),
+            boxify_action(|_, _| Ok(QueueSize)),
+        );
+        fac.register_action_node_type(
+            "^PublishEvent$".try_into().unwrap(),
+            boxify_action(|_, _| Ok(PublishEvent)),
+        );
+        fac.register_action_node_type(
+            "^WaitForEvent$".try_into().unwrap(),
+            boxify_action(|_, _| Ok(WaitForEvent::default())),
+        );
+        fac.register_action_node_type(
+            "^Script$".try_into().unwrap(),
+            boxify_action(|_, _| Ok(Script::default())),
+        );
+        fac.set_text_port("Script", SCRIPT_CODE);
+        fac.register_action_node_type(
+            "^Sleep$".try_into().unwrap(),
+            boxify_action(|_, _| Ok(Sleep::default())),
+        );
+        #[cfg(feature = "http")]
+        fac.register_action_node_type(
+            "^HttpRequest$".try_into().unwrap(),
+            boxify_action(|_, _| Ok(crate::node::http::HttpRequest::default())),
+        );
+        #[cfg(feature = "http")]
+        fac.set_text_port("HttpRequest", crate::node::http::HTTP_BODY);
+        #[cfg(feature = "process")]
+        fac.register_action_node_type(
+            "^RunCommand$".try_into().unwrap(),
+            boxify_action(|_, _| Ok(crate::node::process::RunCommand::default())),
+        );
 
         fac
     }
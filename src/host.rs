@@ -0,0 +1,151 @@
+//! Tree rebuilt from its XML file whenever the file changes.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{
+    factory::Factory, parser::xml::create_bt_tree_from_xml_file, BtError, NodeStatus, Result,
+    TreeNode, TreeNodeWrapper,
+};
+
+/// Owns a tree built from an XML file and rebuilds it when the file changes,
+/// for iterating on behaviors without restarting the process.
+///
+/// The file is polled on every [`TreeHost::tick`], includes are not watched.
+/// On reload the old tree is halted, then blackboard entries of scopes and
+/// completed statuses of nodes whose paths still exist move to the new tree.
+/// Typed entries set with `set_any` move along with the JSON ones. Nodes that
+/// were running start over, hooks installed on the old tree are dropped.
+pub struct TreeHost {
+    factory: Factory,
+    path: PathBuf,
+    stamp: Option<(SystemTime, u64)>,
+    tree: TreeNodeWrapper,
+}
+
+impl TreeHost {
+    pub fn new(factory: Factory, path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let stamp = file_stamp(&path);
+        let tree = build(&factory, &path)?;
+
+        Ok(Self {
+            factory,
+            path,
+            stamp,
+            tree,
+        })
+    }
+
+    pub fn tree(&self) -> &TreeNodeWrapper {
+        &self.tree
+    }
+
+    pub fn tree_mut(&mut self) -> &mut TreeNodeWrapper {
+        &mut self.tree
+    }
+
+    /// Reload if the file changed since the last build, returns whether the
+    /// tree was replaced. The old tree is kept when the new one fails to build.
+    pub fn reload_if_changed(&mut self) -> Result<bool> {
+        let stamp = file_stamp(&self.path);
+        if stamp == self.stamp {
+            return Ok(false);
+        }
+
+        self.stamp = stamp;
+        self.reload()?;
+
+        Ok(true)
+    }
+
+    /// Rebuild the tree from the file and carry over its state.
+    pub fn reload(&mut self) -> Result<()> {
+        let tree = build(&self.factory, &self.path)?;
+
+        let snapshot = self.tree.blackboard_snapshot();
+        let typed: HashMap<_, _> = self
+            .tree
+            .blackboard_scopes()
+            .into_iter()
+            .map(|(path, bb)| (path, bb.read().local_typed_entries()))
+            .collect();
+        let statuses = path_statuses(&self.tree);
+        self.tree.halt_tree();
+
+        for (path, bb) in tree.blackboard_scopes() {
+            if let Some(entries) = snapshot.scopes.get(&path) {
+                bb.write().restore_local(entries.clone());
+            }
+
+            for (key, entry) in typed.get(&path).into_iter().flatten() {
+                bb.read().store_entry(key, Some(entry.clone()));
+            }
+        }
+
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        tree.apply_recursive_visitor(&mut |node, _layer| {
+            let data_proxy = node.data_proxy_ref();
+            let idx = seen.entry(data_proxy.full_path().to_string()).or_default();
+
+            if let Some(status) = statuses
+                .get(data_proxy.full_path())
+                .and_then(|statuses| statuses.get(*idx))
+            {
+                if status.is_completed() {
+                    data_proxy.set_status(*status);
+                }
+            }
+
+            *idx += 1;
+        });
+
+        tracing::info!("tree reloaded: path= {}", self.path.display());
+        self.tree = tree;
+
+        Ok(())
+    }
+
+    /// Reload on change, then tick the tree. A failed reload is logged and the
+    /// current tree keeps running.
+    pub fn tick(&mut self) -> NodeStatus {
+        if let Err(e) = self.reload_if_changed() {
+            tracing::warn!(
+                "reload tree meet failure: path= {} err= {e}",
+                self.path.display()
+            );
+        }
+
+        self.tree.tick()
+    }
+}
+
+fn build(factory: &Factory, path: &Path) -> Result<TreeNodeWrapper> {
+    create_bt_tree_from_xml_file(factory, path)?
+        .ok_or_else(|| BtError::Raw(format!("no tree built: path= {}", path.display())))
+}
+
+// modification time alone can miss quick successive writes
+fn file_stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let meta = std::fs::metadata(path).ok()?;
+
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+// statuses of every node keyed by full path, siblings sharing a path in
+// depth-first order
+fn path_statuses(tree: &TreeNodeWrapper) -> HashMap<String, Vec<NodeStatus>> {
+    let mut statuses: HashMap<String, Vec<NodeStatus>> = HashMap::new();
+
+    tree.apply_recursive_visitor(&mut |node, _layer| {
+        statuses
+            .entry(node.data_proxy_ref().full_path().to_string())
+            .or_default()
+            .push(node.status());
+    });
+
+    statuses
+}
@@ -1,13 +1,28 @@
-use std::any::Any;
+use std::{any::Any, collections::BTreeMap, path::PathBuf, sync::Arc};
+
+use clock::Clock;
 
 use node::{
-    action::ActionWrapper, composite::CompositeWrapper, decorator::DecoratorWrapper, DataProxy,
+    action::ActionWrapper, composite::CompositeWrapper, condition::ConditionWrapper,
+    decorator::DecoratorWrapper, Blackboard, BlackboardSnapshot, DataProxy, StateNotif,
+    TransitionHook,
 };
+use parking_lot::RwLock;
+use runtime::sync::watch;
+use serde::Serialize;
 use thiserror::Error;
 
+pub mod clock;
+pub mod expr;
 pub mod factory;
+pub mod host;
+mod macros;
 pub mod node;
 pub mod parser;
+pub mod prelude;
+pub mod runner;
+pub mod runtime;
+pub mod testing;
 
 type Result<T> = std::result::Result<T, BtError>;
 
@@ -21,6 +36,57 @@ pub enum BtError {
     Str(#[from] std::str::Utf8Error),
     #[error("raw error {0}")]
     Raw(String),
+    // boxed, the tree builder recurses with this error in every frame
+    #[error(transparent)]
+    Port(Box<node::port::PortError>),
+    /// Error while building the element at `offset` of the XML document,
+    /// `line` and `column` are in `file` for elements of an included file
+    #[error("{source} at {}line {line} column {column}: {path}", display_file(.file))]
+    Parse {
+        offset: usize,
+        line: usize,
+        column: usize,
+        /// File the element was read from, `None` for documents given as a string
+        file: Option<PathBuf>,
+        /// Element names from the tree root down to the failing element
+        path: String,
+        source: Box<BtError>,
+    },
+    /// Error while building the node of a JSON tree at `pointer`, e.g.
+    /// `/trees/main/children/1`
+    #[error("{source} at json node: {pointer}")]
+    JsonNode {
+        pointer: String,
+        source: Box<BtError>,
+    },
+    #[error("unknown node types: {}", display_unknown_nodes(.0))]
+    UnknownNodes(Vec<parser::validation::UnknownNode>),
+    #[error("tree {limit} limit of {max} exceeded at: {path}")]
+    LimitExceeded {
+        limit: &'static str,
+        max: usize,
+        path: String,
+    },
+}
+
+fn display_file(file: &Option<PathBuf>) -> String {
+    file.as_ref()
+        .map(|file| format!("{} ", file.display()))
+        .unwrap_or_default()
+}
+
+fn display_unknown_nodes(nodes: &[parser::validation::UnknownNode]) -> String {
+    nodes
+        .iter()
+        .map(|node| format!("[{node}]"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+impl From<node::port::PortError> for BtError {
+    fn from(e: node::port::PortError) -> Self {
+        BtError::Port(Box::new(e))
+    }
 }
 
 #[derive(Default, PartialEq, Eq, Debug, Clone, Copy)]
@@ -30,25 +96,101 @@ pub enum NodeStatus {
     Success,
     Failure,
     Running,
+    /// Not ticked because of `_skipIf`/`_while`, parents step past it
+    Skipped,
 }
 
 impl NodeStatus {
     pub fn is_completed(&self) -> bool {
         self == &NodeStatus::Success || self == &NodeStatus::Failure
     }
+
+    /// Like `NodeStatus::from(res)`, but hands the error to `on_error` first,
+    /// e.g. to log it or store it on the blackboard.
+    pub fn from_result_with<T, E>(
+        res: std::result::Result<T, E>,
+        on_error: impl FnOnce(&E),
+    ) -> NodeStatus {
+        match res {
+            Ok(_) => NodeStatus::Success,
+            Err(e) => {
+                on_error(&e);
+                NodeStatus::Failure
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for NodeStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            NodeStatus::Idle => "IDLE",
+            NodeStatus::Success => "SUCCESS",
+            NodeStatus::Failure => "FAILURE",
+            NodeStatus::Running => "RUNNING",
+            NodeStatus::Skipped => "SKIPPED",
+        })
+    }
+}
+
+impl std::str::FromStr for NodeStatus {
+    type Err = BtError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "IDLE" => Ok(NodeStatus::Idle),
+            "SUCCESS" => Ok(NodeStatus::Success),
+            "FAILURE" => Ok(NodeStatus::Failure),
+            "RUNNING" => Ok(NodeStatus::Running),
+            "SKIPPED" => Ok(NodeStatus::Skipped),
+            _ => Err(BtError::Raw(format!("unknown node status: {s}"))),
+        }
+    }
 }
 
-#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+impl From<bool> for NodeStatus {
+    fn from(value: bool) -> Self {
+        if value {
+            NodeStatus::Success
+        } else {
+            NodeStatus::Failure
+        }
+    }
+}
+
+/// `Ok` maps to Success and `Err` to Failure, the values themselves are dropped.
+impl<T, E> From<std::result::Result<T, E>> for NodeStatus {
+    fn from(value: std::result::Result<T, E>) -> Self {
+        NodeStatus::from_result_with(value, |_| {})
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Serialize)]
 pub enum NodeType {
     Composite,
     Decorator,
     Action,
+    Condition,
 }
 
 pub enum NodeWrapper {
     Composite(CompositeWrapper),
     Decorator(DecoratorWrapper),
     Action(ActionWrapper),
+    Condition(ConditionWrapper),
+}
+
+/// Description of a single node, see [`TreeNodeWrapper::uid_map`].
+#[derive(PartialEq, Eq, Debug, Clone, Serialize)]
+pub struct NodeEntry {
+    pub uid: u16,
+    pub full_path: String,
+    pub name: Option<String>,
+    pub type_name: String,
+    pub node_type: NodeType,
+    pub tags: Vec<String>,
+    /// See [`DataProxy::metadata`]
+    pub metadata: BTreeMap<String, String>,
 }
 
 pub struct TreeNodeWrapper {
@@ -60,8 +202,8 @@ impl TreeNodeWrapper {
         self.data_proxy_ref().status()
     }
 
-    pub fn reset_status(&mut self) {
-        self.data_proxy_ref_mut().reset_status();
+    pub fn reset_status(&self) {
+        self.data_proxy_ref().reset_status();
     }
 
     pub fn new(node_wrapper: NodeWrapper) -> Self {
@@ -73,6 +215,7 @@ impl TreeNodeWrapper {
             NodeWrapper::Composite(_) => NodeType::Composite,
             NodeWrapper::Decorator(_) => NodeType::Decorator,
             NodeWrapper::Action(_) => NodeType::Action,
+            NodeWrapper::Condition(_) => NodeType::Condition,
         }
     }
 
@@ -81,6 +224,7 @@ impl TreeNodeWrapper {
             NodeWrapper::Composite(cp) => &cp.data_proxy,
             NodeWrapper::Decorator(dr) => &dr.data_proxy,
             NodeWrapper::Action(at) => &at.data_proxy,
+            NodeWrapper::Condition(cd) => &cd.data_proxy,
         }
     }
 
@@ -89,9 +233,36 @@ impl TreeNodeWrapper {
             NodeWrapper::Composite(cp) => &mut cp.data_proxy,
             NodeWrapper::Decorator(dr) => &mut dr.data_proxy,
             NodeWrapper::Action(at) => &mut at.data_proxy,
+            NodeWrapper::Condition(cd) => &mut cd.data_proxy,
         }
     }
 
+    // evaluates the inline `_skipIf`-style guards, halting a running node
+    // whose `_while` no longer holds
+    fn check_conditions(&mut self) -> Option<NodeStatus> {
+        let conditions = self.data_proxy_ref().conditions()?.clone();
+        let running = self.status() == NodeStatus::Running;
+
+        let status = match conditions.check(self.data_proxy_ref(), running) {
+            Ok(status) => status?,
+            Err(e) => {
+                self.data_proxy_ref().report_error(e);
+                NodeStatus::Failure
+            }
+        };
+
+        tracing::debug!(
+            "node guarded: path= {} status= {status:?}",
+            self.data_proxy_ref().full_path()
+        );
+        if running {
+            self.halt();
+            self.reset_status();
+        }
+
+        Some(status)
+    }
+
     pub fn uid(&self) -> u16 {
         self.data_proxy_ref().uid()
     }
@@ -104,6 +275,191 @@ impl TreeNodeWrapper {
         self.data_proxy_ref().path()
     }
 
+    /// Halt every running node and reset all statuses to `Idle`.
+    pub fn halt_tree(&mut self) {
+        self.halt();
+
+        self.apply_recursive_visitor_mut(&mut |node, _layer| {
+            node.reset_status();
+        });
+    }
+
+    /// Use `clock` as time source for this node and all of its descendants.
+    pub fn set_clock(&mut self, clock: Arc<Clock>) {
+        self.apply_recursive_visitor_mut(&mut |node, _layer| {
+            let data_proxy = node.data_proxy_ref_mut();
+            data_proxy.blackboard().set_clock(clock.clone());
+            data_proxy.set_clock(clock.clone());
+        });
+    }
+
+    /// Install `hook` on this node and all of its descendants.
+    pub fn add_transition_hook(&mut self, hook: TransitionHook) {
+        self.apply_recursive_visitor_mut(&mut |node, _layer| {
+            node.data_proxy_ref_mut().add_transition_hook(hook.clone());
+        });
+    }
+
+    /// Forward status changes of every node to `on_change` on `runtime`, until
+    /// the tree is dropped.
+    pub fn spawn_status_observer(
+        &self,
+        runtime: &dyn runtime::Runtime,
+        on_change: impl Fn(StateNotif) + Clone + Send + Sync + 'static,
+    ) {
+        self.apply_recursive_visitor(&mut |node, _layer| {
+            let mut rx = node.data_proxy_ref().add_observer();
+            let on_change = on_change.clone();
+
+            runtime.spawn(Box::pin(async move {
+                while rx.changed().await.is_ok() {
+                    let notif = *rx.borrow_and_update();
+                    on_change(notif);
+                }
+            }));
+        });
+    }
+
+    /// Status handles of every node keyed by uid, readable from other threads
+    /// while the tree keeps ticking.
+    pub fn status_handles(&self) -> BTreeMap<u16, node::StatusHandle> {
+        let mut handles = BTreeMap::new();
+
+        self.apply_recursive_visitor(&mut |node, _layer| {
+            handles.insert(node.uid(), node.data_proxy_ref().status_handle());
+        });
+
+        handles
+    }
+
+    /// Every node of the tree keyed by uid, for labelling logs and observer
+    /// notifications without walking the tree.
+    pub fn uid_map(&self) -> BTreeMap<u16, NodeEntry> {
+        let mut map = BTreeMap::new();
+
+        self.apply_recursive_visitor(&mut |node, _layer| {
+            let data_proxy = node.data_proxy_ref();
+
+            map.insert(
+                node.uid(),
+                NodeEntry {
+                    uid: node.uid(),
+                    full_path: data_proxy.full_path().to_string(),
+                    name: data_proxy.name().map(str::to_string),
+                    type_name: node.path().to_string(),
+                    node_type: node.node_type(),
+                    tags: data_proxy.tags().to_vec(),
+                    metadata: data_proxy.metadata().clone(),
+                },
+            );
+        });
+
+        map
+    }
+
+    // each blackboard scope once, with the path of the first node using it
+    fn blackboard_scopes(&self) -> Vec<(String, Arc<RwLock<Blackboard>>)> {
+        let mut scopes: Vec<(String, Arc<RwLock<Blackboard>>)> = vec![];
+
+        self.apply_recursive_visitor(&mut |node, _layer| {
+            let data_proxy = node.data_proxy_ref();
+            let bb = data_proxy.shared_blackboard();
+
+            if scopes.iter().all(|(_, known)| !Arc::ptr_eq(known, bb)) {
+                scopes.push((data_proxy.full_path().to_string(), bb.clone()));
+            }
+        });
+
+        scopes
+    }
+
+    /// Entries of the tree's blackboard and all of its SubTree and branch
+    /// scopes, e.g. to persist mission state across restarts.
+    pub fn blackboard_snapshot(&self) -> BlackboardSnapshot {
+        let scopes = self
+            .blackboard_scopes()
+            .into_iter()
+            .map(|(path, bb)| (path, bb.read().local_entries()))
+            .collect();
+
+        BlackboardSnapshot { scopes }
+    }
+
+    /// Restore a snapshot taken from a tree built from the same document,
+    /// scopes the snapshot doesn't know are cleared.
+    pub fn restore_blackboard(&self, snapshot: &BlackboardSnapshot) -> Result<()> {
+        let scopes = self.blackboard_scopes();
+
+        if let Some(path) = snapshot
+            .scopes
+            .keys()
+            .find(|path| scopes.iter().all(|(known, _)| known != *path))
+        {
+            return Err(BtError::Raw(format!(
+                "snapshot scope not found in tree: path= {path}"
+            )));
+        }
+
+        for (path, bb) in scopes {
+            let entries = snapshot.scopes.get(&path).cloned().unwrap_or_default();
+            bb.write().restore_local(entries);
+        }
+
+        Ok(())
+    }
+
+    /// Uids of all nodes carrying `tag`, in depth-first order.
+    pub fn tagged_uids(&self, tag: &str) -> Vec<u16> {
+        let mut uids = vec![];
+
+        self.apply_recursive_visitor(&mut |node, _layer| {
+            if node.data_proxy_ref().has_tag(tag) {
+                uids.push(node.uid());
+            }
+        });
+
+        uids
+    }
+
+    /// Halt every node carrying `tag` and reset its status.
+    pub fn halt_tagged(&mut self, tag: &str) {
+        self.apply_recursive_visitor_mut(&mut |node, _layer| {
+            if node.data_proxy_ref().has_tag(tag) {
+                node.halt();
+                node.reset_status();
+            }
+        });
+    }
+
+    /// Status observers of every node carrying `tag`, keyed by uid.
+    pub fn subscribe_tagged(&self, tag: &str) -> BTreeMap<u16, watch::Receiver<StateNotif>> {
+        let mut observers = BTreeMap::new();
+
+        self.apply_recursive_visitor(&mut |node, _layer| {
+            let data_proxy = node.data_proxy_ref();
+            if data_proxy.has_tag(tag) {
+                observers.insert(node.uid(), data_proxy.add_observer());
+            }
+        });
+
+        observers
+    }
+
+    /// Senders choosing the child of every `ManualSelector` in the tree, keyed by uid.
+    pub fn selection_senders(&self) -> BTreeMap<u16, Arc<watch::Sender<Option<usize>>>> {
+        let mut senders = BTreeMap::new();
+
+        self.apply_recursive_visitor(&mut |node, _layer| {
+            if let NodeWrapper::Composite(cp) = &node.node_wrapper {
+                if let Some(sender) = cp.selection_sender() {
+                    senders.insert(node.uid(), sender);
+                }
+            }
+        });
+
+        senders
+    }
+
     pub fn node_info(&self) -> String {
         let mut info = String::new();
 
@@ -114,11 +470,16 @@ impl TreeNodeWrapper {
                 info.push('\t');
             }
 
+            let data_proxy = node.data_proxy_ref();
             info.push_str(&format!(
                 "uid= {} path= {}",
                 node.uid(),
-                node.data_proxy_ref().full_path()
+                data_proxy.full_path()
             ));
+
+            for (key, value) in data_proxy.metadata() {
+                info.push_str(&format!(" {key}= {value}"));
+            }
         });
 
         info
@@ -136,17 +497,50 @@ impl TreeNodeWrapper {
         dot_s
     }
 
+    /// Mermaid flowchart of the tree, nodes labelled by type and instance name.
+    pub fn mermaid_info(&self) -> String {
+        let mut content = String::from("graph TD\n");
+
+        Self::mermaid_info_construct(&mut content, self, None);
+
+        content
+    }
+
+    fn mermaid_info_construct(content: &mut String, node: &TreeNodeWrapper, parent: Option<u16>) {
+        content.push_str(&format!(
+            "    n{}[\"{}\"]\n",
+            node.uid(),
+            node.data_proxy_ref().label()
+        ));
+
+        if let Some(parent) = parent {
+            content.push_str(&format!("    n{} --> n{}\n", parent, node.uid()));
+        }
+
+        match &node.node_wrapper {
+            NodeWrapper::Action(_) | NodeWrapper::Condition(_) => {}
+            NodeWrapper::Composite(cp) => {
+                for child_node in &cp.child_nodes {
+                    Self::mermaid_info_construct(content, child_node, Some(node.uid()));
+                }
+            }
+            NodeWrapper::Decorator(dr) => {
+                Self::mermaid_info_construct(content, &dr.inner_node, Some(node.uid()));
+            }
+        }
+    }
+
     fn dot_info_construct(content: &mut String, node: &TreeNodeWrapper, parent: &TreeNodeWrapper) {
-        let p = format!("\"{}_{}\"", parent.uid(), parent.path());
+        let p = format!("\"{}_{}\"", parent.uid(), parent.data_proxy_ref().label());
 
-        let node_s = format!("\"{}_{}\"", node.uid(), node.path());
+        let node_s = format!("\"{}_{}\"", node.uid(), node.data_proxy_ref().label());
 
         if p != node_s {
             content.push_str(&format!("{} -> {};\n", p, node_s));
         }
 
         match &node.node_wrapper {
-            NodeWrapper::Action(_at) => {}
+            NodeWrapper::Action(_) | NodeWrapper::Condition(_) => {}
             NodeWrapper::Composite(cp) => {
                 for child_node in &cp.child_nodes {
                     Self::dot_info_construct(content, child_node, node);
@@ -178,11 +572,78 @@ impl TreeNodeWrapper {
     pub fn apply_recursive_visitor(&self, visitor: &mut impl FnMut(&Self, u16)) {
         self.apply_recursive_visitor_impl(0, visitor);
     }
+
+    fn apply_recursive_visitor_mut_impl(
+        &mut self,
+        layer: u16,
+        visitor: &mut impl FnMut(&mut Self, u16),
+    ) {
+        visitor(self, layer);
+
+        match &mut self.node_wrapper {
+            NodeWrapper::Composite(cp) => {
+                for child in &mut cp.child_nodes {
+                    child.apply_recursive_visitor_mut_impl(layer + 1, visitor);
+                }
+            }
+            NodeWrapper::Decorator(dn) => {
+                dn.inner_node
+                    .apply_recursive_visitor_mut_impl(layer + 1, visitor);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn apply_recursive_visitor_mut(&mut self, visitor: &mut impl FnMut(&mut Self, u16)) {
+        self.apply_recursive_visitor_mut_impl(0, visitor);
+    }
+}
+
+/// Same layout as [`TreeNodeWrapper::node_info`], annotated with the current
+/// status and port values of every node.
+impl std::fmt::Display for TreeNodeWrapper {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut res = Ok(());
+
+        self.apply_recursive_visitor(&mut |node, layer| {
+            if res.is_err() {
+                return;
+            }
+
+            res = (|| {
+                for _ in 0..layer {
+                    f.write_str("\t")?;
+                }
+
+                let data_proxy = node.data_proxy_ref();
+                write!(
+                    f,
+                    "uid= {} path= {} status= {}",
+                    node.uid(),
+                    data_proxy.full_path(),
+                    node.status()
+                )?;
+
+                for (k, v) in data_proxy.input_ports() {
+                    write!(f, " {k}= {v}")?;
+                }
+
+                f.write_str("\n")
+            })();
+        });
+
+        res
+    }
 }
 
 impl TreeNode for TreeNodeWrapper {
     fn tick(&mut self) -> NodeStatus {
         let uid = self.uid();
+        let _current_node = node::audit::CurrentNodeGuard::enter(uid);
+
+        if let Some(status) = self.check_conditions() {
+            return status;
+        }
 
         match &mut self.node_wrapper {
             NodeWrapper::Composite(cp) => cp.tick(),
@@ -191,6 +652,7 @@ impl TreeNode for TreeNodeWrapper {
                 tracing::trace!("action tick: uid= {uid}");
                 tn.tick()
             }
+            NodeWrapper::Condition(cd) => cd.tick(),
         }
     }
 
@@ -201,6 +663,16 @@ impl TreeNode for TreeNodeWrapper {
             NodeWrapper::Action(tn) => {
                 tn.halt();
             }
+            NodeWrapper::Condition(_) => {}
+        }
+    }
+
+    fn is_halting(&self) -> bool {
+        match &self.node_wrapper {
+            NodeWrapper::Composite(cp) => cp.is_halting(),
+            NodeWrapper::Decorator(dn) => dn.is_halting(),
+            NodeWrapper::Action(tn) => tn.is_halting(),
+            NodeWrapper::Condition(_) => false,
         }
     }
 }
@@ -208,4 +680,9 @@ impl TreeNode for TreeNodeWrapper {
 pub trait TreeNode: Any + Send + Sync {
     fn tick(&mut self) -> NodeStatus;
     fn halt(&mut self) {}
+    /// Whether a previous `halt` is still winding down, e.g. a hardware
+    /// command waiting for its cancellation to be acknowledged.
+    fn is_halting(&self) -> bool {
+        false
+    }
 }
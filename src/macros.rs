@@ -0,0 +1,194 @@
+/// Declare a struct holding typed input ports, loaded all at once from a
+/// [`DataProxy`](crate::node::DataProxy).
+///
+/// Fields with a default fall back to it when the port or the blackboard entry
+/// it refers to is missing, fields without one make `load` fail with the
+/// [`PortError`](crate::node::port::PortError). A value that doesn't parse is
+/// always an error.
+///
+/// ```
+/// use std::{collections::HashMap, sync::Arc};
+///
+/// use cornerstone::{declare_ports, node::{Blackboard, DataProxy}};
+/// use parking_lot::RwLock;
+///
+/// declare_ports! {
+///     pub struct MovePorts {
+///         msec: u64 = 100,
+///         target: String,
+///     }
+/// }
+///
+/// let bb = Arc::new(RwLock::new(Blackboard::default()));
+/// let mut data_proxy = DataProxy::new(bb);
+/// data_proxy.add_input("target".to_string(), "dock".to_string());
+///
+/// let ports = MovePorts::load(&data_proxy).unwrap();
+/// assert_eq!(ports.msec, 100);
+/// assert_eq!(ports.target, "dock");
+///
+/// data_proxy.add_input("msec".to_string(), "soon".to_string());
+/// assert!(MovePorts::load(&data_proxy).is_err());
+/// ```
+#[macro_export]
+macro_rules! declare_ports {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $( $field:ident : $ty:ty $(= $default:expr)? ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $( pub $field: $ty, )*
+        }
+
+        impl $name {
+            pub fn load(
+                data_proxy: &$crate::node::DataProxy,
+            ) -> ::std::result::Result<Self, $crate::BtError> {
+                $(
+                    let $field = match data_proxy.try_get_input::<$ty>(stringify!($field)) {
+                        Ok(v) => v,
+                        Err(
+                            e @ ($crate::node::port::PortError::NotProvided { .. }
+                            | $crate::node::port::PortError::KeyMissing { .. }),
+                        ) => $crate::__port_default!($($default)?; e),
+                        Err(e) => return Err(e.into()),
+                    };
+                )*
+
+                Ok(Self { $( $field, )* })
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __port_default {
+    ($default:expr; $error:ident) => {{
+        let _ = $error;
+        $default
+    }};
+    (; $error:ident) => {
+        return Err($error.into())
+    };
+}
+
+/// Build a tree from a Rust description instead of an XML string, with the
+/// nodes of a [`Factory`](crate::factory::Factory). Evaluates to a
+/// `Result<Option<TreeNodeWrapper>, BtError>` like
+/// [`create_bt_tree_from_xml_str`](crate::parser::xml::create_bt_tree_from_xml_str).
+///
+/// A node is its type, optional `(port = value, ...)` and optional
+/// `{ children }`, port values are any `ToString`. Namespaced types are
+/// written `nav.MoveTo`. The description is a single tree with one root node,
+/// so there is nothing for a `SubTree` to refer to.
+///
+/// ```
+/// use cornerstone::{bt, factory::Factory, NodeStatus, TreeNode};
+///
+/// let factory = Factory::default();
+/// let attempts = 3;
+///
+/// let mut node = bt!(&factory, Sequence {
+///     SetBlackboard(value = 42, output_key = "answer")
+///     RetryUntilSuccessful(num_attempts = attempts) {
+///         SetBlackboard(value = "{answer}", output_key = "copy")
+///     }
+/// })
+/// .unwrap()
+/// .unwrap();
+///
+/// assert_eq!(node.tick(), NodeStatus::Success);
+/// assert_eq!(
+///     node.data_proxy_ref().blackboard().get_entry("copy"),
+///     Some(serde_json::json!("42"))
+/// );
+/// ```
+///
+/// A second root node doesn't compile:
+///
+/// ```compile_fail
+/// use cornerstone::{bt, factory::Factory};
+///
+/// let _ = bt!(&Factory::default(), Sequence {} Sequence {});
+/// ```
+#[macro_export]
+macro_rules! bt {
+    ($factory:expr, $($tree:tt)+) => {
+        $crate::parser::xml::create_bt_tree_from_spec($factory, $crate::__bt_node!($($tree)+))
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __bt_node {
+    ($first:ident $(. $segment:ident)* ( $($key:ident = $value:expr),* $(,)? ) { $($children:tt)* }) => {
+        $crate::parser::xml::NodeSpec {
+            type_name: $crate::__bt_type_name!($first $(. $segment)*),
+            ports: ::std::vec![$(
+                (stringify!($key), ::std::string::ToString::to_string(&$value)),
+            )*],
+            children: $crate::__bt_children!([] $($children)*),
+        }
+    };
+    ($first:ident $(. $segment:ident)* ( $($key:ident = $value:expr),* $(,)? )) => {
+        $crate::__bt_node!($first $(. $segment)* ( $($key = $value),* ) {})
+    };
+    ($first:ident $(. $segment:ident)* { $($children:tt)* }) => {
+        $crate::__bt_node!($first $(. $segment)* () { $($children)* })
+    };
+    ($first:ident $(. $segment:ident)*) => {
+        $crate::__bt_node!($first $(. $segment)* () {})
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __bt_type_name {
+    ($first:ident $(. $segment:ident)*) => {
+        concat!(stringify!($first) $(, ".", stringify!($segment))*)
+    };
+}
+
+// children are collected one at a time, a bare node ends at a `,`, the next
+// node or the end of the list
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __bt_children {
+    ([$($done:expr),*]) => {
+        ::std::vec![$($done),*]
+    };
+    ([$($done:expr),*] , $($rest:tt)*) => {
+        $crate::__bt_children!([$($done),*] $($rest)*)
+    };
+    ([$($done:expr),*] $first:ident $(. $segment:ident)* ( $($ports:tt)* ) { $($children:tt)* } $($rest:tt)*) => {
+        $crate::__bt_children!(
+            [$($done,)* $crate::__bt_node!($first $(. $segment)* ( $($ports)* ) { $($children)* })]
+            $($rest)*
+        )
+    };
+    ([$($done:expr),*] $first:ident $(. $segment:ident)* ( $($ports:tt)* ) $($rest:tt)*) => {
+        $crate::__bt_children!(
+            [$($done,)* $crate::__bt_node!($first $(. $segment)* ( $($ports)* ))]
+            $($rest)*
+        )
+    };
+    ([$($done:expr),*] $first:ident $(. $segment:ident)* { $($children:tt)* } $($rest:tt)*) => {
+        $crate::__bt_children!(
+            [$($done,)* $crate::__bt_node!($first $(. $segment)* { $($children)* })]
+            $($rest)*
+        )
+    };
+    ([$($done:expr),*] $first:ident $(. $segment:ident)* , $($rest:tt)*) => {
+        $crate::__bt_children!([$($done,)* $crate::__bt_node!($first $(. $segment)*)] $($rest)*)
+    };
+    ([$($done:expr),*] $first:ident $(. $segment:ident)* $next:ident $($rest:tt)*) => {
+        $crate::__bt_children!([$($done,)* $crate::__bt_node!($first $(. $segment)*)] $next $($rest)*)
+    };
+    ([$($done:expr),*] $first:ident $(. $segment:ident)*) => {
+        $crate::__bt_children!([$($done,)* $crate::__bt_node!($first $(. $segment)*)])
+    };
+}
@@ -1,8 +1,30 @@
-use serde_json::json;
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
 
-use crate::{NodeStatus, TreeNode};
+use parking_lot::Mutex;
 
-use super::DataProxy;
+use serde_json::Value;
+
+use crate::{
+    expr::{self, Script as ParsedScript},
+    runtime::{
+        default_runtime,
+        sync::{broadcast, oneshot},
+        Runtime,
+    },
+    BtError, NodeStatus, TreeNode,
+};
+
+use super::{
+    is_ref_key,
+    port::{PortInfo, PortsList},
+    strip_ref_tag, DataProxy, TreeEvent,
+};
 
 pub trait ActionNodeImpl: Send + Sync {
     fn tick_status(&mut self, data_proxy: &mut DataProxy) -> NodeStatus;
@@ -12,6 +34,293 @@ pub trait ActionNodeImpl: Send + Sync {
     }
 
     fn halt(&mut self) {}
+
+    /// Whether the work stopped by `halt` is still winding down.
+    fn halt_pending(&self) -> bool {
+        false
+    }
+
+    /// Ports accepted from the XML, `None` accepts any attribute.
+    fn provided_ports() -> Option<PortsList>
+    where
+        Self: Sized,
+    {
+        None
+    }
+}
+
+/// Long-running action split into phases, wrap it in [`StatefulAction`] to
+/// register it.
+pub trait StatefulActionNodeImpl: Send + Sync {
+    /// First tick after Idle or completion
+    fn on_start(&mut self, data_proxy: &mut DataProxy) -> NodeStatus;
+
+    /// Ticks while the previous tick returned Running
+    fn on_running(&mut self, data_proxy: &mut DataProxy) -> NodeStatus;
+
+    /// Halted while running
+    fn on_halted(&mut self) {}
+
+    /// Ports accepted from the XML, `None` accepts any attribute.
+    fn provided_ports() -> Option<PortsList>
+    where
+        Self: Sized,
+    {
+        None
+    }
+}
+
+/// Drives a [`StatefulActionNodeImpl`] through its phases.
+pub struct StatefulAction<T> {
+    node: T,
+    running: bool,
+}
+
+impl<T: StatefulActionNodeImpl> StatefulAction<T> {
+    pub fn new(node: T) -> Self {
+        Self {
+            node,
+            running: false,
+        }
+    }
+}
+
+impl<T: StatefulActionNodeImpl> ActionNodeImpl for StatefulAction<T> {
+    fn tick_status(&mut self, data_proxy: &mut DataProxy) -> NodeStatus {
+        let status = if self.running {
+            self.node.on_running(data_proxy)
+        } else {
+            self.node.on_start(data_proxy)
+        };
+
+        self.running = status == NodeStatus::Running;
+
+        status
+    }
+
+    fn node_info(&self) -> String {
+        std::any::type_name::<T>().to_string()
+    }
+
+    fn halt(&mut self) {
+        if std::mem::take(&mut self.running) {
+            self.node.on_halted();
+        }
+    }
+
+    fn provided_ports() -> Option<PortsList> {
+        T::provided_ports()
+    }
+}
+
+pub type ActionFuture = Pin<Box<dyn Future<Output = Result<(), BtError>> + Send + 'static>>;
+
+/// Action written as a future, wrap it in [`AsyncAction`] to register it.
+pub trait AsyncActionNodeImpl: Send + Sync {
+    /// Called on the first tick after Idle or completion, values needed by the
+    /// future have to be read from `data_proxy` up front.
+    fn start(&mut self, data_proxy: &mut DataProxy) -> ActionFuture;
+
+    /// Ports accepted from the XML, `None` accepts any attribute.
+    fn provided_ports() -> Option<PortsList>
+    where
+        Self: Sized,
+    {
+        None
+    }
+}
+
+// future of a started action, shared with the task driving it so a halt can
+// drop it right away
+#[derive(Default)]
+struct FutureSlot {
+    fut: Option<ActionFuture>,
+    waker: Option<Waker>,
+}
+
+type SharedSlot = Arc<Mutex<FutureSlot>>;
+
+/// Runs the future of an [`AsyncActionNodeImpl`] as a task of the runtime,
+/// ticks report Running until it completes and halting drops, i.e. cancels,
+/// the future.
+///
+/// Without a runtime (see [`AsyncAction::inline`]) the future is polled once
+/// per tick on the ticking thread instead.
+pub struct AsyncAction<T> {
+    node: T,
+    runtime: Option<Arc<dyn Runtime>>,
+    slot: SharedSlot,
+    result: Option<oneshot::Receiver<Result<(), BtError>>>,
+}
+
+impl<T: AsyncActionNodeImpl> AsyncAction<T> {
+    /// Spawns onto the runtime selected by features, polls inline without one.
+    pub fn new(node: T) -> Self {
+        Self::with_runtime(node, default_runtime())
+    }
+
+    pub fn with_runtime(node: T, runtime: Option<Arc<dyn Runtime>>) -> Self {
+        Self {
+            node,
+            runtime,
+            slot: SharedSlot::default(),
+            result: None,
+        }
+    }
+
+    /// Polled once per tick on the ticking thread, never spawned.
+    pub fn inline(node: T) -> Self {
+        Self::with_runtime(node, None)
+    }
+
+    fn tick_inline(&mut self, data_proxy: &mut DataProxy) -> NodeStatus {
+        let mut slot = self.slot.lock();
+        let fut = slot.fut.get_or_insert_with(|| self.node.start(data_proxy));
+
+        let mut cx = Context::from_waker(Waker::noop());
+        let Poll::Ready(res) = fut.as_mut().poll(&mut cx) else {
+            return NodeStatus::Running;
+        };
+
+        slot.fut = None;
+        drop(slot);
+
+        NodeStatus::from_result_with(res, |e| data_proxy.report_error(e))
+    }
+}
+
+// drives the future in `slot` until it completes or a halt takes it
+async fn drive(slot: SharedSlot, result: oneshot::Sender<Result<(), BtError>>) {
+    let res = std::future::poll_fn(|cx| {
+        let mut slot = slot.lock();
+        let Some(fut) = slot.fut.as_mut() else {
+            return Poll::Ready(None);
+        };
+
+        match fut.as_mut().poll(cx) {
+            Poll::Ready(res) => {
+                slot.fut = None;
+                Poll::Ready(Some(res))
+            }
+            Poll::Pending => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    })
+    .await;
+
+    if let Some(res) = res {
+        let _ = result.send(res);
+    }
+}
+
+impl<T: AsyncActionNodeImpl> ActionNodeImpl for AsyncAction<T> {
+    fn tick_status(&mut self, data_proxy: &mut DataProxy) -> NodeStatus {
+        let Some(runtime) = &self.runtime else {
+            return self.tick_inline(data_proxy);
+        };
+
+        let result = self.result.get_or_insert_with(|| {
+            let (tx, rx) = oneshot::channel();
+
+            // a fresh slot per run, the task of a halted run finds its own slot empty
+            self.slot = Arc::new(Mutex::new(FutureSlot {
+                fut: Some(self.node.start(data_proxy)),
+                waker: None,
+            }));
+            runtime.spawn(Box::pin(drive(self.slot.clone(), tx)));
+
+            rx
+        });
+
+        let res = match result.try_recv() {
+            Ok(res) => res,
+            Err(oneshot::error::TryRecvError::Empty) => return NodeStatus::Running,
+            Err(oneshot::error::TryRecvError::Closed) => {
+                Err(BtError::Raw("async action task dropped".to_string()))
+            }
+        };
+        self.result = None;
+
+        NodeStatus::from_result_with(res, |e| data_proxy.report_error(e))
+    }
+
+    fn node_info(&self) -> String {
+        std::any::type_name::<T>().to_string()
+    }
+
+    fn halt(&mut self) {
+        self.result = None;
+
+        let mut slot = self.slot.lock();
+        slot.fut = None;
+        // lets the task see the empty slot and finish
+        if let Some(waker) = slot.waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn provided_ports() -> Option<PortsList> {
+        T::provided_ports()
+    }
+}
+
+/// Pending until polled again, i.e. an inline [`AsyncAction`] reports Running
+/// for the current tick and resumes on the next one.
+pub fn yield_running() -> YieldRunning {
+    YieldRunning(false)
+}
+
+pub struct YieldRunning(bool);
+
+impl Future for YieldRunning {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if std::mem::replace(&mut self.0, true) {
+            Poll::Ready(())
+        } else {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+/// Action written as straight-line async code with [`yield_running`] points,
+/// halting the node drops the coroutine at the point it yielded.
+///
+/// ```
+/// # use cornerstone::node::action::{coroutine, yield_running};
+/// let action = coroutine(|data_proxy| {
+///     let bb = data_proxy.shared_blackboard().clone();
+///
+///     Box::pin(async move {
+///         for step in 0..3 {
+///             bb.write().set("step".to_string(), step.into());
+///             yield_running().await;
+///         }
+///
+///         Ok(())
+///     })
+/// });
+/// ```
+pub fn coroutine<F>(body: F) -> AsyncAction<Coroutine<F>>
+where
+    F: FnMut(&mut DataProxy) -> ActionFuture + Send + Sync,
+{
+    AsyncAction::inline(Coroutine(body))
+}
+
+pub struct Coroutine<F>(F);
+
+impl<F> AsyncActionNodeImpl for Coroutine<F>
+where
+    F: FnMut(&mut DataProxy) -> ActionFuture + Send + Sync,
+{
+    fn start(&mut self, data_proxy: &mut DataProxy) -> ActionFuture {
+        (self.0)(data_proxy)
+    }
 }
 
 pub struct ActionWrapper {
@@ -26,6 +335,7 @@ impl TreeNode for ActionWrapper {
         }
 
         let new_status = self.node.tick_status(&mut self.data_proxy);
+        let new_status = self.data_proxy.intercept_transition(new_status);
         self.data_proxy.set_status(new_status);
 
         new_status
@@ -36,6 +346,10 @@ impl TreeNode for ActionWrapper {
 
         self.node.halt();
     }
+
+    fn is_halting(&self) -> bool {
+        self.node.halt_pending()
+    }
 }
 
 impl ActionWrapper {
@@ -44,21 +358,420 @@ impl ActionWrapper {
     }
 }
 
+/// Writes `value` to `output_key`, as a string unless `type` is one of
+/// `int`, `float`, `bool`, `json` or `auto` (JSON when it parses, else string).
 #[derive(Default)]
 pub struct SetBlackboard;
 
+pub const SET_BLACKBOARD_TYPE: &str = "type";
+
+fn typed_value(value: String, type_name: &str) -> Result<Value, BtError> {
+    let invalid = |e: &dyn std::fmt::Display| {
+        BtError::Raw(format!("invalid {type_name} value: {value} err= {e}"))
+    };
+
+    match type_name {
+        "string" => Ok(Value::String(value)),
+        "int" => value
+            .trim()
+            .parse::<i64>()
+            .map(Value::from)
+            .map_err(|e| invalid(&e)),
+        "float" => value
+            .trim()
+            .parse::<f64>()
+            .map(Value::from)
+            .map_err(|e| invalid(&e)),
+        "bool" => value
+            .trim()
+            .parse::<bool>()
+            .map(Value::from)
+            .map_err(|e| invalid(&e)),
+        "json" => serde_json::from_str(&value).map_err(|e| invalid(&e)),
+        "auto" => Ok(serde_json::from_str(&value).unwrap_or(Value::String(value))),
+        _ => Err(BtError::Raw(format!("unknown value type: {type_name}"))),
+    }
+}
+
 impl ActionNodeImpl for SetBlackboard {
     fn tick_status(&mut self, data_proxy: &mut DataProxy) -> NodeStatus {
-        let Some(output_key) = data_proxy.get_input::<String>("output_key") else {
-            return NodeStatus::Failure;
+        let value = match data_proxy.try_get_input::<String>("value") {
+            Ok(value) => value,
+            Err(e) => {
+                data_proxy.report_error(e);
+                return NodeStatus::Failure;
+            }
         };
 
-        let Some(value) = data_proxy.get_input::<String>("value") else {
+        let type_name = data_proxy
+            .get_input::<String>(SET_BLACKBOARD_TYPE)
+            .unwrap_or_else(|| "string".to_string());
+
+        let res = typed_value(value, &type_name)
+            .and_then(|value| data_proxy.set_output("output_key", value));
+
+        NodeStatus::from_result_with(res, |e| data_proxy.report_error(e))
+    }
+
+    fn provided_ports() -> Option<PortsList> {
+        Some(vec![
+            PortInfo::input::<String>("value"),
+            PortInfo::output::<String>("output_key"),
+            PortInfo::input::<String>(SET_BLACKBOARD_TYPE)
+                .with_default("string")
+                .with_description("string, int, float, bool, json or auto"),
+        ])
+    }
+}
+
+/// Runs the `code` script against the blackboard, e.g.
+/// `count = count + 1; ok := battery > 30`. Fails without writing anything
+/// when a statement fails.
+#[derive(Default)]
+pub struct Script {
+    parsed: Option<(String, ParsedScript)>,
+}
+
+pub const SCRIPT_CODE: &str = "code";
+
+impl Script {
+    fn run(&mut self, data_proxy: &DataProxy) -> Result<(), BtError> {
+        let Some(code) = data_proxy.input_port(SCRIPT_CODE) else {
+            return Err(BtError::Raw(format!("missing port: {SCRIPT_CODE}")));
+        };
+
+        if self
+            .parsed
+            .as_ref()
+            .is_none_or(|(cached, _)| cached != code)
+        {
+            self.parsed = Some((code.to_string(), expr::parse_script(code)?));
+        }
+
+        let (_, script) = self.parsed.as_ref().unwrap();
+        let writer = data_proxy.full_path();
+        let assigned = {
+            let bb = data_proxy.shared_blackboard().read();
+            let assigned = script.execute(&|key| bb.get_entry(key))?;
+            for (key, value) in &assigned {
+                bb.check_set_resolved(key, value, writer)?;
+            }
+
+            assigned
+        };
+
+        let mut bb = data_proxy.blackboard();
+        for (key, value) in assigned {
+            bb.try_set_resolved(key, value, writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ActionNodeImpl for Script {
+    fn tick_status(&mut self, data_proxy: &mut DataProxy) -> NodeStatus {
+        let res = self.run(data_proxy);
+
+        NodeStatus::from_result_with(res, |e| data_proxy.report_error(e))
+    }
+
+    fn provided_ports() -> Option<PortsList> {
+        Some(vec![PortInfo::input::<String>(SCRIPT_CODE)])
+    }
+}
+
+/// Removes the entry named by the `key` port, succeeds whether or not it existed.
+#[derive(Default)]
+pub struct UnsetBlackboard;
+
+pub const UNSET_KEY: &str = "key";
+
+impl ActionNodeImpl for UnsetBlackboard {
+    fn tick_status(&mut self, data_proxy: &mut DataProxy) -> NodeStatus {
+        let Some(key) = data_proxy.input_port(UNSET_KEY) else {
+            data_proxy.report_error(format!("missing port: {UNSET_KEY}"));
             return NodeStatus::Failure;
         };
 
-        data_proxy.blackboard().set(output_key, json!(value));
+        let key = strip_ref_tag(key);
+        let removed = data_proxy.blackboard().remove(&key);
+        tracing::debug!("unset blackboard: key= {key} removed= {removed:?}");
 
         NodeStatus::Success
     }
+
+    fn provided_ports() -> Option<PortsList> {
+        Some(vec![PortInfo::input::<String>(UNSET_KEY)])
+    }
+}
+
+pub const QUEUE: &str = "queue";
+pub const QUEUE_VALUE: &str = "value";
+pub const QUEUE_SIZE: &str = "size";
+
+fn queue_key(data_proxy: &DataProxy) -> Result<String, BtError> {
+    data_proxy
+        .input_port(QUEUE)
+        .map(strip_ref_tag)
+        .ok_or_else(|| BtError::Raw(format!("missing port: {QUEUE}")))
+}
+
+/// Appends `value` to the `queue` array, a `{key}` value pushes the entry
+/// itself instead of its name.
+#[derive(Default)]
+pub struct PushToQueue;
+
+impl PushToQueue {
+    fn push(data_proxy: &DataProxy) -> Result<(), BtError> {
+        let key = queue_key(data_proxy)?;
+        let Some(raw) = data_proxy.input_port(QUEUE_VALUE) else {
+            return Err(BtError::Raw(format!("missing port: {QUEUE_VALUE}")));
+        };
+
+        let mut bb = data_proxy.blackboard();
+        let value = if is_ref_key(raw) {
+            let value_key = strip_ref_tag(raw);
+            bb.get_entry(&value_key)
+                .ok_or_else(|| BtError::Raw(format!("no entry: key= {value_key}")))?
+        } else {
+            Value::String(raw.to_string())
+        };
+
+        bb.push_back(&key, value, data_proxy.full_path())
+    }
+}
+
+impl ActionNodeImpl for PushToQueue {
+    fn tick_status(&mut self, data_proxy: &mut DataProxy) -> NodeStatus {
+        let res = Self::push(data_proxy);
+
+        NodeStatus::from_result_with(res, |e| data_proxy.report_error(e))
+    }
+
+    fn provided_ports() -> Option<PortsList> {
+        Some(vec![
+            PortInfo::inout::<Value>(QUEUE),
+            PortInfo::input::<Value>(QUEUE_VALUE),
+        ])
+    }
+}
+
+/// Moves the first element of `queue` into the `value` entry, fails when the
+/// queue is empty.
+#[derive(Default)]
+pub struct PopFromQueue;
+
+impl PopFromQueue {
+    fn pop(data_proxy: &DataProxy) -> Result<bool, BtError> {
+        let key = queue_key(data_proxy)?;
+        if data_proxy.input_port(QUEUE_VALUE).is_none() {
+            return Err(BtError::Raw(format!("missing port: {QUEUE_VALUE}")));
+        }
+
+        let item = data_proxy
+            .blackboard()
+            .pop_front(&key, data_proxy.full_path())?;
+        let Some(item) = item else {
+            return Ok(false);
+        };
+
+        data_proxy.set_output(QUEUE_VALUE, item)?;
+
+        Ok(true)
+    }
+}
+
+impl ActionNodeImpl for PopFromQueue {
+    fn tick_status(&mut self, data_proxy: &mut DataProxy) -> NodeStatus {
+        match Self::pop(data_proxy) {
+            Ok(popped) => popped.into(),
+            Err(e) => {
+                data_proxy.report_error(e);
+                NodeStatus::Failure
+            }
+        }
+    }
+
+    fn provided_ports() -> Option<PortsList> {
+        Some(vec![
+            PortInfo::inout::<Value>(QUEUE),
+            PortInfo::output::<Value>(QUEUE_VALUE),
+        ])
+    }
+}
+
+/// Writes the number of elements in `queue` to the `size` entry.
+#[derive(Default)]
+pub struct QueueSize;
+
+impl QueueSize {
+    fn size(data_proxy: &DataProxy) -> Result<(), BtError> {
+        let key = queue_key(data_proxy)?;
+        let size = data_proxy.blackboard().queue_len(&key)?;
+
+        data_proxy.set_output(QUEUE_SIZE, size)
+    }
+}
+
+impl ActionNodeImpl for QueueSize {
+    fn tick_status(&mut self, data_proxy: &mut DataProxy) -> NodeStatus {
+        let res = Self::size(data_proxy);
+
+        NodeStatus::from_result_with(res, |e| data_proxy.report_error(e))
+    }
+
+    fn provided_ports() -> Option<PortsList> {
+        Some(vec![
+            PortInfo::input::<Value>(QUEUE),
+            PortInfo::output::<usize>(QUEUE_SIZE),
+        ])
+    }
+}
+
+pub const EVENT: &str = "event";
+pub const EVENT_PAYLOAD: &str = "payload";
+
+fn event_name(data_proxy: &DataProxy) -> Result<String, BtError> {
+    data_proxy
+        .get_input::<String>(EVENT)
+        .ok_or_else(|| BtError::Raw(format!("missing port: {EVENT}")))
+}
+
+/// Publishes `event` with the optional `payload` on the tree's event channel.
+#[derive(Default)]
+pub struct PublishEvent;
+
+impl ActionNodeImpl for PublishEvent {
+    fn tick_status(&mut self, data_proxy: &mut DataProxy) -> NodeStatus {
+        let name = match event_name(data_proxy) {
+            Ok(name) => name,
+            Err(e) => {
+                data_proxy.report_error(e);
+                return NodeStatus::Failure;
+            }
+        };
+
+        let payload = data_proxy
+            .get_input::<String>(EVENT_PAYLOAD)
+            .map(Value::String)
+            .unwrap_or_default();
+
+        // nobody listening isn't an error
+        let receivers = data_proxy
+            .events()
+            .send(TreeEvent { name, payload })
+            .unwrap_or(0);
+        tracing::debug!("event published: receivers= {receivers}");
+
+        NodeStatus::Success
+    }
+
+    fn provided_ports() -> Option<PortsList> {
+        Some(vec![
+            PortInfo::input::<String>(EVENT),
+            PortInfo::input::<String>(EVENT_PAYLOAD),
+        ])
+    }
+}
+
+/// Running until `event` is published, then writes its payload to the
+/// `payload` port. Only events published after the first tick are seen.
+#[derive(Default)]
+pub struct WaitForEvent {
+    rx: Option<broadcast::Receiver<TreeEvent>>,
+}
+
+impl ActionNodeImpl for WaitForEvent {
+    fn tick_status(&mut self, data_proxy: &mut DataProxy) -> NodeStatus {
+        let name = match event_name(data_proxy) {
+            Ok(name) => name,
+            Err(e) => {
+                data_proxy.report_error(e);
+                return NodeStatus::Failure;
+            }
+        };
+
+        let rx = self
+            .rx
+            .get_or_insert_with(|| data_proxy.events().subscribe());
+
+        loop {
+            match rx.try_recv() {
+                Ok(event) if event.name == name => {
+                    self.rx = None;
+
+                    if let Err(e) = data_proxy.set_output_if_declared(EVENT_PAYLOAD, event.payload)
+                    {
+                        data_proxy.report_error(e);
+                        return NodeStatus::Failure;
+                    }
+
+                    return NodeStatus::Success;
+                }
+                Ok(_) => {}
+                Err(broadcast::error::TryRecvError::Lagged(skipped)) => {
+                    tracing::warn!("event receiver lagged: skipped= {skipped}");
+                }
+                Err(_) => return NodeStatus::Running,
+            }
+        }
+    }
+
+    fn halt(&mut self) {
+        self.rx = None;
+    }
+
+    fn provided_ports() -> Option<PortsList> {
+        Some(vec![
+            PortInfo::input::<String>(EVENT),
+            PortInfo::output::<Value>(EVENT_PAYLOAD),
+        ])
+    }
+}
+
+pub const MSEC: &str = "msec";
+
+#[derive(Default)]
+pub struct Sleep {
+    deadline: Option<Duration>,
+}
+
+impl ActionNodeImpl for Sleep {
+    fn tick_status(&mut self, data_proxy: &mut DataProxy) -> NodeStatus {
+        let now = data_proxy.clock().now();
+
+        let deadline = match self.deadline {
+            Some(deadline) => deadline,
+            None => {
+                let msec = match data_proxy.try_get_input::<u64>(MSEC) {
+                    Ok(msec) => msec,
+                    Err(e) => {
+                        data_proxy.report_error(e);
+                        return NodeStatus::Failure;
+                    }
+                };
+
+                let deadline = now + Duration::from_millis(msec);
+                self.deadline = Some(deadline);
+
+                deadline
+            }
+        };
+
+        if now >= deadline {
+            self.deadline = None;
+            NodeStatus::Success
+        } else {
+            NodeStatus::Running
+        }
+    }
+
+    fn halt(&mut self) {
+        self.deadline = None;
+    }
+
+    fn provided_ports() -> Option<PortsList> {
+        Some(vec![PortInfo::input::<u64>(MSEC)])
+    }
 }
@@ -0,0 +1,125 @@
+use std::{cell::Cell, collections::VecDeque};
+
+use parking_lot::Mutex;
+
+use crate::runtime::sync::broadcast;
+
+thread_local! {
+    static CURRENT_NODE_UID: Cell<Option<u16>> = const { Cell::new(None) };
+}
+
+/// Uid of the node currently ticking on this thread, if any.
+pub fn current_node_uid() -> Option<u16> {
+    CURRENT_NODE_UID.with(|c| c.get())
+}
+
+/// Marks `uid` as the ticking node until dropped, restoring the outer node.
+pub(crate) struct CurrentNodeGuard {
+    prev: Option<u16>,
+}
+
+impl CurrentNodeGuard {
+    pub(crate) fn enter(uid: u16) -> Self {
+        let prev = CURRENT_NODE_UID.with(|c| c.replace(Some(uid)));
+
+        Self { prev }
+    }
+}
+
+impl Drop for CurrentNodeGuard {
+    fn drop(&mut self) {
+        CURRENT_NODE_UID.with(|c| c.set(self.prev));
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum AccessKind {
+    Get,
+    Set,
+}
+
+/// Where a key was resolved, relative to the blackboard it was accessed through.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum ResolvedScope {
+    Local,
+    Parent { depth: usize, key: String },
+    Mount { namespace: String, key: String },
+    Missing,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct AuditRecord {
+    pub ts: i64,
+    pub access: AccessKind,
+    pub uid: Option<u16>,
+    pub key: String,
+    pub scope: ResolvedScope,
+}
+
+/// Bounded log of blackboard accesses, enabled with `Blackboard::enable_audit`.
+pub struct AuditLog {
+    capacity: usize,
+    records: Mutex<VecDeque<AuditRecord>>,
+    tx: broadcast::Sender<AuditRecord>,
+}
+
+impl std::fmt::Debug for AuditLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuditLog")
+            .field("capacity", &self.capacity)
+            .field("len", &self.records.lock().len())
+            .finish()
+    }
+}
+
+impl AuditLog {
+    pub fn new(capacity: usize) -> Self {
+        let (tx, _rx) = broadcast::channel(capacity.max(1));
+
+        Self {
+            capacity,
+            records: Mutex::new(VecDeque::new()),
+            tx,
+        }
+    }
+
+    pub(crate) fn record(&self, access: AccessKind, key: &str, scope: ResolvedScope) {
+        let record = AuditRecord {
+            ts: chrono::Utc::now().timestamp_millis(),
+            access,
+            uid: current_node_uid(),
+            key: key.to_string(),
+            scope,
+        };
+
+        tracing::trace!("blackboard audit: {record:?}");
+
+        if self.tx.receiver_count() > 0 {
+            let _ = self.tx.send(record.clone());
+        }
+
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut records = self.records.lock();
+        if records.len() >= self.capacity {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+
+    /// Stream of records produced after subscribing.
+    pub fn subscribe(&self) -> broadcast::Receiver<AuditRecord> {
+        self.tx.subscribe()
+    }
+
+    /// The most recent records, oldest first.
+    pub fn dump(&self) -> Vec<AuditRecord> {
+        self.records.lock().iter().cloned().collect()
+    }
+
+    pub fn clear(&self) {
+        self.records.lock().clear();
+    }
+}
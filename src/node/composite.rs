@@ -1,8 +1,25 @@
-use std::collections::HashSet;
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+    sync::Arc,
+};
 
-use crate::{NodeStatus, TreeNode, TreeNodeWrapper};
+#[cfg(feature = "rt-tokio")]
+use std::sync::mpsc;
 
-use super::DataProxy;
+use rand::{distributions::WeightedIndex, prelude::Distribution, rngs::StdRng, SeedableRng};
+use serde_json::{json, Value};
+
+use crate::{runtime::sync::watch, BtError, NodeStatus, NodeType, TreeNode, TreeNodeWrapper};
+
+#[cfg(feature = "rt-tokio")]
+use crate::runtime::{Runtime, TokioRuntime};
+
+use super::{
+    is_ref_key,
+    port::{PortInfo, PortsList},
+    strip_ref_tag, DataProxy,
+};
 
 pub trait CompositeNodeImpl: Send + Sync {
     fn tick_status(
@@ -14,12 +31,68 @@ pub trait CompositeNodeImpl: Send + Sync {
         std::any::type_name::<Self>().to_string()
     }
     fn reset_state(&mut self);
+    /// Whether each child should be built with its own copy-on-write blackboard scope
+    fn copy_on_write_children(&self) -> bool {
+        false
+    }
+    /// Channel choosing the child to tick from outside the tree, see [`ManualSelector`]
+    fn selection_sender(&self) -> Option<Arc<watch::Sender<Option<usize>>>> {
+        None
+    }
+
+    /// Ports accepted from the XML, `None` accepts any attribute.
+    fn provided_ports() -> Option<PortsList>
+    where
+        Self: Sized,
+    {
+        None
+    }
+}
+
+pub type HaltOrderFn = Arc<dyn Fn(&[TreeNodeWrapper]) -> Vec<usize> + Send + Sync>;
+
+/// Order in which a composite halts its running children.
+#[derive(Clone, Default)]
+pub enum HaltOrder {
+    /// Last child first, undoing the order in which children were started
+    #[default]
+    Reverse,
+    Forward,
+    /// Indices of the children to halt first, running children left out are
+    /// halted afterwards in reverse order
+    Custom(HaltOrderFn),
+}
+
+impl HaltOrder {
+    fn indices(&self, child_nodes: &[TreeNodeWrapper]) -> Vec<usize> {
+        let reverse = (0..child_nodes.len()).rev();
+
+        match self {
+            HaltOrder::Reverse => reverse.collect(),
+            HaltOrder::Forward => (0..child_nodes.len()).collect(),
+            HaltOrder::Custom(f) => {
+                let mut indices: Vec<_> = f(child_nodes)
+                    .into_iter()
+                    .filter(|i| *i < child_nodes.len())
+                    .collect();
+
+                for i in reverse {
+                    if !indices.contains(&i) {
+                        indices.push(i);
+                    }
+                }
+
+                indices
+            }
+        }
+    }
 }
 
 pub struct CompositeWrapper {
     pub data_proxy: DataProxy,
     node_wrapper: Box<dyn CompositeNodeImpl>,
     pub child_nodes: Vec<TreeNodeWrapper>,
+    halt_order: HaltOrder,
 }
 
 impl CompositeWrapper {
@@ -28,15 +101,30 @@ impl CompositeWrapper {
             data_proxy,
             node_wrapper,
             child_nodes: vec![],
+            halt_order: HaltOrder::default(),
         }
     }
 
+    pub fn set_halt_order(&mut self, halt_order: HaltOrder) {
+        self.halt_order = halt_order;
+    }
+
+    pub fn copy_on_write_children(&self) -> bool {
+        self.node_wrapper.copy_on_write_children()
+    }
+
+    pub fn selection_sender(&self) -> Option<Arc<watch::Sender<Option<usize>>>> {
+        self.node_wrapper.selection_sender()
+    }
+
     pub fn add_child(&mut self, node: TreeNodeWrapper) {
         self.child_nodes.push(node);
     }
 
     pub fn reset_children(&mut self) {
-        for child_node in &mut self.child_nodes {
+        for i in self.halt_order.indices(&self.child_nodes) {
+            let child_node = &mut self.child_nodes[i];
+
             tracing::trace!(
                 "child node status: uid= {} {:?}",
                 child_node.uid(),
@@ -46,6 +134,9 @@ impl CompositeWrapper {
             if child_node.status() == NodeStatus::Running {
                 child_node.halt();
             }
+        }
+
+        for child_node in &mut self.child_nodes {
             child_node.reset_status();
         }
     }
@@ -60,8 +151,9 @@ impl TreeNode for CompositeWrapper {
         let tick_status = self
             .node_wrapper
             .tick_status(&mut self.data_proxy, &mut self.child_nodes);
+        let tick_status = self.data_proxy.intercept_transition(tick_status);
 
-        if tick_status.is_completed() {
+        if tick_status.is_completed() || tick_status == NodeStatus::Skipped {
             self.halt();
         }
 
@@ -72,27 +164,196 @@ impl TreeNode for CompositeWrapper {
 
     fn halt(&mut self) {
         tracing::debug!("halt self: {}", std::any::type_name::<Self>());
-        self.node_wrapper.reset_state();
         self.reset_children();
+        self.node_wrapper.reset_state();
+    }
+
+    fn is_halting(&self) -> bool {
+        self.child_nodes.iter().any(|child| child.is_halting())
     }
 }
 
-#[derive(Default)]
 pub struct Sequence {
     current_child_idx: usize,
+    skipped_failures: usize,
+    skipped_children: usize,
+    restart_on_failure: bool,
+    // child to resume from once the failure completed this Sequence
+    resume_idx: Option<usize>,
+}
+
+impl Default for Sequence {
+    fn default() -> Self {
+        Self::new(true)
+    }
+}
+
+impl Sequence {
+    /// With `restart_on_failure` unset, the next tick after a Failure resumes
+    /// from the failed child instead of the first one.
+    pub fn new(restart_on_failure: bool) -> Self {
+        Self {
+            current_child_idx: 0,
+            skipped_failures: 0,
+            skipped_children: 0,
+            restart_on_failure,
+            resume_idx: None,
+        }
+    }
+
+    /// Sequence remembering the failed child, registered as `SequenceWithMemory`.
+    pub fn with_memory() -> Self {
+        Self::new(false)
+    }
+}
+
+pub const SEQUENCE_ON_CHILD_FAILURE: &str = "on_child_failure";
+pub const SEQUENCE_RESTART_ON_FAILURE: &str = "restart_on_failure";
+/// Re-evaluate the condition children that already succeeded while a later
+/// child is running, and abort it once one of them fails. Other children are
+/// not ticked again.
+pub const SEQUENCE_PREEMPT: &str = "preempt";
+
+/// What a Sequence does when one of its children fails.
+#[derive(Default, PartialEq, Eq, Debug, Clone, Copy)]
+pub enum ChildFailurePolicy {
+    /// Stop and fail immediately
+    #[default]
+    Fail,
+    /// Log the failure, continue with the next child and fail at the end
+    Skip,
+}
+
+impl FromStr for ChildFailurePolicy {
+    type Err = BtError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fail" => Ok(Self::Fail),
+            "skip" => Ok(Self::Skip),
+            _ => Err(BtError::Raw(format!("unknown child failure policy: {s}"))),
+        }
+    }
 }
 
 impl CompositeNodeImpl for Sequence {
     fn tick_status(
         &mut self,
-        _data_proxy: &mut DataProxy,
+        data_proxy: &mut DataProxy,
         child_nodes: &mut Vec<TreeNodeWrapper>,
     ) -> NodeStatus {
+        let status = self.tick_children(data_proxy, child_nodes);
+        report_active_child(data_proxy, child_nodes, self.current_child_idx);
+
+        status
+    }
+
+    fn node_info(&self) -> String {
+        format!(
+            "Sequence: current_child_idx= {} skipped_failures= {} restart_on_failure= {}",
+            self.current_child_idx, self.skipped_failures, self.restart_on_failure
+        )
+    }
+
+    fn reset_state(&mut self) {
+        let resume_idx = self.resume_idx.take();
+
+        *self = Self::new(self.restart_on_failure);
+        self.current_child_idx = resume_idx.unwrap_or_default();
+    }
+
+    fn provided_ports() -> Option<PortsList> {
+        Some(vec![
+            PortInfo::input::<String>(SEQUENCE_ON_CHILD_FAILURE).with_description("fail or skip"),
+            PortInfo::input::<bool>(SEQUENCE_RESTART_ON_FAILURE),
+            PortInfo::input::<bool>(SEQUENCE_PREEMPT),
+            PortInfo::output::<Value>(ACTIVE_CHILD),
+        ])
+    }
+}
+
+impl Sequence {
+    // whether an earlier condition now fails while the current child is running
+    fn preempted(&mut self, child_nodes: &mut [TreeNodeWrapper]) -> bool {
+        let running_idx = self.current_child_idx;
+        if child_nodes
+            .get(running_idx)
+            .is_none_or(|child| child.status() != NodeStatus::Running)
+        {
+            return false;
+        }
+
+        for idx in 0..running_idx {
+            let child = &mut child_nodes[idx];
+            if child.node_type() != NodeType::Condition || child.status() != NodeStatus::Success {
+                continue;
+            }
+
+            match child.tick() {
+                NodeStatus::Failure => {}
+                NodeStatus::Success => continue,
+                _ => {
+                    child.halt();
+                    child.reset_status();
+                    continue;
+                }
+            }
+
+            tracing::debug!(
+                "preempt running child: condition= {} running= {}",
+                child_nodes[idx].data_proxy_ref().full_path(),
+                child_nodes[running_idx].data_proxy_ref().full_path()
+            );
+
+            let running = &mut child_nodes[running_idx];
+            running.halt();
+            running.reset_status();
+
+            self.current_child_idx = idx;
+            if !self.restart_on_failure {
+                self.resume_idx = Some(idx);
+            }
+
+            return true;
+        }
+
+        false
+    }
+
+    fn tick_children(
+        &mut self,
+        data_proxy: &mut DataProxy,
+        child_nodes: &mut [TreeNodeWrapper],
+    ) -> NodeStatus {
+        let on_child_failure: ChildFailurePolicy = data_proxy
+            .get_input::<String>(SEQUENCE_ON_CHILD_FAILURE)
+            .and_then(|v| v.parse().ok())
+            .unwrap_or_default();
+
+        let preempt = data_proxy.get_input::<bool>(SEQUENCE_PREEMPT) == Some(true);
+        if preempt && self.preempted(child_nodes) {
+            return NodeStatus::Failure;
+        }
+
         let from = self.current_child_idx;
 
         for node in child_nodes.iter_mut().skip(from) {
             match node.tick() {
+                NodeStatus::Failure if on_child_failure == ChildFailurePolicy::Skip => {
+                    tracing::info!(
+                        "skip failed child: uid= {} path= {}",
+                        node.uid(),
+                        node.data_proxy_ref().full_path()
+                    );
+
+                    self.skipped_failures += 1;
+                    self.current_child_idx += 1;
+                }
                 NodeStatus::Failure => {
+                    if !self.restart_on_failure {
+                        self.resume_idx = Some(self.current_child_idx);
+                    }
+
                     return NodeStatus::Failure;
                 }
                 NodeStatus::Running => {
@@ -101,19 +362,102 @@ impl CompositeNodeImpl for Sequence {
                 NodeStatus::Success => {
                     self.current_child_idx += 1;
                 }
+                NodeStatus::Skipped => {
+                    self.skipped_children += 1;
+                    self.current_child_idx += 1;
+                }
                 NodeStatus::Idle => return NodeStatus::Failure,
             }
         }
 
-        NodeStatus::Success
+        if self.skipped_failures > 0 {
+            NodeStatus::Failure
+        } else if self.skipped_children == child_nodes.len() {
+            NodeStatus::Skipped
+        } else {
+            NodeStatus::Success
+        }
     }
+}
 
-    fn node_info(&self) -> String {
-        format!("Sequence: current_child_idx= {}", self.current_child_idx)
+/// Output port receiving the index and path of the running, or finally
+/// deciding, child of a Sequence, Fallback or Switch after each tick.
+pub const ACTIVE_CHILD: &str = "active_child";
+
+fn report_active_child(data_proxy: &mut DataProxy, child_nodes: &[TreeNodeWrapper], idx: usize) {
+    let Some(port_value) = data_proxy.input_port(ACTIVE_CHILD) else {
+        return;
+    };
+
+    if !is_ref_key(port_value) {
+        tracing::warn!("{ACTIVE_CHILD} should reference a blackboard key: {port_value}");
+        return;
     }
 
-    fn reset_state(&mut self) {
-        self.current_child_idx = 0;
+    // past the end once every child completed, the last one decided
+    let idx = idx.min(child_nodes.len().saturating_sub(1));
+    let Some(child) = child_nodes.get(idx) else {
+        return;
+    };
+
+    let value = json!({
+        "index": idx,
+        "path": child.data_proxy_ref().full_path(),
+    });
+
+    if let Err(e) = data_proxy.set_output(ACTIVE_CHILD, value) {
+        tracing::error!("report active child meet failure: err= {e}");
+    }
+}
+
+/// How a copy-on-write Parallel merges a branch scope that writes a key
+/// already merged by another branch.
+#[derive(Default, PartialEq, Eq, Debug, Clone, Copy)]
+pub enum ConflictPolicy {
+    #[default]
+    LastWins,
+    FirstWins,
+    Fail,
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = BtError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "last_wins" => Ok(Self::LastWins),
+            "first_wins" => Ok(Self::FirstWins),
+            "fail" => Ok(Self::Fail),
+            _ => Err(BtError::Raw(format!("unknown conflict policy: {s}"))),
+        }
+    }
+}
+
+/// How many children of a Parallel have to succeed, or fail, to complete it.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum ParallelPolicy {
+    RequireOne,
+    RequireAll,
+}
+
+impl ParallelPolicy {
+    fn threshold(self, children_count: usize) -> usize {
+        match self {
+            Self::RequireOne => 1,
+            Self::RequireAll => children_count,
+        }
+    }
+}
+
+impl FromStr for ParallelPolicy {
+    type Err = BtError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "REQUIRE_ONE" => Ok(Self::RequireOne),
+            "REQUIRE_ALL" => Ok(Self::RequireAll),
+            _ => Err(BtError::Raw(format!("unknown parallel policy: {s}"))),
+        }
     }
 }
 
@@ -123,38 +467,329 @@ pub struct Parallel {
     failure_threshold: Option<usize>,
     success_count: usize,
     failure_count: usize,
-    completed_list: HashSet<usize>,
+    skipped_count: usize,
+    completed: HashMap<usize, NodeStatus>,
+    copy_on_write: bool,
+    conflict_policy: ConflictPolicy,
+    started: bool,
+    merged_keys: HashSet<String>,
+    // ticked at least once and not completed yet
+    active: HashSet<usize>,
 }
 
 pub const PARALLEL_SUCCESS_COUNT: &str = "success_count";
 pub const PARALLEL_FAILURE_COUNT: &str = "failure_count";
+/// Used when `success_count` isn't set
+pub const PARALLEL_SUCCESS_POLICY: &str = "success_policy";
+/// Used when `failure_count` isn't set
+pub const PARALLEL_FAILURE_POLICY: &str = "failure_policy";
+pub const PARALLEL_COPY_ON_WRITE: &str = "copy_on_write";
+pub const PARALLEL_CONFLICT_POLICY: &str = "conflict_policy";
+/// Output port receiving `[{"uid", "path", "status"}]` for every child when the Parallel completes
+pub const PARALLEL_CHILD_RESULTS: &str = "child_results";
+/// Tick the running children as tokio blocking tasks, so blocking actions
+/// overlap
+#[cfg(feature = "rt-tokio")]
+pub const PARALLEL_CONCURRENT: &str = "concurrent";
+/// At most this many children run at once, the others wait for a free slot in
+/// document order
+pub const PARALLEL_MAX_CONCURRENT: &str = "max_concurrent";
+
+impl Parallel {
+    pub fn new(copy_on_write: bool, conflict_policy: ConflictPolicy) -> Self {
+        Self {
+            copy_on_write,
+            conflict_policy,
+            ..Default::default()
+        }
+    }
+
+    fn child_scope(
+        data_proxy: &DataProxy,
+        child: &TreeNodeWrapper,
+    ) -> Option<Arc<parking_lot::RwLock<super::Blackboard>>> {
+        let child_bb = child.data_proxy_ref().shared_blackboard();
+
+        (!Arc::ptr_eq(child_bb, data_proxy.shared_blackboard())).then(|| child_bb.clone())
+    }
+
+    // returns false when the merge conflicts under ConflictPolicy::Fail
+    fn merge_child_scope(&mut self, data_proxy: &mut DataProxy, child: &TreeNodeWrapper) -> bool {
+        let Some(child_bb) = Self::child_scope(data_proxy, child) else {
+            return true;
+        };
+
+        let entries = child_bb.write().take_local();
+
+        for (key, entry) in entries {
+            let conflicted = !self.merged_keys.insert(key.clone());
+
+            if conflicted {
+                tracing::debug!(
+                    "parallel branch conflict: key= {key} child= {}",
+                    child.uid()
+                );
+
+                match self.conflict_policy {
+                    ConflictPolicy::LastWins => {}
+                    ConflictPolicy::FirstWins => continue,
+                    ConflictPolicy::Fail => return false,
+                }
+            }
+
+            let writer = child.data_proxy_ref().full_path().to_string();
+            if let Err(e) = data_proxy.blackboard().try_set_entry(key, entry, &writer) {
+                tracing::error!("merge parallel branch meet failure: err= {e}");
+            }
+        }
+
+        true
+    }
+}
 
 impl CompositeNodeImpl for Parallel {
     fn tick_status(
         &mut self,
         data_proxy: &mut DataProxy,
         child_nodes: &mut Vec<TreeNodeWrapper>,
+    ) -> NodeStatus {
+        let status = self.tick_children(data_proxy, child_nodes);
+
+        if status.is_completed() {
+            self.report_child_results(data_proxy, child_nodes);
+        }
+
+        status
+    }
+
+    fn reset_state(&mut self) {
+        *self = Self::new(self.copy_on_write, self.conflict_policy);
+    }
+
+    fn copy_on_write_children(&self) -> bool {
+        self.copy_on_write
+    }
+
+    fn provided_ports() -> Option<PortsList> {
+        Some(vec![
+            PortInfo::input::<usize>(PARALLEL_SUCCESS_COUNT),
+            PortInfo::input::<usize>(PARALLEL_FAILURE_COUNT),
+            PortInfo::input::<String>(PARALLEL_SUCCESS_POLICY),
+            PortInfo::input::<String>(PARALLEL_FAILURE_POLICY),
+            PortInfo::input::<bool>(PARALLEL_COPY_ON_WRITE),
+            PortInfo::input::<String>(PARALLEL_CONFLICT_POLICY),
+            PortInfo::output::<Value>(PARALLEL_CHILD_RESULTS),
+            #[cfg(feature = "rt-tokio")]
+            PortInfo::input::<bool>(PARALLEL_CONCURRENT),
+            PortInfo::input::<usize>(PARALLEL_MAX_CONCURRENT),
+        ])
+    }
+}
+
+#[cfg(feature = "rt-tokio")]
+type TickResult = (usize, TreeNodeWrapper, NodeStatus);
+
+// hands the node back even when the runtime drops the task unrun, a panicking
+// child fails instead of taking its node down with the task
+#[cfg(feature = "rt-tokio")]
+struct TickTask {
+    idx: usize,
+    node: Option<TreeNodeWrapper>,
+    results: mpsc::Sender<TickResult>,
+}
+
+#[cfg(feature = "rt-tokio")]
+impl TickTask {
+    fn run(mut self) {
+        let Some(mut node) = self.node.take() else {
+            return;
+        };
+
+        let status = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| node.tick()))
+            .unwrap_or_else(|_| {
+                tracing::error!("parallel child panicked: idx= {}", self.idx);
+                NodeStatus::Failure
+            });
+
+        let _ = self.results.send((self.idx, node, status));
+    }
+}
+
+#[cfg(feature = "rt-tokio")]
+impl Drop for TickTask {
+    fn drop(&mut self) {
+        if let Some(node) = self.node.take() {
+            tracing::error!("parallel child task dropped: idx= {}", self.idx);
+            let _ = self.results.send((self.idx, node, NodeStatus::Failure));
+        }
+    }
+}
+
+#[cfg(feature = "rt-tokio")]
+fn tick_as_tasks(nodes: Vec<(usize, TreeNodeWrapper)>) -> Vec<TickResult> {
+    let (results_tx, results) = mpsc::channel();
+
+    for (idx, node) in nodes {
+        let task = TickTask {
+            idx,
+            node: Some(node),
+            results: results_tx.clone(),
+        };
+
+        TokioRuntime.spawn_blocking(Box::new(move || task.run()));
+    }
+    drop(results_tx);
+
+    results.into_iter().collect()
+}
+
+impl Parallel {
+    // start waiting children, in order, while there are free slots
+    fn admit(&mut self, children_count: usize, max_concurrent: usize) {
+        for i in 0..children_count {
+            if self.active.len() >= max_concurrent {
+                break;
+            }
+
+            if !self.completed.contains_key(&i) {
+                self.active.insert(i);
+            }
+        }
+    }
+
+    // every child still running is ticked as a task, all of them back in
+    // `child_nodes` before returning
+    #[cfg(feature = "rt-tokio")]
+    fn tick_concurrently(
+        &mut self,
+        child_nodes: &mut Vec<TreeNodeWrapper>,
+    ) -> HashMap<usize, NodeStatus> {
+        let mut slots: Vec<_> = std::mem::take(child_nodes).into_iter().map(Some).collect();
+
+        let running = slots
+            .iter_mut()
+            .enumerate()
+            .filter(|(i, _)| self.active.contains(i))
+            .filter_map(|(i, slot)| Some((i, slot.take()?)))
+            .collect();
+
+        let mut ticked = HashMap::new();
+        for (i, node, status) in tick_as_tasks(running) {
+            slots[i] = Some(node);
+            ticked.insert(i, status);
+        }
+
+        child_nodes.extend(slots.into_iter().flatten());
+        ticked
+    }
+
+    fn report_child_results(&self, data_proxy: &mut DataProxy, child_nodes: &[TreeNodeWrapper]) {
+        let Some(port_value) = data_proxy.input_port(PARALLEL_CHILD_RESULTS) else {
+            return;
+        };
+
+        if !is_ref_key(port_value) {
+            tracing::warn!(
+                "{PARALLEL_CHILD_RESULTS} should reference a blackboard key: {port_value}"
+            );
+            return;
+        }
+
+        let results: Vec<_> = child_nodes
+            .iter()
+            .enumerate()
+            .map(|(i, child)| {
+                // children still running get halted once the Parallel completes
+                let status = self
+                    .completed
+                    .get(&i)
+                    .map(|status| format!("{status:?}"))
+                    .unwrap_or_else(|| "Halted".to_string());
+
+                json!({
+                    "uid": child.uid(),
+                    "path": child.data_proxy_ref().full_path(),
+                    "status": status,
+                })
+            })
+            .collect();
+
+        if let Err(e) = data_proxy.set_output(PARALLEL_CHILD_RESULTS, json!(results)) {
+            tracing::error!("report parallel child results meet failure: err= {e}");
+        }
+    }
+
+    // a Vec so concurrent ticks can hand the children to the tasks
+    #[cfg_attr(not(feature = "rt-tokio"), allow(clippy::ptr_arg))]
+    fn tick_children(
+        &mut self,
+        data_proxy: &mut DataProxy,
+        child_nodes: &mut Vec<TreeNodeWrapper>,
     ) -> NodeStatus {
         let children_count = child_nodes.len();
 
-        let success_threshold = data_proxy
-            .get_input(PARALLEL_SUCCESS_COUNT)
-            .unwrap_or(self.success_threshold.unwrap_or(children_count));
+        let threshold = |count_port: &str, policy_port: &str, fallback: Option<usize>| {
+            data_proxy
+                .get_input(count_port)
+                .or_else(|| {
+                    data_proxy
+                        .get_input::<String>(policy_port)?
+                        .parse::<ParallelPolicy>()
+                        .map_err(|e| tracing::warn!("{e}, fallback to default"))
+                        .ok()
+                        .map(|policy| policy.threshold(children_count))
+                })
+                .unwrap_or(fallback.unwrap_or(children_count))
+        };
 
-        let failure_threshold = data_proxy
-            .get_input(PARALLEL_FAILURE_COUNT)
-            .unwrap_or(self.failure_threshold.unwrap_or(children_count));
+        let success_threshold = threshold(
+            PARALLEL_SUCCESS_COUNT,
+            PARALLEL_SUCCESS_POLICY,
+            self.success_threshold,
+        );
+        let failure_threshold = threshold(
+            PARALLEL_FAILURE_COUNT,
+            PARALLEL_FAILURE_POLICY,
+            self.failure_threshold,
+        );
 
         if children_count == 0 {
             return NodeStatus::Failure;
         }
 
+        if !self.started {
+            self.started = true;
+
+            // drop leftovers of branches halted in a previous run
+            for child in child_nodes.iter() {
+                if let Some(child_bb) = Self::child_scope(data_proxy, child) {
+                    child_bb.write().take_local();
+                }
+            }
+        }
+
+        let max_concurrent = data_proxy
+            .get_input::<usize>(PARALLEL_MAX_CONCURRENT)
+            .filter(|max| *max > 0)
+            .unwrap_or(children_count);
+        self.admit(children_count, max_concurrent);
+
+        #[cfg(feature = "rt-tokio")]
+        let mut ticked = (data_proxy.get_input::<bool>(PARALLEL_CONCURRENT) == Some(true))
+            .then(|| self.tick_concurrently(child_nodes));
+        #[cfg(not(feature = "rt-tokio"))]
+        let mut ticked: Option<HashMap<usize, NodeStatus>> = None;
+
         for (i, node) in child_nodes.iter_mut().enumerate().take(children_count) {
-            if self.completed_list.contains(&i) {
+            if !self.active.contains(&i) {
                 continue;
             }
 
-            match node.tick() {
+            let status = match &mut ticked {
+                Some(ticked) => ticked.remove(&i).unwrap_or(NodeStatus::Failure),
+                None => node.tick(),
+            };
+            match status {
                 NodeStatus::Idle => return NodeStatus::Failure,
                 NodeStatus::Failure => {
                     self.failure_count += 1;
@@ -162,57 +797,493 @@ impl CompositeNodeImpl for Parallel {
                 NodeStatus::Success => {
                     self.success_count += 1;
                 }
+                NodeStatus::Skipped => {
+                    self.skipped_count += 1;
+                }
                 NodeStatus::Running => continue,
             }
 
-            self.completed_list.insert(i);
+            self.completed.insert(i, status);
+            self.active.remove(&i);
+
+            if self.copy_on_write && !self.merge_child_scope(data_proxy, node) {
+                return NodeStatus::Failure;
+            }
+
+            // skipped children count for neither side, the thresholds shrink
+            // to the children left
+            let counted = children_count - self.skipped_count;
+            if counted == 0 {
+                return NodeStatus::Skipped;
+            }
 
-            if self.success_count >= success_threshold {
+            if self.success_count >= success_threshold.min(counted) {
                 return NodeStatus::Success;
             }
 
-            if self.failure_count >= failure_threshold {
+            if self.failure_count >= failure_threshold.min(counted) {
                 return NodeStatus::Failure;
             }
         }
 
         NodeStatus::Running
     }
-
-    fn reset_state(&mut self) {
-        std::mem::swap(self, &mut Self::default());
-    }
 }
 
 #[derive(Default)]
 pub struct Selector {
     current_child_idx: usize,
+    skipped_children: usize,
 }
 
 impl CompositeNodeImpl for Selector {
     fn tick_status(
         &mut self,
-        _data_proxy: &mut DataProxy,
+        data_proxy: &mut DataProxy,
         child_nodes: &mut Vec<TreeNodeWrapper>,
     ) -> NodeStatus {
+        let status = self.tick_children(child_nodes);
+        report_active_child(data_proxy, child_nodes, self.current_child_idx);
+
+        status
+    }
+
+    fn reset_state(&mut self) {
+        *self = Self::default();
+    }
+
+    fn provided_ports() -> Option<PortsList> {
+        Some(vec![PortInfo::output::<Value>(ACTIVE_CHILD)])
+    }
+}
+
+impl Selector {
+    // state is reset by the wrapper once completed
+    fn tick_children(&mut self, child_nodes: &mut [TreeNodeWrapper]) -> NodeStatus {
         for node in child_nodes.iter_mut().skip(self.current_child_idx) {
             match node.tick() {
                 NodeStatus::Idle => return NodeStatus::Failure,
-                NodeStatus::Success => {
-                    self.reset_state();
-                    return NodeStatus::Success;
-                }
+                NodeStatus::Success => return NodeStatus::Success,
                 NodeStatus::Running => return NodeStatus::Running,
                 NodeStatus::Failure => {
                     self.current_child_idx += 1;
                 }
+                NodeStatus::Skipped => {
+                    self.skipped_children += 1;
+                    self.current_child_idx += 1;
+                }
+            }
+        }
+
+        if self.skipped_children == child_nodes.len() {
+            NodeStatus::Skipped
+        } else {
+            NodeStatus::Failure
+        }
+    }
+}
+
+pub const SWITCH_VARIABLE: &str = "variable";
+/// Prefix of the `case_1..case_N` ports of a Switch.
+pub const SWITCH_CASE_PREFIX: &str = "case_";
+
+/// Ticks the child whose `case_N` port equals `variable`, the child after the
+/// last case is the default branch.
+#[derive(Default)]
+pub struct Switch {
+    running_child_idx: Option<usize>,
+}
+
+impl Switch {
+    // blackboard values are compared by their string form, `1` matches `"1"`
+    fn port_string(data_proxy: &DataProxy, key: &str) -> Option<String> {
+        let raw = data_proxy.input_port(key)?;
+
+        if !is_ref_key(raw) {
+            return Some(raw.to_string());
+        }
+
+        let value = data_proxy
+            .shared_blackboard()
+            .read()
+            .get_entry(&strip_ref_tag(raw))?;
+
+        Some(match value {
+            serde_json::Value::String(s) => s,
+            v => v.to_string(),
+        })
+    }
+
+    fn matching_child(data_proxy: &DataProxy) -> usize {
+        let variable = Self::port_string(data_proxy, SWITCH_VARIABLE);
+
+        let mut idx = 0;
+        loop {
+            let Some(case) =
+                Self::port_string(data_proxy, &format!("{SWITCH_CASE_PREFIX}{}", idx + 1))
+            else {
+                // the default branch
+                return idx;
+            };
+
+            if variable.as_ref() == Some(&case) {
+                return idx;
+            }
+
+            idx += 1;
+        }
+    }
+}
+
+impl CompositeNodeImpl for Switch {
+    fn tick_status(
+        &mut self,
+        data_proxy: &mut DataProxy,
+        child_nodes: &mut Vec<TreeNodeWrapper>,
+    ) -> NodeStatus {
+        let idx = Self::matching_child(data_proxy);
+
+        // the variable changed while another branch was running
+        if let Some(running_idx) = self.running_child_idx.filter(|i| *i != idx) {
+            if let Some(child) = child_nodes.get_mut(running_idx) {
+                child.halt();
+                child.reset_status();
+            }
+        }
+
+        let Some(child) = child_nodes.get_mut(idx) else {
+            tracing::warn!(
+                "no branch for switch: path= {} idx= {idx}",
+                data_proxy.full_path()
+            );
+            return NodeStatus::Failure;
+        };
+
+        let status = child.tick();
+        self.running_child_idx = (status == NodeStatus::Running).then_some(idx);
+        report_active_child(data_proxy, child_nodes, idx);
+
+        status
+    }
+
+    fn reset_state(&mut self) {
+        *self = Self::default();
+    }
+}
+
+pub const PARALLEL_ALL_MAX_FAILURES: &str = "max_failures";
+
+/// Ticks every child to completion, then fails if more than `max_failures`
+/// (default 0) of them failed.
+#[derive(Default)]
+pub struct ParallelAll {
+    completed: HashSet<usize>,
+    failure_count: usize,
+    skipped_count: usize,
+}
+
+impl CompositeNodeImpl for ParallelAll {
+    fn tick_status(
+        &mut self,
+        data_proxy: &mut DataProxy,
+        child_nodes: &mut Vec<TreeNodeWrapper>,
+    ) -> NodeStatus {
+        for (i, node) in child_nodes.iter_mut().enumerate() {
+            if self.completed.contains(&i) {
+                continue;
+            }
+
+            match node.tick() {
+                NodeStatus::Running => continue,
+                NodeStatus::Failure | NodeStatus::Idle => self.failure_count += 1,
+                NodeStatus::Skipped => self.skipped_count += 1,
+                NodeStatus::Success => {}
+            }
+
+            self.completed.insert(i);
+        }
+
+        if self.completed.len() < child_nodes.len() {
+            return NodeStatus::Running;
+        }
+
+        if self.skipped_count == child_nodes.len() {
+            return NodeStatus::Skipped;
+        }
+
+        let max_failures = data_proxy.get_input(PARALLEL_ALL_MAX_FAILURES).unwrap_or(0);
+
+        if self.failure_count > max_failures {
+            NodeStatus::Failure
+        } else {
+            NodeStatus::Success
+        }
+    }
+
+    fn node_info(&self) -> String {
+        format!(
+            "ParallelAll: completed= {} failure_count= {}",
+            self.completed.len(),
+            self.failure_count
+        )
+    }
+
+    fn reset_state(&mut self) {
+        *self = Self::default();
+    }
+
+    fn provided_ports() -> Option<PortsList> {
+        Some(vec![
+            PortInfo::input::<usize>(PARALLEL_ALL_MAX_FAILURES).with_default("0")
+        ])
+    }
+}
+
+/// Index of the child a ManualSelector ticks, usually a blackboard entry set by
+/// an operator.
+pub const MANUAL_SELECTOR_SELECTED: &str = "selected";
+
+/// Ticks the child picked at runtime through `selected`, and keeps running
+/// while nothing is selected. Changing the selection halts the running child.
+///
+/// An operator can also select through the channel of
+/// [`TreeNodeWrapper::selection_senders`], a `Some` index sent there wins over
+/// `selected` until `None` is sent.
+pub struct ManualSelector {
+    running_child_idx: Option<usize>,
+    selection_tx: Arc<watch::Sender<Option<usize>>>,
+    selection: watch::Receiver<Option<usize>>,
+}
+
+impl Default for ManualSelector {
+    fn default() -> Self {
+        let (selection_tx, selection) = watch::channel(None);
+
+        Self {
+            running_child_idx: None,
+            selection_tx: Arc::new(selection_tx),
+            selection,
+        }
+    }
+}
+
+impl CompositeNodeImpl for ManualSelector {
+    fn tick_status(
+        &mut self,
+        data_proxy: &mut DataProxy,
+        child_nodes: &mut Vec<TreeNodeWrapper>,
+    ) -> NodeStatus {
+        let selected = self
+            .selection
+            .borrow()
+            .or_else(|| data_proxy.get_input::<usize>(MANUAL_SELECTOR_SELECTED));
+
+        if let Some(running_idx) = self.running_child_idx.filter(|i| Some(*i) != selected) {
+            tracing::debug!("selection changed, halt child: idx= {running_idx}");
+
+            if let Some(child) = child_nodes.get_mut(running_idx) {
+                child.halt();
+                child.reset_status();
+            }
+            self.running_child_idx = None;
+        }
+
+        let Some(idx) = selected else {
+            return NodeStatus::Running;
+        };
+
+        let Some(child) = child_nodes.get_mut(idx) else {
+            tracing::warn!(
+                "selected child out of range: path= {} idx= {idx}",
+                data_proxy.full_path()
+            );
+            return NodeStatus::Failure;
+        };
+
+        let status = child.tick();
+        self.running_child_idx = (status == NodeStatus::Running).then_some(idx);
+        report_active_child(data_proxy, child_nodes, idx);
+
+        status
+    }
+
+    // the selection outlives activations, like the blackboard entry does
+    fn reset_state(&mut self) {
+        self.running_child_idx = None;
+    }
+
+    fn selection_sender(&self) -> Option<Arc<watch::Sender<Option<usize>>>> {
+        Some(self.selection_tx.clone())
+    }
+
+    fn provided_ports() -> Option<PortsList> {
+        Some(vec![
+            PortInfo::input::<usize>(MANUAL_SELECTOR_SELECTED),
+            PortInfo::output::<Value>(ACTIVE_CHILD),
+        ])
+    }
+}
+
+/// `;` separated weight of each child, e.g. `3;1;1`.
+pub const WEIGHTED_RANDOM_WEIGHTS: &str = "weights";
+/// Seed of the random generator, random runs when missing.
+pub const WEIGHTED_RANDOM_SEED: &str = "seed";
+
+/// Picks one child per activation according to `weights` and ticks it until it
+/// completes.
+#[derive(Default)]
+pub struct WeightedRandomSelector {
+    // kept across activations so a seeded tree replays the same choices
+    rng: Option<StdRng>,
+    chosen_child_idx: Option<usize>,
+}
+
+impl WeightedRandomSelector {
+    fn choose(&mut self, data_proxy: &DataProxy, children_count: usize) -> Option<usize> {
+        let weights = data_proxy.get_input::<String>(WEIGHTED_RANDOM_WEIGHTS)?;
+        let weights = weights
+            .split(';')
+            .map(|w| w.trim().parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| tracing::warn!("invalid weights: {weights} err= {e}"))
+            .ok()?;
+
+        if weights.len() != children_count {
+            tracing::warn!(
+                "weights don't match children: path= {} weights= {} children= {children_count}",
+                data_proxy.full_path(),
+                weights.len()
+            );
+            return None;
+        }
+
+        let dist = WeightedIndex::new(&weights)
+            .map_err(|e| tracing::warn!("invalid weights: {weights:?} err= {e}"))
+            .ok()?;
+
+        let rng = self.rng.get_or_insert_with(|| {
+            match data_proxy.get_input::<u64>(WEIGHTED_RANDOM_SEED) {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_entropy(),
+            }
+        });
+
+        Some(dist.sample(rng))
+    }
+}
+
+impl CompositeNodeImpl for WeightedRandomSelector {
+    fn tick_status(
+        &mut self,
+        data_proxy: &mut DataProxy,
+        child_nodes: &mut Vec<TreeNodeWrapper>,
+    ) -> NodeStatus {
+        let idx = match self.chosen_child_idx {
+            Some(idx) => idx,
+            None => {
+                let Some(idx) = self.choose(data_proxy, child_nodes.len()) else {
+                    return NodeStatus::Failure;
+                };
+
+                self.chosen_child_idx = Some(idx);
+                idx
+            }
+        };
+
+        let status = child_nodes[idx].tick();
+        report_active_child(data_proxy, child_nodes, idx);
+
+        status
+    }
+
+    fn reset_state(&mut self) {
+        self.chosen_child_idx = None;
+    }
+
+    fn provided_ports() -> Option<PortsList> {
+        Some(vec![
+            PortInfo::input::<String>(WEIGHTED_RANDOM_WEIGHTS),
+            PortInfo::input::<u64>(WEIGHTED_RANDOM_SEED),
+            PortInfo::output::<Value>(ACTIVE_CHILD),
+        ])
+    }
+}
+
+pub const RECOVERY_NUMBER_OF_RETRIES: &str = "number_of_retries";
+
+/// Two children: when the first one fails, tick the second (recovery) one and
+/// retry the first, up to `number_of_retries` (default 1) times.
+#[derive(Default)]
+pub struct RecoveryNode {
+    retry_count: usize,
+    in_recovery: bool,
+}
+
+impl CompositeNodeImpl for RecoveryNode {
+    fn tick_status(
+        &mut self,
+        data_proxy: &mut DataProxy,
+        child_nodes: &mut Vec<TreeNodeWrapper>,
+    ) -> NodeStatus {
+        let [first, recovery] = child_nodes.as_mut_slice() else {
+            tracing::warn!(
+                "RecoveryNode needs exactly two children: path= {}",
+                data_proxy.full_path()
+            );
+            return NodeStatus::Failure;
+        };
+
+        let number_of_retries = data_proxy
+            .get_input(RECOVERY_NUMBER_OF_RETRIES)
+            .unwrap_or(1);
+
+        loop {
+            if !self.in_recovery {
+                match first.tick() {
+                    NodeStatus::Success => return NodeStatus::Success,
+                    NodeStatus::Running => return NodeStatus::Running,
+                    NodeStatus::Skipped => return NodeStatus::Skipped,
+                    NodeStatus::Failure | NodeStatus::Idle => {
+                        if self.retry_count >= number_of_retries {
+                            return NodeStatus::Failure;
+                        }
+
+                        self.in_recovery = true;
+                    }
+                }
+            }
+
+            match recovery.tick() {
+                NodeStatus::Running => return NodeStatus::Running,
+                // a skipped recovery can't make the retry succeed
+                NodeStatus::Failure | NodeStatus::Idle | NodeStatus::Skipped => {
+                    return NodeStatus::Failure
+                }
+                NodeStatus::Success => {
+                    self.in_recovery = false;
+                    self.retry_count += 1;
+
+                    first.reset_status();
+                    recovery.reset_status();
+                }
             }
         }
+    }
 
-        NodeStatus::Failure
+    fn node_info(&self) -> String {
+        format!(
+            "RecoveryNode: retry_count= {} in_recovery= {}",
+            self.retry_count, self.in_recovery
+        )
     }
 
     fn reset_state(&mut self) {
-        std::mem::swap(self, &mut Self::default());
+        *self = Self::default();
+    }
+
+    fn provided_ports() -> Option<PortsList> {
+        Some(vec![
+            PortInfo::input::<usize>(RECOVERY_NUMBER_OF_RETRIES).with_default("1")
+        ])
     }
 }
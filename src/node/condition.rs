@@ -0,0 +1,53 @@
+use crate::{NodeStatus, TreeNode};
+
+use super::{port::PortsList, DataProxy};
+
+/// Leaf checking some state, it completes within a single tick and has
+/// nothing to halt.
+pub trait ConditionNodeImpl: Send + Sync {
+    /// Must return Success or Failure, anything else is reported as an error
+    /// and treated as Failure.
+    fn tick_status(&mut self, data_proxy: &mut DataProxy) -> NodeStatus;
+
+    fn node_info(&self) -> String {
+        std::any::type_name::<Self>().to_string()
+    }
+
+    /// Ports accepted from the XML, `None` accepts any attribute.
+    fn provided_ports() -> Option<PortsList>
+    where
+        Self: Sized,
+    {
+        None
+    }
+}
+
+pub struct ConditionWrapper {
+    pub data_proxy: DataProxy,
+    node: Box<dyn ConditionNodeImpl>,
+}
+
+impl TreeNode for ConditionWrapper {
+    fn tick(&mut self) -> NodeStatus {
+        let mut status = self.node.tick_status(&mut self.data_proxy);
+
+        if !status.is_completed() {
+            self.data_proxy.report_error(format!(
+                "condition returned {status}: path= {}",
+                self.data_proxy.full_path()
+            ));
+            status = NodeStatus::Failure;
+        }
+
+        let status = self.data_proxy.intercept_transition(status);
+        self.data_proxy.set_status(status);
+
+        status
+    }
+}
+
+impl ConditionWrapper {
+    pub fn new(data_proxy: DataProxy, node: Box<dyn ConditionNodeImpl>) -> Self {
+        Self { data_proxy, node }
+    }
+}
@@ -1,6 +1,17 @@
-use crate::{NodeStatus, TreeNode, TreeNodeWrapper};
+use std::{collections::VecDeque, time::Duration};
 
-use super::DataProxy;
+use serde_json::Value;
+
+use crate::{
+    expr::{self, Expr},
+    BtError, NodeStatus, TreeNode, TreeNodeWrapper,
+};
+
+use super::{
+    is_ref_key,
+    port::{PortError, PortInfo, PortsList},
+    strip_ref_tag, DataProxy,
+};
 
 pub trait DecoratorNodeImpl: Send + Sync {
     fn tick_status(
@@ -12,6 +23,14 @@ pub trait DecoratorNodeImpl: Send + Sync {
         std::any::type_name::<Self>().to_string()
     }
     fn reset_state(&mut self) {}
+
+    /// Ports accepted from the XML, `None` accepts any attribute.
+    fn provided_ports() -> Option<PortsList>
+    where
+        Self: Sized,
+    {
+        None
+    }
 }
 
 pub struct DecoratorWrapper {
@@ -29,7 +48,8 @@ impl TreeNode for DecoratorWrapper {
         let tick_status = self
             .node_wrapper
             .tick_status(&mut self.data_proxy, &mut self.inner_node);
-        if tick_status.is_completed() {
+        let tick_status = self.data_proxy.intercept_transition(tick_status);
+        if tick_status.is_completed() || tick_status == NodeStatus::Skipped {
             self.halt();
         }
 
@@ -41,8 +61,13 @@ impl TreeNode for DecoratorWrapper {
     fn halt(&mut self) {
         tracing::debug!("halt self: {}", std::any::type_name::<Self>());
 
-        self.node_wrapper.reset_state();
+        // inner nodes may depend on state owned by the decorator, tear them down first
         self.reset_inner();
+        self.node_wrapper.reset_state();
+    }
+
+    fn is_halting(&self) -> bool {
+        self.inner_node.is_halting()
     }
 }
 
@@ -79,9 +104,14 @@ impl DecoratorNodeImpl for ForceSuccess {
     ) -> NodeStatus {
         match inner_node.tick() {
             NodeStatus::Running => NodeStatus::Running,
+            NodeStatus::Skipped => NodeStatus::Skipped,
             _ => NodeStatus::Success,
         }
     }
+
+    fn provided_ports() -> Option<PortsList> {
+        Some(vec![])
+    }
 }
 
 #[derive(Default)]
@@ -95,9 +125,14 @@ impl DecoratorNodeImpl for ForceFailure {
     ) -> NodeStatus {
         match inner_node.tick() {
             NodeStatus::Running => NodeStatus::Running,
+            NodeStatus::Skipped => NodeStatus::Skipped,
             _ => NodeStatus::Failure,
         }
     }
+
+    fn provided_ports() -> Option<PortsList> {
+        Some(vec![])
+    }
 }
 
 #[derive(Default)]
@@ -114,15 +149,21 @@ impl DecoratorNodeImpl for Inverter {
             NodeStatus::Failure => NodeStatus::Success,
             NodeStatus::Success => NodeStatus::Failure,
             NodeStatus::Idle => NodeStatus::Failure,
+            NodeStatus::Skipped => NodeStatus::Skipped,
         }
     }
+
+    fn provided_ports() -> Option<PortsList> {
+        Some(vec![])
+    }
 }
 
 #[derive(Default)]
 pub struct Repeat {
-    repeat_count: usize,
+    repeat_count: i64,
 }
 
+/// Number of cycles, `-1` repeats forever
 pub const NUM_CYCLES: &str = "num_cycles";
 
 impl DecoratorNodeImpl for Repeat {
@@ -131,11 +172,17 @@ impl DecoratorNodeImpl for Repeat {
         data_proxy: &mut DataProxy,
         inner_node: &mut TreeNodeWrapper,
     ) -> NodeStatus {
-        let num_cycles = data_proxy.get_input(NUM_CYCLES).unwrap_or(1);
+        let num_cycles: i64 = data_proxy.get_input(NUM_CYCLES).unwrap_or(1);
 
         tracing::trace!("bb num cycles: {num_cycles}");
 
-        if num_cycles == 0 {
+        if num_cycles < -1 {
+            data_proxy.report_error(format!("invalid {NUM_CYCLES}: {num_cycles}"));
+            return NodeStatus::Failure;
+        }
+
+        // the port may have been lowered while cycling
+        if num_cycles >= 0 && self.repeat_count >= num_cycles {
             return NodeStatus::Success;
         }
 
@@ -143,24 +190,56 @@ impl DecoratorNodeImpl for Repeat {
             a @ NodeStatus::Success | a @ NodeStatus::Failure => {
                 self.repeat_count += 1;
 
-                if self.repeat_count == num_cycles {
+                if num_cycles >= 0 && self.repeat_count >= num_cycles {
                     a
                 } else {
+                    inner_node.reset_status();
                     NodeStatus::Running
                 }
             }
-            res => res,
+            NodeStatus::Idle => NodeStatus::Failure,
+            NodeStatus::Running => NodeStatus::Running,
+            NodeStatus::Skipped => NodeStatus::Skipped,
         }
     }
 
     fn reset_state(&mut self) {
-        std::mem::swap(self, &mut Self::default());
+        *self = Self::default();
+    }
+
+    fn provided_ports() -> Option<PortsList> {
+        Some(vec![PortInfo::input::<i64>(NUM_CYCLES)
+            .with_default("1")
+            .with_description("-1 repeats forever")])
     }
 }
 
 #[derive(Default)]
 pub struct Retry {
     try_count: usize,
+    retry_at: Option<Duration>,
+}
+
+impl Retry {
+    // wait before attempt `try_count + 1`, growing by `backoff_factor` per failure
+    fn delay(data_proxy: &DataProxy, try_count: usize) -> Duration {
+        let Some(delay_msec) = data_proxy.get_input::<u64>(RETRY_DELAY_MSEC) else {
+            return Duration::ZERO;
+        };
+
+        let factor = data_proxy
+            .get_input::<f64>(BACKOFF_FACTOR)
+            .unwrap_or(1.0)
+            .max(1.0);
+        let exponent = try_count.saturating_sub(1).min(i32::MAX as usize) as i32;
+        let mut delay_msec = delay_msec as f64 * factor.powi(exponent);
+
+        if let Some(max_delay_msec) = data_proxy.get_input::<u64>(MAX_DELAY_MSEC) {
+            delay_msec = delay_msec.min(max_delay_msec as f64);
+        }
+
+        Duration::from_millis(delay_msec.min(u64::MAX as f64) as u64)
+    }
 }
 
 impl DecoratorNodeImpl for Retry {
@@ -169,17 +248,39 @@ impl DecoratorNodeImpl for Retry {
         data_proxy: &mut DataProxy,
         inner_node: &mut TreeNodeWrapper,
     ) -> NodeStatus {
-        let num_attempts = data_proxy.get_input(NUM_ATTEMPTS).unwrap_or(1);
+        let num_attempts = match data_proxy.try_get_input::<usize>(NUM_ATTEMPTS) {
+            Ok(num_attempts) => num_attempts,
+            Err(PortError::NotProvided { .. }) => 1,
+            Err(e) => {
+                data_proxy.report_error(e);
+                return NodeStatus::Failure;
+            }
+        };
+        let now = data_proxy.clock().now();
+
+        if let Some(retry_at) = self.retry_at {
+            if now < retry_at {
+                return NodeStatus::Running;
+            }
+
+            self.retry_at = None;
+        }
 
         while self.try_count <= num_attempts {
             match inner_node.tick() {
                 NodeStatus::Idle => return NodeStatus::Failure,
                 NodeStatus::Failure => {
                     self.try_count += 1;
-                    continue;
+
+                    let delay = Self::delay(data_proxy, self.try_count);
+                    if !delay.is_zero() && self.try_count <= num_attempts {
+                        self.retry_at = Some(now + delay);
+                        return NodeStatus::Running;
+                    }
                 }
                 NodeStatus::Running => return NodeStatus::Running,
                 NodeStatus::Success => return NodeStatus::Success,
+                NodeStatus::Skipped => return NodeStatus::Skipped,
             }
         }
 
@@ -187,11 +288,448 @@ impl DecoratorNodeImpl for Retry {
     }
 
     fn reset_state(&mut self) {
-        std::mem::swap(self, &mut Self::default());
+        *self = Self::default();
+    }
+
+    fn provided_ports() -> Option<PortsList> {
+        Some(vec![
+            PortInfo::input::<usize>(NUM_ATTEMPTS).with_default("1"),
+            PortInfo::input::<u64>(RETRY_DELAY_MSEC),
+            PortInfo::input::<f64>(BACKOFF_FACTOR),
+            PortInfo::input::<u64>(MAX_DELAY_MSEC),
+        ])
     }
 }
 
 pub const NUM_ATTEMPTS: &str = "num_attempts";
+/// Wait between a failure and the next attempt.
+pub const RETRY_DELAY_MSEC: &str = "delay_msec";
+/// Multiplies the delay after every failed attempt, 1 keeps it constant.
+pub const BACKOFF_FACTOR: &str = "backoff_factor";
+/// Upper bound of the growing delay.
+pub const MAX_DELAY_MSEC: &str = "max_delay_msec";
+
+#[derive(Default)]
+pub struct Delay {
+    deadline: Option<Duration>,
+}
+
+pub const DELAY_MSEC: &str = "delay_msec";
+
+impl DecoratorNodeImpl for Delay {
+    fn tick_status(
+        &mut self,
+        data_proxy: &mut DataProxy,
+        inner_node: &mut TreeNodeWrapper,
+    ) -> NodeStatus {
+        let now = data_proxy.clock().now();
+
+        let deadline = match self.deadline {
+            Some(deadline) => deadline,
+            None => {
+                let delay_msec = match data_proxy.try_get_input::<u64>(DELAY_MSEC) {
+                    Ok(delay_msec) => delay_msec,
+                    Err(e) => {
+                        data_proxy.report_error(e);
+                        return NodeStatus::Failure;
+                    }
+                };
+
+                let deadline = now + Duration::from_millis(delay_msec);
+                self.deadline = Some(deadline);
+
+                deadline
+            }
+        };
+
+        if now < deadline {
+            return NodeStatus::Running;
+        }
+
+        inner_node.tick()
+    }
+
+    fn reset_state(&mut self) {
+        *self = Self::default();
+    }
+
+    fn provided_ports() -> Option<PortsList> {
+        Some(vec![PortInfo::input::<u64>(DELAY_MSEC)])
+    }
+}
+
+#[derive(Default)]
+pub struct Timeout {
+    deadline: Option<Duration>,
+    grace_deadline: Option<Duration>,
+}
+
+pub const TIMEOUT_MSEC: &str = "msec";
+/// After the timeout, halt the child and keep Running for up to this long
+/// while it winds down, see [`TreeNode::is_halting`].
+pub const TIMEOUT_GRACE_MSEC: &str = "grace_msec";
+
+impl DecoratorNodeImpl for Timeout {
+    fn tick_status(
+        &mut self,
+        data_proxy: &mut DataProxy,
+        inner_node: &mut TreeNodeWrapper,
+    ) -> NodeStatus {
+        let now = data_proxy.clock().now();
+
+        if let Some(grace_deadline) = self.grace_deadline {
+            if inner_node.is_halting() && now < grace_deadline {
+                return NodeStatus::Running;
+            }
+
+            // already halted, keep the wrapper from halting it again
+            inner_node.reset_status();
+            return NodeStatus::Failure;
+        }
+
+        let deadline = match self.deadline {
+            Some(deadline) => deadline,
+            None => {
+                let msec = match data_proxy.try_get_input::<u64>(TIMEOUT_MSEC) {
+                    Ok(msec) => msec,
+                    Err(e) => {
+                        data_proxy.report_error(e);
+                        return NodeStatus::Failure;
+                    }
+                };
+
+                let deadline = now + Duration::from_millis(msec);
+                self.deadline = Some(deadline);
+
+                deadline
+            }
+        };
+
+        if now >= deadline {
+            tracing::debug!("timeout reached: uid= {}", data_proxy.uid());
+
+            let grace_msec = data_proxy.get_input::<u64>(TIMEOUT_GRACE_MSEC).unwrap_or(0);
+            if grace_msec > 0 && inner_node.status() == NodeStatus::Running {
+                inner_node.halt();
+
+                if inner_node.is_halting() {
+                    self.grace_deadline = Some(now + Duration::from_millis(grace_msec));
+                    return NodeStatus::Running;
+                }
+
+                // halted synchronously, keep the wrapper from halting it again
+                inner_node.reset_status();
+                return NodeStatus::Failure;
+            }
+
+            // the running inner node is halted when the wrapper completes
+            return NodeStatus::Failure;
+        }
+
+        inner_node.tick()
+    }
+
+    fn reset_state(&mut self) {
+        *self = Self::default();
+    }
+
+    fn provided_ports() -> Option<PortsList> {
+        Some(vec![
+            PortInfo::input::<u64>(TIMEOUT_MSEC),
+            PortInfo::input::<u64>(TIMEOUT_GRACE_MSEC).with_default("0"),
+        ])
+    }
+}
+
+// an expression port, parsed again only when the port value changes
+#[derive(Default)]
+struct CachedExpr(Option<(String, Expr)>);
+
+impl CachedExpr {
+    fn eval_bool(&mut self, data_proxy: &DataProxy, port: &str) -> Result<bool, BtError> {
+        let Some(source) = data_proxy.input_port(port) else {
+            return Err(BtError::Raw(format!("missing port: {port}")));
+        };
+
+        if self.0.as_ref().is_none_or(|(cached, _)| cached != source) {
+            self.0 = Some((source.to_string(), expr::parse(source)?));
+        }
+
+        let (_, expr) = self.0.as_ref().unwrap();
+        let bb = data_proxy.shared_blackboard().read();
+
+        expr.eval_bool(&|key| bb.get_entry(key))
+    }
+}
+
+/// Ticks the child only while the `if` expression holds, otherwise returns
+/// the `else` status (Failure by default). A running child is not re-checked.
+#[derive(Default)]
+pub struct Precondition {
+    expr: CachedExpr,
+}
+
+pub const PRECONDITION_IF: &str = "if";
+pub const PRECONDITION_ELSE: &str = "else";
+
+impl DecoratorNodeImpl for Precondition {
+    fn tick_status(
+        &mut self,
+        data_proxy: &mut DataProxy,
+        inner_node: &mut TreeNodeWrapper,
+    ) -> NodeStatus {
+        if inner_node.status() != NodeStatus::Running {
+            match self.expr.eval_bool(data_proxy, PRECONDITION_IF) {
+                Ok(true) => {}
+                Ok(false) => {
+                    return data_proxy
+                        .get_input::<String>(PRECONDITION_ELSE)
+                        .and_then(|s| s.parse().ok())
+                        .unwrap_or(NodeStatus::Failure);
+                }
+                Err(e) => {
+                    data_proxy.report_error(e);
+                    return NodeStatus::Failure;
+                }
+            }
+        }
+
+        inner_node.tick()
+    }
+
+    fn provided_ports() -> Option<PortsList> {
+        Some(vec![
+            PortInfo::input::<String>(PRECONDITION_IF),
+            PortInfo::input::<NodeStatus>(PRECONDITION_ELSE),
+        ])
+    }
+}
+
+/// Ticks the child once per element of `queue`, writing the element to
+/// `value` first. `queue` is either a blackboard array, popped from the front,
+/// or a static `a;b;c` list. Returns `if_empty` (Success by default) once the
+/// queue is exhausted and Failure as soon as the child fails.
+#[derive(Default)]
+pub struct LoopNode {
+    static_queue: Option<VecDeque<String>>,
+}
+
+pub const LOOP_QUEUE: &str = "queue";
+pub const LOOP_VALUE: &str = "value";
+pub const LOOP_IF_EMPTY: &str = "if_empty";
+
+impl LoopNode {
+    fn pop(&mut self, data_proxy: &DataProxy) -> Result<Option<Value>, BtError> {
+        let Some(queue) = data_proxy.input_port(LOOP_QUEUE) else {
+            return Err(BtError::Raw(format!("missing port: {LOOP_QUEUE}")));
+        };
+
+        if !is_ref_key(queue) {
+            let static_queue = self.static_queue.get_or_insert_with(|| {
+                queue
+                    .split(';')
+                    .map(str::trim)
+                    .filter(|v| !v.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            });
+
+            return Ok(static_queue.pop_front().map(Value::String));
+        }
+
+        data_proxy
+            .blackboard()
+            .pop_front(&strip_ref_tag(queue), data_proxy.full_path())
+    }
+}
+
+impl DecoratorNodeImpl for LoopNode {
+    fn tick_status(
+        &mut self,
+        data_proxy: &mut DataProxy,
+        inner_node: &mut TreeNodeWrapper,
+    ) -> NodeStatus {
+        if inner_node.status() != NodeStatus::Running {
+            let item = match self.pop(data_proxy) {
+                Ok(Some(item)) => item,
+                Ok(None) => {
+                    return data_proxy
+                        .get_input::<String>(LOOP_IF_EMPTY)
+                        .and_then(|s| s.parse().ok())
+                        .unwrap_or(NodeStatus::Success);
+                }
+                Err(e) => {
+                    data_proxy.report_error(e);
+                    return NodeStatus::Failure;
+                }
+            };
+
+            if data_proxy.input_port(LOOP_VALUE).is_some() {
+                if let Err(e) = data_proxy.set_output(LOOP_VALUE, item) {
+                    data_proxy.report_error(e);
+                    return NodeStatus::Failure;
+                }
+            }
+        }
+
+        match inner_node.tick() {
+            NodeStatus::Success => {
+                inner_node.reset_status();
+                NodeStatus::Running
+            }
+            status => status,
+        }
+    }
+
+    fn reset_state(&mut self) {
+        *self = Self::default();
+    }
+
+    fn provided_ports() -> Option<PortsList> {
+        Some(vec![
+            PortInfo::inout::<Value>(LOOP_QUEUE),
+            PortInfo::output::<Value>(LOOP_VALUE),
+            PortInfo::input::<NodeStatus>(LOOP_IF_EMPTY),
+        ])
+    }
+}
+
+/// Checks `condition` on every tick, also while the child is running, and
+/// fails as soon as it is false, halting the child.
+#[derive(Default)]
+pub struct Guard {
+    condition: CachedExpr,
+}
+
+pub const GUARD_CONDITION: &str = "condition";
+
+impl DecoratorNodeImpl for Guard {
+    fn tick_status(
+        &mut self,
+        data_proxy: &mut DataProxy,
+        inner_node: &mut TreeNodeWrapper,
+    ) -> NodeStatus {
+        match self.condition.eval_bool(data_proxy, GUARD_CONDITION) {
+            Ok(true) => inner_node.tick(),
+            Ok(false) => {
+                tracing::debug!("guard failed: path= {}", data_proxy.full_path());
+                // the running child is halted when the wrapper completes
+                NodeStatus::Failure
+            }
+            Err(e) => {
+                data_proxy.report_error(e);
+                NodeStatus::Failure
+            }
+        }
+    }
+
+    fn provided_ports() -> Option<PortsList> {
+        Some(vec![PortInfo::input::<String>(GUARD_CONDITION)])
+    }
+}
+
+/// Measures the wall time of every child tick against `budget_usec`. Fails
+/// after `max_overruns` consecutive overruns (3 by default), each overrun is
+/// recorded under `overrun_key` as `{"consecutive", "elapsed_usec", "budget_usec"}`.
+#[derive(Default)]
+pub struct TickBudget {
+    consecutive_overruns: usize,
+}
+
+pub const TICK_BUDGET_USEC: &str = "budget_usec";
+pub const TICK_BUDGET_MAX_OVERRUNS: &str = "max_overruns";
+pub const TICK_BUDGET_OVERRUN_KEY: &str = "overrun_key";
+
+impl DecoratorNodeImpl for TickBudget {
+    fn tick_status(
+        &mut self,
+        data_proxy: &mut DataProxy,
+        inner_node: &mut TreeNodeWrapper,
+    ) -> NodeStatus {
+        let budget_usec = match data_proxy.try_get_input::<u64>(TICK_BUDGET_USEC) {
+            Ok(budget_usec) => budget_usec,
+            Err(e) => {
+                data_proxy.report_error(e);
+                return NodeStatus::Failure;
+            }
+        };
+
+        let start = std::time::Instant::now();
+        let status = inner_node.tick();
+        let elapsed_usec = start.elapsed().as_micros() as u64;
+
+        if elapsed_usec <= budget_usec {
+            self.consecutive_overruns = 0;
+            return status;
+        }
+
+        self.consecutive_overruns += 1;
+        tracing::warn!(
+            "tick budget overrun: path= {} elapsed_usec= {elapsed_usec} budget_usec= {budget_usec} consecutive= {}",
+            data_proxy.full_path(),
+            self.consecutive_overruns
+        );
+
+        if data_proxy.input_port(TICK_BUDGET_OVERRUN_KEY).is_some() {
+            let overrun = serde_json::json!({
+                "consecutive": self.consecutive_overruns,
+                "elapsed_usec": elapsed_usec,
+                "budget_usec": budget_usec,
+            });
+            if let Err(e) = data_proxy.set_output(TICK_BUDGET_OVERRUN_KEY, overrun) {
+                data_proxy.report_error(e);
+            }
+        }
+
+        let max_overruns = data_proxy
+            .get_input::<usize>(TICK_BUDGET_MAX_OVERRUNS)
+            .unwrap_or(3);
+        if self.consecutive_overruns >= max_overruns {
+            return NodeStatus::Failure;
+        }
+
+        status
+    }
+
+    fn reset_state(&mut self) {
+        *self = Self::default();
+    }
+
+    fn provided_ports() -> Option<PortsList> {
+        Some(vec![
+            PortInfo::input::<u64>(TICK_BUDGET_USEC),
+            PortInfo::input::<usize>(TICK_BUDGET_MAX_OVERRUNS).with_default("3"),
+            PortInfo::output::<Value>(TICK_BUDGET_OVERRUN_KEY),
+        ])
+    }
+}
+
+/// Mirrors the child's status of every tick into the `output_key` entry,
+/// e.g. `"RUNNING"`.
+#[derive(Default)]
+pub struct StatusToBlackboard;
+
+pub const STATUS_OUTPUT_KEY: &str = "output_key";
+
+impl DecoratorNodeImpl for StatusToBlackboard {
+    fn tick_status(
+        &mut self,
+        data_proxy: &mut DataProxy,
+        inner_node: &mut TreeNodeWrapper,
+    ) -> NodeStatus {
+        let status = inner_node.tick();
+
+        if let Err(e) = data_proxy.set_output(STATUS_OUTPUT_KEY, status.to_string()) {
+            data_proxy.report_error(e);
+        }
+
+        status
+    }
+
+    fn provided_ports() -> Option<PortsList> {
+        Some(vec![PortInfo::output::<String>(STATUS_OUTPUT_KEY)])
+    }
+}
 
 pub struct SubTree {
     _id: String,
@@ -0,0 +1,130 @@
+//! HTTP requests from trees, enabled by the `http` feature.
+
+use std::{thread::JoinHandle, time::Duration};
+
+use serde_json::Value;
+
+use crate::{BtError, NodeStatus};
+
+use super::{
+    action::ActionNodeImpl,
+    port::{PortInfo, PortsList},
+    DataProxy,
+};
+
+pub const HTTP_URL: &str = "url";
+/// `GET` by default
+pub const HTTP_METHOD: &str = "method";
+pub const HTTP_BODY: &str = "body";
+pub const HTTP_TIMEOUT_MSEC: &str = "timeout_msec";
+pub const HTTP_RESPONSE: &str = "response";
+pub const HTTP_STATUS_CODE: &str = "status_code";
+
+type Reply = Result<(u16, String), String>;
+
+/// Sends a request from a worker thread, Running while it is in flight and
+/// Success on a 2xx status. The response body and status code are written
+/// to the `response` and `status_code` ports when set. Halting abandons the
+/// request, its reply is discarded.
+#[derive(Default)]
+pub struct HttpRequest {
+    in_flight: Option<JoinHandle<Reply>>,
+}
+
+impl HttpRequest {
+    fn send(data_proxy: &DataProxy) -> Result<JoinHandle<Reply>, BtError> {
+        let url = data_proxy.try_get_input::<String>(HTTP_URL)?;
+
+        let method = data_proxy
+            .get_input::<String>(HTTP_METHOD)
+            .unwrap_or_else(|| "GET".to_string())
+            .to_uppercase();
+        let body = data_proxy.get_input::<String>(HTTP_BODY);
+        let timeout = data_proxy
+            .get_input::<u64>(HTTP_TIMEOUT_MSEC)
+            .map(Duration::from_millis);
+
+        tracing::debug!("http request: method= {method} url= {url}");
+
+        Ok(std::thread::spawn(move || {
+            let mut request = ureq::request(&method, &url);
+            if let Some(timeout) = timeout {
+                request = request.timeout(timeout);
+            }
+
+            let res = match body {
+                Some(body) => request.send_string(&body),
+                None => request.call(),
+            };
+
+            let response = match res {
+                Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+                Err(e) => return Err(e.to_string()),
+            };
+
+            let status = response.status();
+            response
+                .into_string()
+                .map(|body| (status, body))
+                .map_err(|e| e.to_string())
+        }))
+    }
+}
+
+impl ActionNodeImpl for HttpRequest {
+    fn tick_status(&mut self, data_proxy: &mut DataProxy) -> NodeStatus {
+        let in_flight = match self.in_flight.take() {
+            Some(in_flight) => in_flight,
+            None => match Self::send(data_proxy) {
+                Ok(in_flight) => in_flight,
+                Err(e) => {
+                    data_proxy.report_error(e);
+                    return NodeStatus::Failure;
+                }
+            },
+        };
+
+        if !in_flight.is_finished() {
+            self.in_flight = Some(in_flight);
+            return NodeStatus::Running;
+        }
+
+        let reply = in_flight
+            .join()
+            .unwrap_or_else(|_| Err("request thread panicked".to_string()));
+
+        match reply {
+            Ok((status, body)) => {
+                for (port, value) in [
+                    (HTTP_STATUS_CODE, Value::from(status)),
+                    (HTTP_RESPONSE, Value::String(body)),
+                ] {
+                    if let Err(e) = data_proxy.set_output_if_declared(port, value) {
+                        data_proxy.report_error(e);
+                    }
+                }
+
+                (200..300).contains(&status).into()
+            }
+            Err(e) => {
+                data_proxy.report_error(format!("http request failed: {e}"));
+                NodeStatus::Failure
+            }
+        }
+    }
+
+    fn halt(&mut self) {
+        self.in_flight = None;
+    }
+
+    fn provided_ports() -> Option<PortsList> {
+        Some(vec![
+            PortInfo::input::<String>(HTTP_URL),
+            PortInfo::input::<String>(HTTP_METHOD).with_default("GET"),
+            PortInfo::input::<String>(HTTP_BODY),
+            PortInfo::input::<u64>(HTTP_TIMEOUT_MSEC),
+            PortInfo::output::<String>(HTTP_RESPONSE),
+            PortInfo::output::<u16>(HTTP_STATUS_CODE),
+        ])
+    }
+}
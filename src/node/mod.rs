@@ -1,82 +1,1311 @@
 use std::{
-    collections::HashMap,
+    any::Any,
+    collections::{BTreeMap, HashMap, HashSet},
     str::FromStr,
-    sync::{Arc, Weak},
+    sync::{
+        atomic::{AtomicU64, AtomicU8, Ordering},
+        Arc, Weak,
+    },
+    time::Duration,
 };
 
+use once_cell::sync::{Lazy, OnceCell};
 use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use tokio::sync::watch;
 
-use crate::NodeStatus;
+use crate::{
+    clock::Clock,
+    expr::Expr,
+    runtime::sync::{broadcast, watch},
+    BtError, NodeStatus, Result,
+};
+
+use self::audit::{current_node_uid, AccessKind, AuditLog, ResolvedScope};
+use self::port::{short_type_name, PortError};
+
+pub mod action;
+pub mod audit;
+pub mod composite;
+pub mod condition;
+pub mod decorator;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod port;
+#[cfg(feature = "process")]
+pub mod process;
+
+/// Blackboard entry of an arbitrary type, shared without a JSON round-trip.
+pub type AnyValue = Arc<dyn Any + Send + Sync>;
+
+/// Value stored under a blackboard key, JSON as written by the XML and most
+/// nodes, or any Rust value set with [`Blackboard::set_any`].
+#[derive(Clone)]
+pub enum Entry {
+    Json(Value),
+    Any {
+        value: AnyValue,
+        /// Shown as [`EntryMeta::type_name`]
+        type_name: Arc<str>,
+    },
+}
+
+impl Entry {
+    /// The JSON value, `None` for typed entries.
+    pub fn into_json(self) -> Option<Value> {
+        match self {
+            Entry::Json(value) => Some(value),
+            Entry::Any { .. } => None,
+        }
+    }
+
+    /// The typed value, `None` for JSON entries and values of another type.
+    pub fn downcast<T: Any + Send + Sync>(self) -> Option<Arc<T>> {
+        match self {
+            Entry::Json(_) => None,
+            Entry::Any { value, .. } => value.downcast().ok(),
+        }
+    }
+
+    pub fn type_name(&self) -> &str {
+        match self {
+            Entry::Json(value) => json_type_name(value),
+            Entry::Any { type_name, .. } => type_name,
+        }
+    }
+}
+
+impl std::fmt::Debug for Entry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Entry::Json(value) => write!(f, "{value}"),
+            Entry::Any { type_name, .. } => write!(f, "<{type_name}>"),
+        }
+    }
+}
+
+/// Interned blackboard key or port name, cloning doesn't allocate.
+pub type Key = Arc<str>;
+
+static INTERNER: Lazy<RwLock<HashSet<Key>>> = Lazy::new(Default::default);
+
+/// Shared [`Key`] for `s`, every call with the same content returns the same allocation.
+pub fn intern(s: &str) -> Key {
+    if let Some(key) = INTERNER.read().get(s) {
+        return key.clone();
+    }
+
+    let mut interner = INTERNER.write();
+    if let Some(key) = interner.get(s) {
+        return key.clone();
+    }
+
+    let key = Key::from(s);
+    interner.insert(key.clone());
+    key
+}
+
+pub type ValueValidator = Arc<dyn Fn(&Value) -> std::result::Result<(), String> + Send + Sync>;
+
+#[derive(Default, PartialEq, Eq, Debug, Clone, Copy)]
+pub enum ValidationPolicy {
+    /// Refuse to store invalid values
+    #[default]
+    Reject,
+    /// Store invalid values anyway, only log the violation
+    Log,
+}
+
+#[derive(Clone)]
+struct KeyValidator {
+    policy: ValidationPolicy,
+    validator: ValueValidator,
+}
+
+#[derive(Default)]
+pub struct Blackboard {
+    storage: RwLock<HashMap<Key, Entry>>,
+    subscribers: RwLock<HashMap<Key, watch::Sender<Value>>>,
+    // clock time after which an entry set with a ttl is gone
+    deadlines: RwLock<HashMap<Key, Duration>>,
+    metadata: RwLock<HashMap<Key, EntryMeta>>,
+    clock: Option<Arc<Clock>>,
+    parent_bb: Option<Weak<RwLock<Blackboard>>>,
+    internal_to_external: RwLock<HashMap<String, String>>,
+    validators: RwLock<HashMap<String, KeyValidator>>,
+    mounts: RwLock<HashMap<String, Arc<RwLock<Blackboard>>>>,
+    // private scopes only see parent entries through explicit remappings
+    private: bool,
+    // every key forwards to the same-named parent entry, writes included
+    autoremap: bool,
+    audit: Option<Arc<AuditLog>>,
+    events: OnceCell<broadcast::Sender<TreeEvent>>,
+}
+
+/// Who wrote an entry last and when, see [`Blackboard::entry_meta`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EntryMeta {
+    /// Uid of the node ticking while the entry was written, `None` outside a tick
+    pub writer_uid: Option<u16>,
+    /// [`Clock`] time of the write
+    pub updated_at: Duration,
+    /// JSON type, e.g. `number`, or the Rust type of entries set with `set_any`
+    pub type_name: String,
+    /// Grows with every write of the entry, never reused for the same key,
+    /// see [`Blackboard::get_entry_versioned`]
+    pub version: u64,
+}
+
+static GLOBAL_BLACKBOARD: Lazy<Arc<RwLock<Blackboard>>> = Lazy::new(Default::default);
+
+// shared by all blackboards, so a key removed and set again or restored
+// from a snapshot never reports an older version
+static WRITE_SEQ: AtomicU64 = AtomicU64::new(1);
+
+fn json_type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// JSON entries of every blackboard scope of a tree, keyed by the path of the
+/// first node using the scope, see [`crate::TreeNodeWrapper::blackboard_snapshot`].
+/// Typed entries and mounted segments are left out.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BlackboardSnapshot {
+    pub scopes: BTreeMap<String, HashMap<String, Value>>,
+}
+
+impl BlackboardSnapshot {
+    pub fn to_json_string(&self) -> Result<String> {
+        serde_json::to_string(self)
+            .map_err(|e| BtError::Raw(format!("serialize snapshot meet failure: err= {e}")))
+    }
+
+    pub fn from_json_str(s: &str) -> Result<Self> {
+        serde_json::from_str(s).map_err(|e| BtError::Raw(format!("invalid snapshot: err= {e}")))
+    }
+}
+
+/// One scope of a [`Blackboard::debug_dump`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScopeDump {
+    /// 0 for the dumped scope, 1 for its parent and so on
+    pub depth: usize,
+    pub private: bool,
+    pub autoremap: bool,
+    pub entries: BTreeMap<String, Value>,
+    /// Keys of entries set with `set_any`
+    pub typed_keys: Vec<String>,
+    /// Key in this scope to the parent key it reads, with the parent's value
+    pub remappings: BTreeMap<String, (String, Option<Value>)>,
+    pub mounts: Vec<String>,
+}
+
+/// Scopes from a blackboard up to the root, see [`Blackboard::debug_dump`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BlackboardDump {
+    pub scopes: Vec<ScopeDump>,
+}
+
+impl std::fmt::Display for BlackboardDump {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for scope in &self.scopes {
+            write!(f, "scope {}", scope.depth)?;
+            if scope.private {
+                f.write_str(" private")?;
+            }
+            if scope.autoremap {
+                f.write_str(" autoremap")?;
+            }
+            writeln!(f)?;
+
+            for (key, (parent_key, value)) in &scope.remappings {
+                match value {
+                    Some(value) => writeln!(f, "\t{key} -> {parent_key} = {value}")?,
+                    None => writeln!(f, "\t{key} -> {parent_key} (missing)")?,
+                }
+            }
+            for (key, value) in &scope.entries {
+                writeln!(f, "\t{key} = {value}")?;
+            }
+            for key in &scope.typed_keys {
+                writeln!(f, "\t{key} = <typed>")?;
+            }
+            for namespace in &scope.mounts {
+                writeln!(f, "\t{namespace}{MOUNT_SEPARATOR}* mounted")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Published with [`DataProxy::events`], e.g. by `PublishEvent`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TreeEvent {
+    pub name: String,
+    pub payload: Value,
+}
+
+const EVENT_CAPACITY: usize = 64;
+
+/// Separates the namespace of a mounted segment from the key inside it, e.g. `fleet/target`
+pub const MOUNT_SEPARATOR: char = '/';
+
+/// Prefix of keys resolved against the root blackboard, bypassing remappings
+/// and private scopes, e.g. `{@config}`. Trees on
+/// [`Blackboard::new_global_child`] resolve them against the global scope.
+pub const ROOT_KEY_PREFIX: char = '@';
+
+fn mount_namespace(key: &str) -> &str {
+    key.split_once(MOUNT_SEPARATOR)
+        .map(|(namespace, _)| namespace)
+        .unwrap_or_default()
+}
+
+impl std::fmt::Debug for Blackboard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Blackboard")
+            .field(
+                "parent_bb",
+                &self.parent_bb.as_ref().and_then(|pb| pb.upgrade()),
+            )
+            .field("internal_to_external", &self.internal_to_external)
+            .field("mounts", &self.mounts.read().keys())
+            .finish()
+    }
+}
+
+impl Blackboard {
+    pub fn extend_parent_remappings(&mut self, remappings: HashMap<String, String>) {
+        self.internal_to_external.write().extend(remappings);
+    }
+
+    pub fn port_remappings(&self) -> RwLockReadGuard<'_, HashMap<String, String>> {
+        self.internal_to_external.read()
+    }
+
+    pub fn parent(&self) -> Option<Arc<RwLock<Blackboard>>> {
+        self.parent_bb.as_ref().and_then(|a| a.upgrade())
+    }
+
+    pub fn new_with_parent(parent_bb: &Arc<RwLock<Blackboard>>) -> Self {
+        let parent_bb = Some(Arc::downgrade(parent_bb));
+
+        Self {
+            parent_bb,
+            ..Default::default()
+        }
+    }
+
+    /// The process wide scope, shared by every tree whose root blackboard was
+    /// created with [`Blackboard::new_global_child`].
+    pub fn global() -> Arc<RwLock<Blackboard>> {
+        GLOBAL_BLACKBOARD.clone()
+    }
+
+    /// Tree root scope with the global scope as its parent: entries not set
+    /// locally are read from it and `{@key}` reads and writes it.
+    pub fn new_global_child() -> Self {
+        Self::new_with_parent(&GLOBAL_BLACKBOARD)
+    }
+
+    pub fn set_private(&mut self, private: bool) {
+        self.private = private;
+    }
+
+    pub fn is_private(&self) -> bool {
+        self.private
+    }
+
+    /// Forward reads and writes of every key to the same-named parent entry,
+    /// as if each key was remapped with `{=}`.
+    pub fn set_autoremap(&mut self, autoremap: bool) {
+        self.autoremap = autoremap;
+    }
+
+    pub fn is_autoremap(&self) -> bool {
+        self.autoremap
+    }
+
+    fn autoremap_parent(&self) -> Option<Arc<RwLock<Blackboard>>> {
+        if self.autoremap {
+            self.parent()
+        } else {
+            None
+        }
+    }
+
+    /// Mount a blackboard shared with other trees, its keys are visible as
+    /// `namespace/key` from this blackboard and all of its subtree scopes.
+    pub fn mount(&mut self, namespace: String, segment: Arc<RwLock<Blackboard>>) {
+        self.mounts.write().insert(namespace, segment);
+    }
+
+    pub fn unmount(&mut self, namespace: &str) -> Option<Arc<RwLock<Blackboard>>> {
+        self.mounts.write().remove(namespace)
+    }
+
+    fn resolve_mount(&self, key: &str) -> Option<(Arc<RwLock<Blackboard>>, String)> {
+        if let Some((namespace, inner_key)) = key.split_once(MOUNT_SEPARATOR) {
+            if let Some(segment) = self.mounts.read().get(namespace) {
+                return Some((segment.clone(), inner_key.to_string()));
+            }
+        }
+
+        if self.private {
+            return None;
+        }
+
+        self.parent_bb
+            .as_ref()
+            .and_then(|a| a.upgrade())
+            .and_then(|parent_bb| parent_bb.read().resolve_mount(key))
+    }
+
+    /// Keys stored in this scope, not including parents or mounted segments.
+    pub fn keys(&self) -> Vec<String> {
+        self.purge_expired();
+        self.storage.read().keys().map(|k| k.to_string()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.purge_expired();
+        self.storage.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.purge_expired();
+        self.storage.read().is_empty()
+    }
+
+    /// Time source of entry ttls, the parent's clock or the global one unless set.
+    pub fn set_clock(&mut self, clock: Arc<Clock>) {
+        self.clock = Some(clock);
+    }
+
+    pub fn clock(&self) -> Arc<Clock> {
+        self.clock
+            .clone()
+            .or_else(|| self.parent().map(|parent_bb| parent_bb.read().clock()))
+            .unwrap_or_else(Clock::global)
+    }
+
+    // expired entries are dropped lazily, on the next access of this scope
+    fn purge_expired(&self) {
+        if self.deadlines.read().is_empty() {
+            return;
+        }
+
+        let now = self.clock().now();
+        let expired: Vec<_> = self
+            .deadlines
+            .read()
+            .iter()
+            .filter(|(_, deadline)| **deadline <= now)
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        for key in expired {
+            tracing::trace!("blackboard entry expired: key= {key}");
+            self.store_local(&key, None);
+        }
+    }
+
+    /// Remove and return the entries stored in this scope.
+    pub fn take_local(&mut self) -> HashMap<String, Entry> {
+        let mut metadata = self.metadata.write();
+
+        std::mem::take(&mut *self.storage.write())
+            .into_iter()
+            .inspect(|(k, _)| {
+                metadata.remove(k);
+            })
+            .map(|(k, v)| (k.to_string(), v))
+            .collect()
+    }
+
+    /// JSON entries stored in this scope.
+    pub fn local_entries(&self) -> HashMap<String, Value> {
+        self.purge_expired();
+        self.storage
+            .read()
+            .iter()
+            .filter_map(|(k, entry)| Some((k.to_string(), entry.clone().into_json()?)))
+            .collect()
+    }
+
+    // typed entries stored in this scope, the ones `local_entries` leaves out
+    pub(crate) fn local_typed_entries(&self) -> HashMap<String, Entry> {
+        self.storage
+            .read()
+            .iter()
+            .filter(|(_, entry)| matches!(entry, Entry::Any { .. }))
+            .map(|(k, entry)| (k.to_string(), entry.clone()))
+            .collect()
+    }
+
+    /// Replace the JSON entries stored in this scope, subscribers of changed
+    /// keys get notified. Typed entries are kept unless `entries` sets their key.
+    pub fn restore_local(&mut self, entries: HashMap<String, Value>) {
+        for key in self.local_entries().into_keys() {
+            if !entries.contains_key(&key) {
+                self.store_local(&key, None);
+            }
+        }
+
+        for (key, value) in entries {
+            self.store_local(&key, Some(value));
+        }
+    }
+
+    /// Entries, remappings and mounts of this scope and each of its ancestors,
+    /// printable with `{}` to find out why a key doesn't resolve.
+    pub fn debug_dump(&self) -> BlackboardDump {
+        let mut scopes = vec![self.dump_scope(0)];
+
+        let mut parent = self.parent();
+        while let Some(parent_bb) = parent {
+            let parent_bb = parent_bb.read();
+            scopes.push(parent_bb.dump_scope(scopes.len()));
+            parent = parent_bb.parent();
+        }
+
+        BlackboardDump { scopes }
+    }
+
+    fn dump_scope(&self, depth: usize) -> ScopeDump {
+        let parent = self.parent();
+
+        let remappings = self
+            .internal_to_external
+            .read()
+            .iter()
+            .map(|(key, parent_key)| {
+                let value = parent
+                    .as_ref()
+                    // no audit record, dumping isn't an access
+                    .and_then(|parent_bb| parent_bb.read().resolve(parent_key))
+                    .and_then(|(entry, _)| entry.into_json());
+
+                (key.clone(), (parent_key.clone(), value))
+            })
+            .collect();
+
+        let mut typed_keys: Vec<_> = self.local_typed_entries().into_keys().collect();
+        typed_keys.sort();
+        let mut mounts: Vec<_> = self.mounts.read().keys().cloned().collect();
+        mounts.sort();
+
+        ScopeDump {
+            depth,
+            private: self.private,
+            autoremap: self.autoremap,
+            entries: self.local_entries().into_iter().collect(),
+            typed_keys,
+            remappings,
+            mounts,
+        }
+    }
+
+    /// Every entry reachable through `get_entry` from this scope: own entries,
+    /// parent entries (also under their remapped names) and mounted segments.
+    pub fn visible_entries(&self) -> HashMap<String, Value> {
+        self.purge_expired();
+        let parent_entries = self
+            .parent_bb
+            .as_ref()
+            .and_then(|a| a.upgrade())
+            .map(|parent_bb| parent_bb.read().visible_entries())
+            .unwrap_or_default();
+
+        let mut entries = if self.private && !self.autoremap {
+            HashMap::new()
+        } else {
+            parent_entries.clone()
+        };
+
+        for (internal_key, external_key) in self.internal_to_external.read().iter() {
+            if let Some(value) = parent_entries.get(external_key).cloned() {
+                entries.insert(internal_key.clone(), value);
+            }
+        }
+
+        for (namespace, segment) in self.mounts.read().iter() {
+            for (key, value) in segment.read().visible_entries() {
+                entries.insert(format!("{namespace}{MOUNT_SEPARATOR}{key}"), value);
+            }
+        }
+
+        entries.extend(self.local_entries());
+
+        entries
+    }
+
+    /// Visible entries whose key matches a glob pattern (`*` and `?` wildcards),
+    /// sorted by key.
+    pub fn query(&self, pattern: &str) -> Vec<(String, Value)> {
+        let regex_s: String = pattern
+            .chars()
+            .map(|c| match c {
+                '*' => ".*".to_string(),
+                '?' => ".".to_string(),
+                c => regex::escape(&c.to_string()),
+            })
+            .collect();
+
+        let Ok(regex) = regex::Regex::new(&format!("^{regex_s}$")) else {
+            tracing::warn!("invalid blackboard query pattern: {pattern}");
+            return vec![];
+        };
+
+        let mut entries: Vec<_> = self
+            .visible_entries()
+            .into_iter()
+            .filter(|(k, _)| regex.is_match(k))
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+        entries
+    }
+
+    /// Record every access through this blackboard and its subtree scopes.
+    pub fn enable_audit(&mut self, capacity: usize) -> Arc<AuditLog> {
+        let audit = Arc::new(AuditLog::new(capacity));
+        self.audit = Some(audit.clone());
+
+        audit
+    }
+
+    /// Event channel of this blackboard, nodes use the one of the root
+    /// blackboard through [`DataProxy::events`].
+    pub fn events(&self) -> broadcast::Sender<TreeEvent> {
+        self.events
+            .get_or_init(|| broadcast::channel(EVENT_CAPACITY).0)
+            .clone()
+    }
+
+    pub fn audit_log(&self) -> Option<Arc<AuditLog>> {
+        self.audit.clone().or_else(|| {
+            self.parent_bb
+                .as_ref()
+                .and_then(|a| a.upgrade())
+                .and_then(|parent_bb| parent_bb.read().audit_log())
+        })
+    }
+
+    fn resolve(&self, key: &str) -> Option<(Entry, ResolvedScope)> {
+        if let Some(root_key) = key.strip_prefix(ROOT_KEY_PREFIX) {
+            return match self.parent() {
+                Some(parent_bb) => parent_bb.read().resolve(key),
+                None => self.resolve(root_key),
+            };
+        }
+
+        self.purge_expired();
+
+        if let Some(entry) = self.storage.read().get(key).cloned() {
+            Some((entry, ResolvedScope::Local))
+        } else if let Some((segment, inner_key)) = self.resolve_mount(key) {
+            let value = segment.read().entry(&inner_key);
+
+            value.map(|v| {
+                (
+                    v,
+                    ResolvedScope::Mount {
+                        namespace: mount_namespace(key).to_string(),
+                        key: inner_key,
+                    },
+                )
+            })
+        } else {
+            let i_to_e_guard = self.internal_to_external.read();
+
+            let parent_key = if let Some(external_key) = i_to_e_guard.get(key) {
+                external_key
+            } else if self.private && !self.autoremap {
+                return None;
+            } else {
+                key
+            };
+
+            let parent_bb = self.parent_bb.as_ref().and_then(|a| a.upgrade())?;
+            let resolved = parent_bb.read().resolve(parent_key);
+
+            resolved.map(|(v, scope)| {
+                let scope = match scope {
+                    ResolvedScope::Local => ResolvedScope::Parent {
+                        depth: 1,
+                        key: parent_key.to_string(),
+                    },
+                    ResolvedScope::Parent { depth, key } => ResolvedScope::Parent {
+                        depth: depth + 1,
+                        key,
+                    },
+                    scope => scope,
+                };
+
+                (v, scope)
+            })
+        }
+    }
+
+    /// JSON value of the entry `key` resolves to, `None` when missing or typed.
+    pub fn get_entry(&self, key: &str) -> Option<Value> {
+        self.entry(key)?.into_json()
+    }
+
+    /// Entry `key` resolves to, JSON or typed, following mounts and remappings
+    /// like [`Blackboard::get_entry`].
+    pub fn entry(&self, key: &str) -> Option<Entry> {
+        let resolved = self.resolve(key);
+
+        if let Some(audit) = self.audit_log() {
+            let scope = resolved
+                .as_ref()
+                .map(|(_, scope)| scope.clone())
+                .unwrap_or(ResolvedScope::Missing);
+
+            audit.record(AccessKind::Get, key, scope);
+        }
+
+        resolved.map(|(v, _)| v)
+    }
+
+    /// Entry with the [`EntryMeta::version`] of its last write, a changed version
+    /// tells the entry was written again without comparing values.
+    pub fn get_entry_versioned(&self, key: &str) -> Option<(Value, u64)> {
+        let value = self.get_entry(key)?;
+        let version = self.entry_meta(key).map_or(0, |meta| meta.version);
+
+        Some((value, version))
+    }
+
+    /// Typed entry stored with [`Blackboard::set_any`], `None` when missing or
+    /// of another type. Resolves like [`Blackboard::get_entry`].
+    pub fn get_any<T: Any + Send + Sync>(&self, key: &str) -> Option<Arc<T>> {
+        self.entry(key)?.downcast()
+    }
+
+    /// Store a value of any type, e.g. a client handle, it replaces a JSON
+    /// entry of the same key. `get_entry` returns `None` for it, `get_input`
+    /// of the same type clones it.
+    pub fn set_any<T: Any + Send + Sync>(&mut self, key: String, value: T) {
+        self.set_object(
+            key,
+            Entry::Any {
+                value: Arc::new(value),
+                type_name: short_type_name::<T>().into(),
+            },
+        );
+    }
+
+    pub fn set_any_shared(&mut self, key: String, value: AnyValue) {
+        self.set_object(
+            key,
+            Entry::Any {
+                value,
+                type_name: "dyn Any".into(),
+            },
+        );
+    }
+
+    /// Like [`Blackboard::try_set`] for an entry of either kind, typed entries
+    /// skip the validators.
+    pub fn try_set_entry(&mut self, key: String, entry: Entry, writer: &str) -> Result<()> {
+        match entry {
+            Entry::Json(value) => self.try_set(key, value, writer),
+            entry => {
+                self.set_object(key, entry);
+                Ok(())
+            }
+        }
+    }
+
+    fn set_object(&mut self, key: String, entry: Entry) {
+        tracing::trace!("set blackboard object: key= {key} entry= {entry:?}");
+
+        if let Some(root_key) = key.strip_prefix(ROOT_KEY_PREFIX) {
+            return match self.parent() {
+                Some(parent_bb) => parent_bb.write().set_object(key, entry),
+                None => self.set_object(root_key.to_string(), entry),
+            };
+        }
+
+        if let Some((segment, inner_key)) = self.resolve_mount(&key) {
+            return segment.write().set_object(inner_key, entry);
+        }
+
+        if let Some(parent_bb) = self.autoremap_parent() {
+            return parent_bb.write().set_object(key, entry);
+        }
+
+        if let Some(audit) = self.audit_log() {
+            audit.record(AccessKind::Set, &key, ResolvedScope::Local);
+        }
+
+        self.store_entry(&key, Some(entry));
+    }
+
+    fn new_meta(&self, type_name: &str) -> EntryMeta {
+        EntryMeta {
+            writer_uid: current_node_uid(),
+            updated_at: self.clock().now(),
+            type_name: type_name.to_string(),
+            version: WRITE_SEQ.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    /// Metadata of the entry `key` resolves to, JSON or typed, following
+    /// mounts and remappings like [`Blackboard::get_entry`].
+    pub fn entry_meta(&self, key: &str) -> Option<EntryMeta> {
+        if let Some(root_key) = key.strip_prefix(ROOT_KEY_PREFIX) {
+            return match self.parent() {
+                Some(parent_bb) => parent_bb.read().entry_meta(key),
+                None => self.entry_meta(root_key),
+            };
+        }
+
+        self.purge_expired();
+
+        if let Some(meta) = self.metadata.read().get(key).cloned() {
+            return Some(meta);
+        }
+
+        if let Some((segment, inner_key)) = self.resolve_mount(key) {
+            return segment.read().entry_meta(&inner_key);
+        }
+
+        let parent_key = match self.internal_to_external.read().get(key) {
+            Some(external_key) => external_key.clone(),
+            None if self.private && !self.autoremap => return None,
+            None => key.to_string(),
+        };
+
+        self.parent()?.read().entry_meta(&parent_key)
+    }
+
+    fn store_local(&self, key: &str, value: Option<Value>) -> Option<Value> {
+        self.store_entry(key, value.map(Entry::Json))?.into_json()
+    }
+
+    // every write ending up in this scope goes through here, a removed or
+    // typed entry is published as `Value::Null`
+    pub(crate) fn store_entry(&self, key: &str, entry: Option<Entry>) -> Option<Entry> {
+        self.deadlines.write().remove(key);
+
+        if let Some(tx) = self.subscribers.read().get(key) {
+            let value = entry.clone().and_then(Entry::into_json);
+            tx.send_replace(value.unwrap_or_default());
+        }
+
+        match entry {
+            Some(entry) => {
+                let key = intern(key);
+                let meta = self.new_meta(entry.type_name());
+                self.metadata.write().insert(key.clone(), meta);
+                self.storage.write().insert(key, entry)
+            }
+            None => {
+                self.metadata.write().remove(key);
+                self.storage.write().remove(key)
+            }
+        }
+    }
+
+    /// Watch the entry `key` resolves to, following mounts and remappings like
+    /// writes do. The receiver starts with the current value, `Value::Null`
+    /// when missing or removed.
+    pub fn subscribe(&self, key: &str) -> watch::Receiver<Value> {
+        if let Some(root_key) = key.strip_prefix(ROOT_KEY_PREFIX) {
+            return match self.parent() {
+                Some(parent_bb) => parent_bb.read().subscribe(key),
+                None => self.subscribe(root_key),
+            };
+        }
+
+        if let Some((segment, inner_key)) = self.resolve_mount(key) {
+            return segment.read().subscribe(&inner_key);
+        }
+
+        if let Some(parent_bb) = self.autoremap_parent() {
+            return parent_bb.read().subscribe(key);
+        }
+
+        let external_key = self.internal_to_external.read().get(key).cloned();
+        if let (Some(external_key), Some(parent_bb)) = (external_key, self.parent()) {
+            return parent_bb.read().subscribe(&external_key);
+        }
+
+        let current = self.get_entry(key).unwrap_or_default();
+        self.subscribers
+            .write()
+            .entry(intern(key))
+            .or_insert_with(|| watch::channel(current).0)
+            .subscribe()
+    }
+
+    pub fn register_validator(
+        &mut self,
+        key: String,
+        policy: ValidationPolicy,
+        validator: ValueValidator,
+    ) {
+        self.validators
+            .write()
+            .insert(key, KeyValidator { policy, validator });
+    }
+
+    // Err when the value must not be stored
+    fn validate(&self, key: &str, value: &Value, writer: &str) -> Result<()> {
+        let Some(KeyValidator { policy, validator }) = self.validators.read().get(key).cloned()
+        else {
+            return Ok(());
+        };
+
+        let Err(reason) = validator(value) else {
+            return Ok(());
+        };
+
+        let msg = format!(
+            "invalid blackboard value: key= {key} value= {value} writer= {writer} reason= {reason}"
+        );
+
+        match policy {
+            ValidationPolicy::Reject => Err(BtError::Raw(msg)),
+            ValidationPolicy::Log => {
+                tracing::warn!("{msg}");
+                Ok(())
+            }
+        }
+    }
+
+    pub fn set(&mut self, key: String, value: Value) {
+        if let Err(e) = self.try_set(key, value, "unknown") {
+            tracing::error!("{e}");
+        }
+    }
+
+    /// Like [`Blackboard::set`], but reports values rejected by a validator,
+    /// `writer` is the path of the writing node.
+    pub fn try_set(&mut self, key: String, value: Value, writer: &str) -> Result<()> {
+        self.try_set_expiring(key, value, None, writer)
+    }
+
+    /// Store an entry that `get_entry` stops returning once `ttl` of
+    /// [`Clock`] time passed, e.g. a sensor reading that goes stale.
+    pub fn set_with_ttl(&mut self, key: String, value: Value, ttl: Duration) {
+        if let Err(e) = self.try_set_expiring(key, value, Some(ttl), "unknown") {
+            tracing::error!("{e}");
+        }
+    }
+
+    pub fn try_set_with_ttl(
+        &mut self,
+        key: String,
+        value: Value,
+        ttl: Duration,
+        writer: &str,
+    ) -> Result<()> {
+        self.try_set_expiring(key, value, Some(ttl), writer)
+    }
+
+    fn try_set_expiring(
+        &mut self,
+        key: String,
+        value: Value,
+        ttl: Option<Duration>,
+        writer: &str,
+    ) -> Result<()> {
+        tracing::trace!(
+            "set blackboard: key= {key} value= {value:?} ttl= {ttl:?} writer= {writer}"
+        );
+
+        if let Some(root_key) = key.strip_prefix(ROOT_KEY_PREFIX) {
+            return match self.parent() {
+                Some(parent_bb) => parent_bb.write().try_set_expiring(key, value, ttl, writer),
+                None => self.try_set_expiring(root_key.to_string(), value, ttl, writer),
+            };
+        }
+
+        if let Some((segment, inner_key)) = self.resolve_mount(&key) {
+            if let Some(audit) = self.audit_log() {
+                let scope = ResolvedScope::Mount {
+                    namespace: mount_namespace(&key).to_string(),
+                    key: inner_key.clone(),
+                };
+
+                audit.record(AccessKind::Set, &key, scope);
+            }
+
+            return segment
+                .write()
+                .try_set_expiring(inner_key, value, ttl, writer);
+        }
+
+        if let Some(parent_bb) = self.autoremap_parent() {
+            self.validate(&key, &value, writer)?;
+
+            return parent_bb.write().try_set_expiring(key, value, ttl, writer);
+        }
+
+        self.validate(&key, &value, writer)?;
+
+        if let Some(audit) = self.audit_log() {
+            audit.record(AccessKind::Set, &key, ResolvedScope::Local);
+        }
+
+        self.store_local(&key, Some(value));
+        if let Some(ttl) = ttl {
+            let deadline = self.clock().now() + ttl;
+            self.deadlines.write().insert(intern(&key), deadline);
+        }
+
+        Ok(())
+    }
+
+    /// Like [`Blackboard::try_set`], but a remapped key is written to the parent
+    /// entry it maps to, e.g. a SubTree output port.
+    pub fn try_set_through(&mut self, key: String, value: Value, writer: &str) -> Result<()> {
+        let external_key = self.internal_to_external.read().get(&key).cloned();
+
+        match (external_key, self.parent()) {
+            (Some(external_key), Some(parent_bb)) => {
+                parent_bb
+                    .write()
+                    .try_set_through(external_key, value, writer)
+            }
+            _ => self.try_set(key, value, writer),
+        }
+    }
+
+    /// Like [`Blackboard::try_set`], but `key` is written to the scope
+    /// [`Blackboard::get_entry`] reads it from, e.g. the parent entry a SubTree
+    /// key is remapped to, unless this scope holds the entry itself.
+    pub fn try_set_resolved(&mut self, key: String, value: Value, writer: &str) -> Result<()> {
+        if let Some((parent_bb, external_key)) = self.remapped_parent(&key) {
+            return parent_bb
+                .write()
+                .try_set_resolved(external_key, value, writer);
+        }
+
+        self.try_set(key, value, writer)
+    }
+
+    // runs the validators `try_set_resolved` would run without writing, so a
+    // caller can check several keys before writing any of them
+    pub(crate) fn check_set_resolved(&self, key: &str, value: &Value, writer: &str) -> Result<()> {
+        if let Some((parent_bb, external_key)) = self.remapped_parent(key) {
+            return parent_bb
+                .read()
+                .check_set_resolved(&external_key, value, writer);
+        }
+
+        self.check_set(key, value, writer)
+    }
+
+    // same for `try_set`, following the routing of `try_set_expiring`
+    fn check_set(&self, key: &str, value: &Value, writer: &str) -> Result<()> {
+        if let Some(root_key) = key.strip_prefix(ROOT_KEY_PREFIX) {
+            return match self.parent() {
+                Some(parent_bb) => parent_bb.read().check_set(key, value, writer),
+                None => self.check_set(root_key, value, writer),
+            };
+        }
+
+        if let Some((segment, inner_key)) = self.resolve_mount(key) {
+            return segment.read().check_set(&inner_key, value, writer);
+        }
+
+        self.validate(key, value, writer)?;
+
+        match self.autoremap_parent() {
+            Some(parent_bb) => parent_bb.read().check_set(key, value, writer),
+            None => Ok(()),
+        }
+    }
+
+    // parent scope and key a remapped `key` reads from, `None` for entries
+    // stored here or in a mount
+    fn remapped_parent(&self, key: &str) -> Option<(Arc<RwLock<Blackboard>>, String)> {
+        if key.starts_with(ROOT_KEY_PREFIX)
+            || self.storage.read().contains_key(key)
+            || self.resolve_mount(key).is_some()
+        {
+            return None;
+        }
+
+        let external_key = self.internal_to_external.read().get(key).cloned()?;
+
+        Some((self.parent()?, external_key))
+    }
+
+    // the entry as a queue, a missing entry is an empty queue
+    fn queue(&self, key: &str) -> Result<Vec<Value>> {
+        match self.get_entry(key) {
+            None => Ok(vec![]),
+            Some(Value::Array(items)) => Ok(items),
+            Some(_) => Err(BtError::Raw(format!("not an array: key= {key}"))),
+        }
+    }
+
+    /// Treat the `key` array as a FIFO queue and take its first element.
+    pub fn pop_front(&mut self, key: &str, writer: &str) -> Result<Option<Value>> {
+        let mut items = self.queue(key)?;
+        if items.is_empty() {
+            return Ok(None);
+        }
+
+        let item = items.remove(0);
+        self.try_set_resolved(key.to_string(), Value::Array(items), writer)?;
+
+        Ok(Some(item))
+    }
+
+    /// Append `value` to the `key` array, creating it when missing.
+    pub fn push_back(&mut self, key: &str, value: Value, writer: &str) -> Result<()> {
+        let mut items = self.queue(key)?;
+        items.push(value);
+
+        self.try_set_resolved(key.to_string(), Value::Array(items), writer)
+    }
+
+    pub fn queue_len(&self, key: &str) -> Result<usize> {
+        self.queue(key).map(|items| items.len())
+    }
+
+    /// Remove `key` from the scope `get_entry` would read it from, following
+    /// mounts and remappings to parent scopes.
+    pub fn remove(&mut self, key: &str) -> Option<Value> {
+        if let Some(root_key) = key.strip_prefix(ROOT_KEY_PREFIX) {
+            return match self.parent() {
+                Some(parent_bb) => parent_bb.write().remove(key),
+                None => self.remove(root_key),
+            };
+        }
+
+        self.purge_expired();
+
+        if self.storage.read().contains_key(key) {
+            return self.store_local(key, None);
+        }
+
+        if let Some((segment, inner_key)) = self.resolve_mount(key) {
+            return segment.write().remove(&inner_key);
+        }
+
+        let parent_key = match self.internal_to_external.read().get(key) {
+            Some(external_key) => external_key.clone(),
+            None if self.private && !self.autoremap => return None,
+            None => key.to_string(),
+        };
+
+        self.parent()?.write().remove(&parent_key)
+    }
+
+    /// Run `f` against a transaction, its writes are committed when `f` returns
+    /// `Ok` and discarded when it returns `Err` or a validator rejected one of
+    /// them.
+    ///
+    /// Only the keys stored in this scope become visible together, keys routed
+    /// to the root scope, a mount or an autoremapped parent are written like
+    /// [`Blackboard::try_set`] does, one scope at a time.
+    pub fn transaction<T, E: From<BtError>>(
+        &mut self,
+        f: impl FnOnce(&mut BlackboardTransaction) -> std::result::Result<T, E>,
+    ) -> std::result::Result<T, E> {
+        let mut tx = BlackboardTransaction {
+            bb: self,
+            pending: HashMap::new(),
+            rejected: None,
+        };
+
+        let res = f(&mut tx);
+        let BlackboardTransaction {
+            pending, rejected, ..
+        } = tx;
+
+        let value = match (res, rejected) {
+            (Ok(value), None) => value,
+            (Ok(_), Some(e)) => {
+                tracing::trace!("rollback blackboard transaction: err= {e}");
+                return Err(e.into());
+            }
+            (Err(e), _) => {
+                tracing::trace!("rollback blackboard transaction");
+                return Err(e);
+            }
+        };
+
+        tracing::trace!("commit blackboard transaction: keys= {:?}", pending.keys());
+        for (key, value) in pending {
+            if let Err(e) = self.try_set(key, value, TRANSACTION_WRITER) {
+                tracing::error!("commit blackboard transaction meet failure: err= {e}");
+            }
+        }
+
+        Ok(value)
+    }
+
+    /// Read-modify-write several keys while the caller holds this scope's lock,
+    /// e.g. through [`DataProxy::blackboard`], so nodes sharing the scope never
+    /// see a partial update. A [`Blackboard::transaction`] with [`BtError`] as
+    /// error type.
+    pub fn update<T>(
+        &mut self,
+        f: impl FnOnce(&mut BlackboardTransaction) -> Result<T>,
+    ) -> Result<T> {
+        self.transaction(f)
+    }
+}
 
-pub mod action;
-pub mod composite;
-pub mod decorator;
+const TRANSACTION_WRITER: &str = "transaction";
 
-#[derive(Default)]
-pub struct Blackboard {
-    storage: RwLock<HashMap<String, Value>>,
-    parent_bb: Option<Weak<RwLock<Blackboard>>>,
-    internal_to_external: RwLock<HashMap<String, String>>,
+pub struct BlackboardTransaction<'a> {
+    bb: &'a Blackboard,
+    pending: HashMap<String, Value>,
+    // the first write a validator rejected, the transaction won't commit
+    rejected: Option<BtError>,
 }
 
-impl std::fmt::Debug for Blackboard {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("Blackboard")
-            .field(
-                "parent_bb",
-                &self.parent_bb.as_ref().and_then(|pb| pb.upgrade()),
-            )
-            .field("internal_to_external", &self.internal_to_external)
-            .finish()
+impl BlackboardTransaction<'_> {
+    /// Read through the pending writes of this transaction.
+    pub fn get_entry(&self, key: &str) -> Option<Value> {
+        self.pending
+            .get(key)
+            .cloned()
+            .or_else(|| self.bb.get_entry(key))
+    }
+
+    /// Stage a write, a value rejected by a validator fails the whole
+    /// transaction even when the error is ignored.
+    pub fn set(&mut self, key: String, value: Value) -> Result<()> {
+        if let Err(e) = self.bb.check_set(&key, &value, TRANSACTION_WRITER) {
+            self.rejected
+                .get_or_insert_with(|| BtError::Raw(e.to_string()));
+            return Err(e);
+        }
+
+        self.pending.insert(key, value);
+
+        Ok(())
+    }
+
+    /// Replace the entry with `f` applied to its current value.
+    pub fn modify(&mut self, key: &str, f: impl FnOnce(Option<Value>) -> Value) -> Result<()> {
+        let value = f(self.get_entry(key));
+
+        self.set(key.to_string(), value)
     }
 }
 
-impl Blackboard {
-    pub fn extend_parent_remappings(&mut self, remappings: HashMap<String, String>) {
-        self.internal_to_external.write().extend(remappings);
+/// The outermost ancestor of `bb` below the global scope, i.e. the blackboard
+/// of the main tree. Trees on [`Blackboard::new_global_child`] keep their own
+/// event channel and last error.
+pub fn root_blackboard(bb: &Arc<RwLock<Blackboard>>) -> Arc<RwLock<Blackboard>> {
+    let mut current = bb.clone();
+
+    loop {
+        let parent = current.read().parent();
+
+        match parent {
+            Some(parent) if !Arc::ptr_eq(&parent, &GLOBAL_BLACKBOARD) => current = parent,
+            _ => return current,
+        }
     }
+}
 
-    pub fn port_remappings(&self) -> RwLockReadGuard<HashMap<String, String>> {
-        self.internal_to_external.read()
+/// Lock-free status of a node, shared between its [`DataProxy`] and any
+/// external reader, so statuses can be inspected while the tree is ticking.
+#[derive(Default, Debug, Clone)]
+pub struct StatusHandle(Arc<AtomicU8>);
+
+impl StatusHandle {
+    pub fn get(&self) -> NodeStatus {
+        Self::decode(self.0.load(Ordering::Acquire))
     }
 
-    pub fn new_with_parent(parent_bb: &Arc<RwLock<Blackboard>>) -> Self {
-        let parent_bb = Some(Arc::downgrade(parent_bb));
+    // returns the previous status
+    fn replace(&self, status: NodeStatus) -> NodeStatus {
+        let v = match status {
+            NodeStatus::Idle => 0,
+            NodeStatus::Success => 1,
+            NodeStatus::Failure => 2,
+            NodeStatus::Running => 3,
+            NodeStatus::Skipped => 4,
+        };
 
-        Self {
-            parent_bb,
-            ..Default::default()
+        Self::decode(self.0.swap(v, Ordering::AcqRel))
+    }
+
+    fn decode(v: u8) -> NodeStatus {
+        match v {
+            1 => NodeStatus::Success,
+            2 => NodeStatus::Failure,
+            3 => NodeStatus::Running,
+            4 => NodeStatus::Skipped,
+            _ => NodeStatus::Idle,
         }
     }
+}
 
-    pub fn get_entry(&self, key: &str) -> Option<Value> {
-        if let Some(v) = self.storage.read().get(key).cloned() {
-            Some(v)
-        } else {
-            let i_to_e_guard = self.internal_to_external.read();
+/// Intercepts the status a node returned from a tick, the returned status is
+/// used instead, e.g. keep `Running` until an external acknowledgment arrives.
+pub type TransitionHook = Arc<dyn Fn(&DataProxy, NodeStatus) -> NodeStatus + Send + Sync>;
 
-            let parent_key = if let Some(external_key) = i_to_e_guard.get(key) {
-                external_key
-            } else {
-                key
-            };
+/// Inline guards evaluated around a node's tick, parsed from the
+/// `_failureIf`/`_successIf`/`_skipIf`/`_while` attributes.
+#[derive(Default, Debug, Clone)]
+pub struct NodeConditions {
+    /// Fail without ticking when true
+    pub failure_if: Option<Expr>,
+    /// Succeed without ticking when true
+    pub success_if: Option<Expr>,
+    /// Skip the node when true, see [`NodeStatus::Skipped`]
+    pub skip_if: Option<Expr>,
+    /// Skip the node while false, a running node is halted
+    pub while_cond: Option<Expr>,
+}
 
-            if let Some(parent_bb) = self.parent_bb.as_ref().and_then(|a| a.upgrade()) {
-                let value = parent_bb.read().get_entry(parent_key);
+impl NodeConditions {
+    pub fn is_empty(&self) -> bool {
+        self.failure_if.is_none()
+            && self.success_if.is_none()
+            && self.skip_if.is_none()
+            && self.while_cond.is_none()
+    }
 
-                value
-            } else {
-                None
+    /// Status to return instead of ticking the node, if any.
+    pub fn check(&self, data_proxy: &DataProxy, running: bool) -> Result<Option<NodeStatus>> {
+        let bb = data_proxy.shared_blackboard().read();
+        let lookup = |key: &str| bb.get_entry(key);
+        let holds = |expr: &Option<Expr>| -> Result<Option<bool>> {
+            expr.as_ref().map(|e| e.eval_bool(&lookup)).transpose()
+        };
+
+        if !running {
+            if holds(&self.failure_if)? == Some(true) {
+                return Ok(Some(NodeStatus::Failure));
+            }
+            if holds(&self.success_if)? == Some(true) {
+                return Ok(Some(NodeStatus::Success));
+            }
+            if holds(&self.skip_if)? == Some(true) {
+                return Ok(Some(NodeStatus::Skipped));
             }
         }
-    }
 
-    pub fn set(&mut self, key: String, value: Value) {
-        tracing::trace!("set blackboard: key= {key} value= {value:?}");
+        if holds(&self.while_cond)? == Some(false) {
+            return Ok(Some(NodeStatus::Skipped));
+        }
 
-        self.storage.write().insert(key, value);
+        Ok(None)
     }
 }
 
@@ -88,13 +1317,52 @@ pub struct StateNotif {
     pub new_status: NodeStatus,
 }
 
+/// Error reported by a node through [`DataProxy::report_error`].
+#[derive(Default, PartialEq, Eq, Debug, Clone)]
+pub struct NodeError {
+    pub ts: i64,
+    pub uid: u16,
+    pub path: String,
+    pub message: String,
+}
+
+impl NodeError {
+    fn to_value(&self) -> Value {
+        serde_json::json!({
+            "ts": self.ts,
+            "uid": self.uid,
+            "path": self.path,
+            "message": self.message,
+        })
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        Some(Self {
+            ts: value.get("ts")?.as_i64()?,
+            uid: value.get("uid")?.as_u64()?.try_into().ok()?,
+            path: value.get("path")?.as_str()?.to_string(),
+            message: value.get("message")?.as_str()?.to_string(),
+        })
+    }
+}
+
+/// Root blackboard key holding the most recent [`NodeError`] of the tree
+pub const LAST_ERROR_KEY: &str = "_last_error";
+
 pub struct DataProxy {
     bb: Arc<RwLock<Blackboard>>,
-    input_ports: HashMap<String, String>,
-    status: NodeStatus,
+    input_ports: HashMap<Key, PortValue>,
+    status: StatusHandle,
     uid: u16,
     full_path: String,
+    name: Option<String>,
+    tags: Vec<String>,
+    metadata: BTreeMap<String, String>,
+    conditions: Option<Arc<NodeConditions>>,
+    transition_hooks: Vec<TransitionHook>,
     state_observer: watch::Sender<StateNotif>,
+    error_observer: watch::Sender<Option<NodeError>>,
+    clock: Arc<Clock>,
 }
 
 impl std::fmt::Debug for DataProxy {
@@ -105,10 +1373,28 @@ impl std::fmt::Debug for DataProxy {
     }
 }
 
+// raw port value with the blackboard key it references stripped once up front,
+// so reading a port doesn't allocate
+struct PortValue {
+    raw: String,
+    ref_key: Option<Key>,
+}
+
+impl PortValue {
+    fn new(raw: String) -> Self {
+        let ref_key = is_ref_key(&raw).then(|| intern(&strip_ref_tag(&raw)));
+
+        Self { raw, ref_key }
+    }
+}
+
 pub fn is_ref_key(key: &str) -> bool {
     key.starts_with('{') && key.ends_with('}')
 }
 
+/// Port value mapping a SubTree key to the parent key of the same name
+pub const SAME_NAME_REMAP: &str = "{=}";
+
 pub fn strip_ref_tag(key: &str) -> String {
     key.replace(['{', '}'], "")
 }
@@ -122,8 +1408,56 @@ impl DataProxy {
         &self.full_path
     }
 
+    pub fn set_name(&mut self, name: Option<String>) {
+        self.name = name;
+    }
+
+    /// Instance label from the `name` attribute
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Last segment of the full path, the type name followed by the instance
+    /// name if there is one, e.g. `Sleep(wait_dock)`
+    pub fn label(&self) -> &str {
+        self.full_path.split('/').next_back().unwrap_or("unknown")
+    }
+
+    pub fn set_metadata(&mut self, metadata: BTreeMap<String, String>) {
+        self.metadata = metadata;
+    }
+
+    /// `_` attributes the builder doesn't interpret, e.g. `_description`
+    pub fn metadata(&self) -> &BTreeMap<String, String> {
+        &self.metadata
+    }
+
+    pub fn set_tags(&mut self, tags: Vec<String>) {
+        self.tags = tags;
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    pub fn set_conditions(&mut self, conditions: NodeConditions) {
+        self.conditions = (!conditions.is_empty()).then(|| Arc::new(conditions));
+    }
+
+    pub fn conditions(&self) -> Option<&Arc<NodeConditions>> {
+        self.conditions.as_ref()
+    }
+
     pub fn path(&self) -> &str {
-        self.full_path.split('/').last().unwrap_or("unknown")
+        let label = self.label();
+
+        label
+            .split_once('(')
+            .map_or(label, |(type_name, _)| type_name)
     }
 
     pub fn new(bb: Arc<RwLock<Blackboard>>) -> Self {
@@ -136,40 +1470,150 @@ impl DataProxy {
         input_ports: HashMap<String, String>,
     ) -> Self {
         let (tx, _rx) = watch::channel(StateNotif::default());
+        let (error_tx, _rx) = watch::channel(None);
+
+        let input_ports = input_ports
+            .into_iter()
+            .map(|(key, value)| (intern(&key), PortValue::new(value)))
+            .collect();
 
         Self {
             bb,
             input_ports,
-            status: NodeStatus::default(),
+            status: StatusHandle::default(),
             uid,
             full_path: String::new(),
+            name: None,
+            metadata: BTreeMap::new(),
+            tags: Vec::new(),
+            conditions: None,
+            transition_hooks: Vec::new(),
             state_observer: tx,
+            error_observer: error_tx,
+            clock: Clock::global(),
         }
     }
 
     pub fn add_input(&mut self, key: String, value: String) {
-        self.input_ports.insert(key, value);
+        self.input_ports.insert(intern(&key), PortValue::new(value));
+    }
+
+    /// Raw port value as written in the XML, blackboard references are not resolved.
+    pub fn input_port(&self, key: &str) -> Option<&str> {
+        self.input_ports.get(key).map(|v| v.raw.as_str())
+    }
+
+    /// All raw port values, sorted by key.
+    pub fn input_ports(&self) -> Vec<(&str, &str)> {
+        let mut ports: Vec<_> = self
+            .input_ports
+            .iter()
+            .map(|(k, v)| (&**k, v.raw.as_str()))
+            .collect();
+        ports.sort();
+
+        ports
     }
 
-    pub fn get_input<T: FromStr>(&self, key: &str) -> Option<T>
+    pub fn get_input<T>(&self, key: &str) -> Option<T>
     where
-        for<'de> T: serde::Deserialize<'de>,
+        T: FromStr + for<'de> serde::Deserialize<'de> + Any + Clone,
     {
-        let Some(input_value_str) = self.input_ports.get(key) else {
-            return None;
+        self.try_get_input(key).ok()
+    }
+
+    /// Like [`DataProxy::get_input`], but tells a missing port, a missing
+    /// blackboard entry and a failed conversion apart. A typed entry of type
+    /// `T` is cloned out.
+    pub fn try_get_input<T>(&self, key: &str) -> std::result::Result<T, PortError>
+    where
+        T: FromStr + for<'de> serde::Deserialize<'de> + Any + Clone,
+    {
+        let Some(port_value) = self.input_ports.get(key) else {
+            return Err(PortError::NotProvided {
+                path: self.full_path.clone(),
+                port: key.to_string(),
+            });
+        };
+
+        let conversion_error = |value: String| PortError::Conversion {
+            path: self.full_path.clone(),
+            port: key.to_string(),
+            value,
+            type_name: std::any::type_name::<T>().to_string(),
         };
 
-        if is_ref_key(input_value_str) {
-            let stripped_key = strip_ref_tag(input_value_str);
+        let Some(ref_key) = &port_value.ref_key else {
+            return port_value
+                .raw
+                .parse()
+                .map_err(|_| conversion_error(port_value.raw.clone()));
+        };
 
-            let Some(bb_value) = self.bb.read().get_entry(&stripped_key) else {
-                return None;
-            };
+        let bb_value = match self.bb.read().entry(ref_key) {
+            Some(Entry::Json(bb_value)) => bb_value,
+            // typed entries are read back only as the type they were set with
+            Some(Entry::Any { value, type_name }) => {
+                return value
+                    .downcast_ref::<T>()
+                    .cloned()
+                    .ok_or_else(|| conversion_error(format!("<{type_name}>")));
+            }
+            None => {
+                return Err(PortError::KeyMissing {
+                    path: self.full_path.clone(),
+                    port: key.to_string(),
+                    key: ref_key.to_string(),
+                })
+            }
+        };
 
-            serde_json::from_value(bb_value).ok()
-        } else {
-            input_value_str.parse().ok()
+        // `"5"` reads as a number and `5` as a string
+        let converted = match &bb_value {
+            Value::String(s) => serde_json::from_value(bb_value.clone())
+                .ok()
+                .or_else(|| s.parse().ok()),
+            v => serde_json::from_value(v.clone())
+                .ok()
+                .or_else(|| v.to_string().parse().ok()),
+        };
+
+        converted.ok_or_else(|| conversion_error(bb_value.to_string()))
+    }
+
+    /// Write `value` to the entry the output port points to, e.g. `result="{my_result}"`,
+    /// following SubTree remappings.
+    pub fn set_output(&self, port: &str, value: impl Into<Value>) -> Result<()> {
+        let Some(port_value) = self.input_ports.get(port) else {
+            return Err(BtError::Raw(format!("missing port: {port}")));
+        };
+
+        let key = match &port_value.ref_key {
+            Some(ref_key) => ref_key.to_string(),
+            None => strip_ref_tag(&port_value.raw),
+        };
+
+        self.bb
+            .write()
+            .try_set_through(key, value.into(), &self.full_path)
+    }
+
+    /// [`DataProxy::set_output`] for optional output ports, a port the XML leaves
+    /// out is skipped.
+    pub fn set_output_if_declared(&self, port: &str, value: impl Into<Value>) -> Result<()> {
+        if !self.input_ports.contains_key(port) {
+            return Ok(());
         }
+
+        self.set_output(port, value)
+    }
+
+    /// Typed blackboard entry referenced by the port, see [`Blackboard::set_any`].
+    /// Literal port values are only readable with [`DataProxy::get_input`].
+    pub fn get_input_any<T: Any + Send + Sync>(&self, key: &str) -> Option<Arc<T>> {
+        let ref_key = self.input_ports.get(key)?.ref_key.as_ref()?;
+
+        self.bb.read().get_any(ref_key)
     }
 
     pub fn set_uid(&mut self, uid: u16) {
@@ -180,7 +1624,24 @@ impl DataProxy {
         self.uid
     }
 
-    pub fn blackboard(&self) -> RwLockWriteGuard<Blackboard> {
+    pub fn clock(&self) -> &Arc<Clock> {
+        &self.clock
+    }
+
+    pub fn set_clock(&mut self, clock: Arc<Clock>) {
+        self.clock = clock;
+    }
+
+    pub fn shared_blackboard(&self) -> &Arc<RwLock<Blackboard>> {
+        &self.bb
+    }
+
+    /// Event channel shared by every node of the tree.
+    pub fn events(&self) -> broadcast::Sender<TreeEvent> {
+        root_blackboard(&self.bb).read().events()
+    }
+
+    pub fn blackboard(&self) -> RwLockWriteGuard<'_, Blackboard> {
         self.bb.write()
     }
 
@@ -188,23 +1649,62 @@ impl DataProxy {
         self.state_observer.subscribe()
     }
 
-    pub fn reset_status(&mut self) {
+    pub fn add_error_observer(&self) -> watch::Receiver<Option<NodeError>> {
+        self.error_observer.subscribe()
+    }
+
+    /// Record `err` under [`LAST_ERROR_KEY`] in the root blackboard and notify
+    /// error observers of this node.
+    pub fn report_error(&self, err: impl std::fmt::Display) {
+        let error = NodeError {
+            ts: chrono::Utc::now().timestamp_millis(),
+            uid: self.uid,
+            path: self.full_path.clone(),
+            message: err.to_string(),
+        };
+
+        tracing::warn!("node report error: {error:?}");
+
+        let root_bb = root_blackboard(&self.bb);
+        let res = root_bb.write().try_set(
+            LAST_ERROR_KEY.to_string(),
+            error.to_value(),
+            &self.full_path,
+        );
+        if let Err(e) = res {
+            tracing::error!("store last error meet failure: err= {e}");
+        }
+
+        if self.error_observer.receiver_count() > 0 {
+            self.error_observer.send_replace(Some(error));
+        }
+    }
+
+    /// The most recent error reported by any node of the tree.
+    pub fn last_error(&self) -> Option<NodeError> {
+        let value = root_blackboard(&self.bb).read().get_entry(LAST_ERROR_KEY)?;
+
+        NodeError::from_value(&value)
+    }
+
+    pub fn reset_status(&self) {
         self.set_status(NodeStatus::Idle);
     }
 
-    pub fn set_status(&mut self, new_status: NodeStatus) {
+    pub fn set_status(&self, new_status: NodeStatus) {
+        let prev_status = self.status.replace(new_status);
         tracing::trace!(
             "set status: {} old= {:?} new= {:?}",
             self.uid(),
-            self.status(),
+            prev_status,
             new_status
         );
 
-        if new_status != self.status && self.state_observer.receiver_count() > 0 {
+        if new_status != prev_status && self.state_observer.receiver_count() > 0 {
             let notif = StateNotif {
                 ts: chrono::Utc::now().timestamp_millis(),
                 uid: self.uid,
-                prev_status: self.status,
+                prev_status,
                 new_status,
             };
 
@@ -213,10 +1713,395 @@ impl DataProxy {
                 tracing::warn!("all subscriber has closed");
             }
         }
-        self.status = new_status;
     }
 
     pub fn status(&self) -> NodeStatus {
-        self.status
+        self.status.get()
+    }
+
+    /// Handle for reading this node's status without borrowing the tree.
+    pub fn status_handle(&self) -> StatusHandle {
+        self.status.clone()
+    }
+
+    pub fn add_transition_hook(&mut self, hook: TransitionHook) {
+        self.transition_hooks.push(hook);
+    }
+
+    /// Run `proposed` through the transition hooks, in registration order.
+    pub fn intercept_transition(&self, proposed: NodeStatus) -> NodeStatus {
+        self.transition_hooks.iter().fold(proposed, |status, hook| {
+            let new_status = hook(self, status);
+            if new_status != status {
+                tracing::debug!(
+                    "transition intercepted: path= {} proposed= {status:?} new= {new_status:?}",
+                    self.full_path
+                );
+            }
+
+            new_status
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_transaction() {
+        let mut bb = Blackboard::default();
+
+        let res: Result<()> = bb.transaction(|tx| {
+            tx.set("x".to_string(), json!(1))?;
+            tx.set("y".to_string(), json!(2))?;
+            assert_eq!(tx.get_entry("x"), Some(json!(1)));
+
+            Err(BtError::Raw("abort".to_string()))
+        });
+        assert!(res.is_err());
+        assert_eq!(bb.get_entry("x"), None);
+
+        let res: Result<()> = bb.transaction(|tx| {
+            tx.set("x".to_string(), json!(1))?;
+            tx.set("y".to_string(), json!(2))?;
+
+            Ok(())
+        });
+        assert!(res.is_ok());
+        assert_eq!(bb.get_entry("x"), Some(json!(1)));
+        assert_eq!(bb.get_entry("y"), Some(json!(2)));
+        bb.register_validator(
+            "y".to_string(),
+            ValidationPolicy::Reject,
+            Arc::new(|v| {
+                v.as_i64()
+                    .map(|_| ())
+                    .ok_or("expect an integer".to_string())
+            }),
+        );
+
+        // the rejected write fails the commit even though `f` returns Ok
+        let res: Result<()> = bb.transaction(|tx| {
+            tx.set("x".to_string(), json!(3))?;
+            let _ = tx.set("y".to_string(), json!("three"));
+
+            Ok(())
+        });
+        assert!(res.is_err());
+        assert_eq!(bb.get_entry("x"), Some(json!(1)));
+        assert_eq!(bb.get_entry("y"), Some(json!(2)));
+    }
+
+    #[test]
+    fn test_update() {
+        let bb = Arc::new(RwLock::new(Blackboard::default()));
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let bb = bb.clone();
+
+                std::thread::spawn(move || {
+                    for _ in 0..100 {
+                        bb.write()
+                            .update(|tx| {
+                                let bump = |v: Option<Value>| {
+                                    json!(v.and_then(|v| v.as_i64()).unwrap_or(0) + 1)
+                                };
+                                tx.modify("x", bump)?;
+                                tx.modify("y", bump)
+                            })
+                            .unwrap();
+
+                        let bb = bb.read();
+                        assert_eq!(bb.get_entry("x"), bb.get_entry("y"));
+                    }
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(bb.read().get_entry("x"), Some(json!(400)));
+    }
+
+    #[test]
+    fn test_validator() {
+        let mut bb = Blackboard::default();
+        bb.register_validator(
+            "battery".to_string(),
+            ValidationPolicy::Reject,
+            Arc::new(|v| match v.as_f64() {
+                Some(level) if (0.0..=100.0).contains(&level) => Ok(()),
+                _ => Err("expect a level between 0 and 100".to_string()),
+            }),
+        );
+
+        assert!(bb
+            .try_set("battery".to_string(), json!(120), "Sequence/Charge")
+            .is_err());
+        assert_eq!(bb.get_entry("battery"), None);
+
+        bb.set("battery".to_string(), json!(42));
+        assert_eq!(bb.get_entry("battery"), Some(json!(42)));
+    }
+
+    #[test]
+    fn test_mount() {
+        let segment = Arc::new(RwLock::new(Blackboard::default()));
+
+        let tree_a = Arc::new(RwLock::new(Blackboard::default()));
+        tree_a.write().mount("fleet".to_string(), segment.clone());
+        let subtree_a = Blackboard::new_with_parent(&tree_a);
+
+        let mut tree_b = Blackboard::default();
+        tree_b.mount("fleet".to_string(), segment.clone());
+
+        tree_b.set("fleet/target".to_string(), json!("dock"));
+
+        assert_eq!(segment.read().get_entry("target"), Some(json!("dock")));
+        assert_eq!(subtree_a.get_entry("fleet/target"), Some(json!("dock")));
+        assert_eq!(tree_b.get_entry("target"), None);
+    }
+
+    #[test]
+    fn test_any_value() {
+        struct Client {
+            id: u32,
+        }
+
+        let root = Arc::new(RwLock::new(Blackboard::default()));
+        root.write().set_any("client".to_string(), Client { id: 7 });
+
+        let mut subtree = Blackboard::new_with_parent(&root);
+        subtree
+            .extend_parent_remappings(HashMap::from([("conn".to_string(), "client".to_string())]));
+
+        let mut data_proxy = DataProxy::new(Arc::new(RwLock::new(subtree)));
+        data_proxy.add_input("client".to_string(), "{conn}".to_string());
+
+        let client = data_proxy.get_input_any::<Client>("client").unwrap();
+        assert_eq!(client.id, 7);
+        assert!(data_proxy.get_input_any::<String>("client").is_none());
+        assert_eq!(root.read().get_entry("client"), None);
+        assert_eq!(root.read().keys(), vec!["client".to_string()]);
+
+        // plain ports read typed entries of the requested type
+        root.write().set_any("speed".to_string(), 3i64);
+        data_proxy.add_input("speed".to_string(), "{speed}".to_string());
+        assert_eq!(data_proxy.get_input::<i64>("speed"), Some(3));
+        assert!(matches!(
+            data_proxy.try_get_input::<String>("speed"),
+            Err(PortError::Conversion { .. })
+        ));
+
+        root.write().set("client".to_string(), json!("offline"));
+        assert!(root.read().get_any::<Client>("client").is_none());
+    }
+
+    #[test]
+    fn test_subscribe() {
+        let root = Arc::new(RwLock::new(Blackboard::default()));
+        root.write().set("pose".to_string(), json!(1));
+
+        let mut subtree = Blackboard::new_with_parent(&root);
+        subtree
+            .extend_parent_remappings(HashMap::from([("target".to_string(), "pose".to_string())]));
+
+        let mut rx = subtree.subscribe("target");
+        assert_eq!(*rx.borrow_and_update(), json!(1));
+
+        subtree
+            .try_set_through("target".to_string(), json!(2), "test")
+            .unwrap();
+        assert!(rx.has_changed().unwrap());
+        assert_eq!(*rx.borrow_and_update(), json!(2));
+
+        // unrelated keys don't wake the receiver
+        root.write().set("other".to_string(), json!(3));
+        assert!(!rx.has_changed().unwrap());
+
+        root.write().remove("pose");
+        assert_eq!(*rx.borrow_and_update(), Value::Null);
+    }
+
+    #[test]
+    fn test_ttl() {
+        let mut bb = Blackboard::default();
+        bb.set_clock(Arc::new(Clock::new(1000.0)));
+
+        bb.set_with_ttl("obstacle".to_string(), json!(true), Duration::from_secs(1));
+        bb.set_with_ttl("pose".to_string(), json!(1), Duration::from_secs(1));
+        // a plain write makes the entry permanent again
+        bb.set("pose".to_string(), json!(2));
+        assert_eq!(bb.get_entry("obstacle"), Some(json!(true)));
+
+        std::thread::sleep(Duration::from_millis(5));
+
+        assert_eq!(bb.get_entry("obstacle"), None);
+        assert_eq!(bb.keys(), ["pose"]);
+    }
+
+    #[test]
+    fn test_entry_meta() {
+        let root = Arc::new(RwLock::new(Blackboard::default()));
+        root.write().set("pose".to_string(), json!({"x": 1}));
+
+        let mut child = Blackboard::new_with_parent(&root);
+        {
+            let _guard = audit::CurrentNodeGuard::enter(7);
+            child.set("speed".to_string(), json!(0.5));
+            child.set_any("client".to_string(), vec![1u8]);
+        }
+
+        let meta = child.entry_meta("speed").unwrap();
+        assert_eq!(meta.writer_uid, Some(7));
+        assert_eq!(meta.type_name, "number");
+        assert_eq!(child.entry_meta("client").unwrap().type_name, "Vec<u8>");
+
+        // resolved through the parent scope
+        let meta = child.entry_meta("pose").unwrap();
+        assert_eq!(meta.writer_uid, None);
+        assert_eq!(meta.type_name, "object");
+
+        child.remove("speed");
+        assert_eq!(child.entry_meta("speed"), None);
+    }
+
+    #[test]
+    fn test_entry_version() {
+        let mut bb = Blackboard::default();
+        assert_eq!(bb.get_entry_versioned("pose"), None);
+
+        bb.set("pose".to_string(), json!(1));
+        let (value, v1) = bb.get_entry_versioned("pose").unwrap();
+        assert_eq!(value, json!(1));
+
+        // same value, still a new write
+        bb.set("pose".to_string(), json!(1));
+        let (_, v2) = bb.get_entry_versioned("pose").unwrap();
+        assert!(v2 > v1);
+
+        bb.remove("pose");
+        bb.set("pose".to_string(), json!(1));
+        assert!(bb.get_entry_versioned("pose").unwrap().1 > v2);
+    }
+
+    #[test]
+    fn test_debug_dump() {
+        let root = Arc::new(RwLock::new(Blackboard::default()));
+        root.write().set("pose".to_string(), json!(1));
+
+        let mut subtree = Blackboard::new_with_parent(&root);
+        subtree.set_private(true);
+        subtree.extend_parent_remappings(HashMap::from([
+            ("target".to_string(), "pose".to_string()),
+            ("goal".to_string(), "missing".to_string()),
+        ]));
+        subtree.set("x".to_string(), json!("local"));
+
+        let dump = subtree.debug_dump();
+        assert_eq!(dump.scopes.len(), 2);
+        assert_eq!(
+            dump.to_string(),
+            "scope 0 private\n\tgoal -> missing (missing)\n\ttarget -> pose = 1\n\tx = \"local\"\nscope 1\n\tpose = 1\n"
+        );
+    }
+
+    #[test]
+    fn test_try_get_input() {
+        let bb = Arc::new(RwLock::new(Blackboard::default()));
+        bb.write().set("speed".to_string(), json!("fast"));
+
+        let mut data_proxy = DataProxy::new(bb);
+        data_proxy.set_full_path("Sequence/Move".to_string());
+        data_proxy.add_input("speed".to_string(), "{speed}".to_string());
+        data_proxy.add_input("goal".to_string(), "{goal}".to_string());
+
+        assert_eq!(
+            data_proxy.try_get_input::<String>("speed"),
+            Ok("fast".to_string())
+        );
+        assert!(matches!(
+            data_proxy.try_get_input::<f64>("speed"),
+            Err(PortError::Conversion { value, .. }) if value == "\"fast\""
+        ));
+        assert_eq!(
+            data_proxy.try_get_input::<String>("goal"),
+            Err(PortError::KeyMissing {
+                path: "Sequence/Move".to_string(),
+                port: "goal".to_string(),
+                key: "goal".to_string(),
+            })
+        );
+
+        let err = data_proxy.try_get_input::<String>("timeout").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "port not provided: path= Sequence/Move port= timeout"
+        );
+    }
+
+    #[test]
+    fn test_intern() {
+        let key = intern("target_pose");
+        assert!(Arc::ptr_eq(&key, &intern(&format!("target_{}", "pose"))));
+        assert!(!Arc::ptr_eq(&key, &intern("target_speed")));
+
+        let mut bb = Blackboard::default();
+        bb.set("target_pose".to_string(), json!(3));
+        assert_eq!(bb.get_entry("target_pose"), Some(json!(3)));
+        assert_eq!(bb.keys(), vec!["target_pose".to_string()]);
+    }
+
+    #[test]
+    fn test_query() {
+        let root = Arc::new(RwLock::new(Blackboard::default()));
+        root.write().set("pose_x".to_string(), json!(1.0));
+        root.write().set("pose_y".to_string(), json!(2.0));
+        root.write().set("mode".to_string(), json!("auto"));
+
+        let mut subtree = Blackboard::new_with_parent(&root);
+        subtree.extend_parent_remappings(HashMap::from([(
+            "target_x".to_string(),
+            "pose_x".to_string(),
+        )]));
+        subtree.set("pose_theta".to_string(), json!(0.5));
+
+        assert_eq!(subtree.len(), 1);
+        assert_eq!(
+            subtree.query("pose_*"),
+            vec![
+                ("pose_theta".to_string(), json!(0.5)),
+                ("pose_x".to_string(), json!(1.0)),
+                ("pose_y".to_string(), json!(2.0)),
+            ]
+        );
+        assert_eq!(
+            subtree.query("target_?"),
+            vec![("target_x".to_string(), json!(1.0))]
+        );
+    }
+
+    #[test]
+    fn test_report_error() {
+        let root = Arc::new(RwLock::new(Blackboard::default()));
+        let subtree = Arc::new(RwLock::new(Blackboard::new_with_parent(&root)));
+
+        let mut data_proxy = DataProxy::new_with_uid(3, subtree, HashMap::new());
+        data_proxy.set_full_path("Sequence/SubTree/MoveTo".to_string());
+        let rx = data_proxy.add_error_observer();
+
+        data_proxy.report_error("goal unreachable");
+
+        let last_error = DataProxy::new(root).last_error().unwrap();
+        assert_eq!(last_error.uid, 3);
+        assert_eq!(last_error.path, "Sequence/SubTree/MoveTo");
+        assert_eq!(last_error.message, "goal unreachable");
+        assert_eq!(rx.borrow().as_ref(), Some(&last_error));
     }
 }
@@ -0,0 +1,87 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Serialize;
+use thiserror::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum PortDirection {
+    Input,
+    Output,
+    InOut,
+}
+
+/// Port a node type accepts from the XML, see `provided_ports` of the node traits.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PortInfo {
+    pub name: String,
+    pub direction: PortDirection,
+    /// Rust type without module paths, e.g. `u64` or `String`
+    pub type_name: String,
+    pub default: Option<String>,
+    pub description: String,
+}
+
+pub type PortsList = Vec<PortInfo>;
+
+static PATH_SEGMENT: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"[A-Za-z_][A-Za-z0-9_]*::").expect("valid path segment regex"));
+
+pub(crate) fn short_type_name<T: ?Sized>() -> String {
+    PATH_SEGMENT
+        .replace_all(std::any::type_name::<T>(), "")
+        .into_owned()
+}
+
+impl PortInfo {
+    pub fn new<T: ?Sized>(name: &str, direction: PortDirection) -> Self {
+        Self {
+            name: name.to_string(),
+            direction,
+            type_name: short_type_name::<T>(),
+            default: None,
+            description: String::new(),
+        }
+    }
+
+    pub fn input<T: ?Sized>(name: &str) -> Self {
+        Self::new::<T>(name, PortDirection::Input)
+    }
+
+    pub fn output<T: ?Sized>(name: &str) -> Self {
+        Self::new::<T>(name, PortDirection::Output)
+    }
+
+    pub fn inout<T: ?Sized>(name: &str) -> Self {
+        Self::new::<T>(name, PortDirection::InOut)
+    }
+
+    pub fn with_default(mut self, default: impl Into<String>) -> Self {
+        self.default = Some(default.into());
+        self
+    }
+
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+}
+
+/// Why [`super::DataProxy::try_get_input`] couldn't produce a value.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PortError {
+    #[error("port not provided: path= {path} port= {port}")]
+    NotProvided { path: String, port: String },
+    #[error("blackboard key missing: path= {path} port= {port} key= {key}")]
+    KeyMissing {
+        path: String,
+        port: String,
+        key: String,
+    },
+    #[error("port conversion failed: path= {path} port= {port} value= {value} type= {type_name}")]
+    Conversion {
+        path: String,
+        port: String,
+        value: String,
+        type_name: String,
+    },
+}
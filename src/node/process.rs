@@ -0,0 +1,129 @@
+//! Shell out to external tools, enabled by the `process` feature.
+
+use std::{
+    io::Read,
+    process::{Child, Command, Stdio},
+    thread::JoinHandle,
+};
+
+use serde_json::Value;
+
+use crate::{BtError, NodeStatus};
+
+use super::{
+    action::ActionNodeImpl,
+    port::{PortInfo, PortsList},
+    DataProxy,
+};
+
+pub const COMMAND: &str = "command";
+/// `;` separated arguments
+pub const COMMAND_ARGS: &str = "args";
+pub const COMMAND_EXIT_CODE: &str = "exit_code";
+pub const COMMAND_STDOUT: &str = "stdout";
+
+/// Runs `command` with `args`, Running while the process is alive and
+/// Success on a zero exit code. The exit code and, when the `stdout` port is
+/// set, the output are written to the blackboard. Halting kills the process.
+#[derive(Default)]
+pub struct RunCommand {
+    child: Option<Child>,
+    stdout: Option<JoinHandle<String>>,
+}
+
+impl RunCommand {
+    fn spawn(&mut self, data_proxy: &DataProxy) -> Result<(), BtError> {
+        let program = data_proxy.try_get_input::<String>(COMMAND)?;
+
+        let args = data_proxy
+            .get_input::<String>(COMMAND_ARGS)
+            .unwrap_or_default();
+
+        let mut command = Command::new(&program);
+        command
+            .args(args.split(';').filter(|arg| !arg.is_empty()))
+            .stdin(Stdio::null());
+
+        let capture = data_proxy.input_port(COMMAND_STDOUT).is_some();
+        command.stdout(if capture {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        });
+
+        let mut child = command
+            .spawn()
+            .map_err(|e| BtError::Raw(format!("spawn failed: command= {program} err= {e}")))?;
+
+        // drained on its own thread, a full pipe would block the child
+        self.stdout = child.stdout.take().map(|mut stdout| {
+            std::thread::spawn(move || {
+                let mut output = String::new();
+                let _ = stdout.read_to_string(&mut output);
+
+                output
+            })
+        });
+        self.child = Some(child);
+
+        Ok(())
+    }
+}
+
+impl ActionNodeImpl for RunCommand {
+    fn tick_status(&mut self, data_proxy: &mut DataProxy) -> NodeStatus {
+        if self.child.is_none() {
+            if let Err(e) = self.spawn(data_proxy) {
+                data_proxy.report_error(e);
+                return NodeStatus::Failure;
+            }
+        }
+
+        let child = self.child.as_mut().unwrap();
+        let exit_status = match child.try_wait() {
+            Ok(None) => return NodeStatus::Running,
+            Ok(Some(exit_status)) => exit_status,
+            Err(e) => {
+                data_proxy.report_error(e);
+                self.halt();
+                return NodeStatus::Failure;
+            }
+        };
+        self.child = None;
+
+        if let Some(stdout) = self.stdout.take() {
+            let output = stdout.join().unwrap_or_default();
+            if let Err(e) = data_proxy.set_output_if_declared(COMMAND_STDOUT, output) {
+                data_proxy.report_error(e);
+            }
+        }
+
+        // killed by a signal on unix
+        let exit_code = exit_status.code().map(Value::from).unwrap_or_default();
+        if let Err(e) = data_proxy.set_output_if_declared(COMMAND_EXIT_CODE, exit_code) {
+            data_proxy.report_error(e);
+        }
+
+        exit_status.success().into()
+    }
+
+    fn halt(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            tracing::debug!("kill command: pid= {}", child.id());
+
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+
+        self.stdout = None;
+    }
+
+    fn provided_ports() -> Option<PortsList> {
+        Some(vec![
+            PortInfo::input::<String>(COMMAND),
+            PortInfo::input::<String>(COMMAND_ARGS).with_description("separated by `;`"),
+            PortInfo::output::<i64>(COMMAND_EXIT_CODE),
+            PortInfo::output::<String>(COMMAND_STDOUT),
+        ])
+    }
+}
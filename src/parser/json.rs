@@ -0,0 +1,345 @@
+//! Trees as JSON, for tooling that generates them programmatically.
+//!
+//! ```json
+//! {
+//!     "main_tree_to_execute": "main",
+//!     "trees": {
+//!         "main": {
+//!             "type": "Sequence",
+//!             "children": [
+//!                 { "type": "Sleep", "ports": { "msec": 10 } },
+//!                 { "type": "SubTree", "ports": { "ID": "dock" } }
+//!             ]
+//!         },
+//!         "dock": { "type": "SetBlackboard", "ports": { "value": "docked", "output_key": "state" } }
+//!     }
+//! }
+//! ```
+//!
+//! A bare node is a document with that node as its only tree. Documents are
+//! translated to XML and built by [`super::xml`], so ports, SubTrees and
+//! remappings behave the same. Build errors of a node are reported as
+//! [`BtError::JsonNode`] with its JSON pointer.
+
+use std::{collections::BTreeMap, fmt};
+
+use serde::{
+    de::{Error, MapAccess, Visitor},
+    Deserialize, Deserializer,
+};
+use serde_json::Value;
+
+use crate::{factory::Factory, BtError, Result, TreeNodeWrapper};
+
+use super::xml::{create_bt_tree_from_xml_str, push_attribute};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct JsonNode {
+    #[serde(rename = "type")]
+    pub type_name: String,
+    /// Attributes of the node, non-string values are written as their JSON text
+    #[serde(default)]
+    pub ports: BTreeMap<String, Value>,
+    #[serde(default)]
+    pub children: Vec<JsonNode>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct JsonDocument {
+    #[serde(default)]
+    pub main_tree_to_execute: Option<String>,
+    /// Trees by ID in document order, the first one is the main tree unless
+    /// `main_tree_to_execute` is set
+    #[serde(deserialize_with = "deserialize_trees")]
+    pub trees: Vec<(String, JsonNode)>,
+}
+
+const DEFAULT_TREE_ID: &str = "main";
+
+// the `trees` object as its entries in document order
+fn deserialize_trees<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Vec<(String, JsonNode)>, D::Error> {
+    struct TreesVisitor;
+
+    impl<'de> Visitor<'de> for TreesVisitor {
+        type Value = Vec<(String, JsonNode)>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("an object of trees by ID")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(
+            self,
+            mut map: A,
+        ) -> std::result::Result<Self::Value, A::Error> {
+            let mut trees: Vec<(String, JsonNode)> = vec![];
+
+            while let Some((id, node)) = map.next_entry::<String, JsonNode>()? {
+                if trees.iter().any(|(known, _)| known == &id) {
+                    return Err(A::Error::custom(format!("duplicate tree: {id}")));
+                }
+
+                trees.push((id, node));
+            }
+
+            Ok(trees)
+        }
+    }
+
+    deserializer.deserialize_map(TreesVisitor)
+}
+
+// a node element of the translated XML, to map build errors back to the JSON
+struct NodeSpan {
+    offset: usize,
+    type_name: String,
+    pointer: String,
+}
+
+// `s` escaped as a JSON pointer reference token
+fn pointer_token(s: &str) -> String {
+    s.replace('~', "~0").replace('/', "~1")
+}
+
+fn is_xml_name(s: &str) -> bool {
+    let mut chars = s.chars();
+
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+fn write_node(
+    xml: &mut String,
+    node: &JsonNode,
+    pointer: String,
+    spans: &mut Vec<NodeSpan>,
+) -> Result<()> {
+    if !is_xml_name(&node.type_name) {
+        return Err(BtError::Raw(format!(
+            "invalid node type: {}",
+            node.type_name
+        )));
+    }
+
+    spans.push(NodeSpan {
+        offset: xml.len(),
+        type_name: node.type_name.clone(),
+        pointer: pointer.clone(),
+    });
+
+    xml.push('<');
+    xml.push_str(&node.type_name);
+
+    for (key, value) in &node.ports {
+        if !is_xml_name(key) {
+            return Err(BtError::Raw(format!(
+                "invalid port: type= {} key= {key}",
+                node.type_name
+            )));
+        }
+
+        let value = match value {
+            Value::String(s) => s.clone(),
+            v => v.to_string(),
+        };
+
+        push_attribute(xml, key, &value);
+    }
+
+    if node.children.is_empty() {
+        xml.push_str("/>");
+        return Ok(());
+    }
+
+    xml.push('>');
+    for (i, child) in node.children.iter().enumerate() {
+        write_node(xml, child, format!("{pointer}/children/{i}"), spans)?;
+    }
+    xml.push_str(&format!("</{}>", node.type_name));
+
+    Ok(())
+}
+
+impl JsonDocument {
+    pub fn to_xml(&self) -> Result<String> {
+        self.translate().map(|(xml, _)| xml)
+    }
+
+    // the XML document and where each node was written to
+    fn translate(&self) -> Result<(String, Vec<NodeSpan>)> {
+        let mut xml = String::from("<root BTCPP_format=\"4\"");
+        if let Some(main_tree_id) = &self.main_tree_to_execute {
+            push_attribute(&mut xml, "main_tree_to_execute", main_tree_id);
+        }
+        xml.push('>');
+
+        let mut spans = vec![];
+        for (id, node) in &self.trees {
+            xml.push_str("<BehaviorTree");
+            push_attribute(&mut xml, "ID", id);
+            xml.push('>');
+            write_node(
+                &mut xml,
+                node,
+                format!("/trees/{}", pointer_token(id)),
+                &mut spans,
+            )?;
+            xml.push_str("</BehaviorTree>");
+        }
+        xml.push_str("</root>");
+
+        Ok((xml, spans))
+    }
+}
+
+fn parse_json_tree(s: &str) -> Result<JsonDocument> {
+    let value: Value = serde_json::from_str(s)
+        .map_err(|e| BtError::Raw(format!("invalid json tree: err= {e}")))?;
+
+    // parsed again from the text, a `Value` object doesn't keep the tree order
+    if value.get("trees").is_some() {
+        serde_json::from_str(s)
+    } else {
+        serde_json::from_value(value).map(|node| JsonDocument {
+            main_tree_to_execute: Some(DEFAULT_TREE_ID.to_string()),
+            trees: vec![(DEFAULT_TREE_ID.to_string(), node)],
+        })
+    }
+    .map_err(|e| BtError::Raw(format!("invalid json tree: err= {e}")))
+}
+
+// a build error of the translated XML, at the JSON node the failing element
+// was written from
+fn json_located_error(e: BtError, spans: &[NodeSpan]) -> BtError {
+    let BtError::Parse { offset, path, .. } = &e else {
+        return e;
+    };
+
+    // errors of SubTrees registered with the factory are located in their own document
+    let type_name = path.rsplit('/').next().unwrap_or_default();
+    let Some(span) = spans
+        .iter()
+        .find(|span| span.offset == *offset && span.type_name == type_name)
+    else {
+        return e;
+    };
+    let pointer = span.pointer.clone();
+
+    match e {
+        BtError::Parse { source, .. } => BtError::JsonNode { pointer, source },
+        e => e,
+    }
+}
+
+/// Translate a JSON tree to the equivalent XML document.
+pub fn json_tree_to_xml(s: &str) -> Result<String> {
+    parse_json_tree(s)?.to_xml()
+}
+
+pub fn create_bt_tree_from_json_str(factory: &Factory, s: &str) -> Result<Option<TreeNodeWrapper>> {
+    let (xml, spans) = parse_json_tree(s)?.translate()?;
+
+    create_bt_tree_from_xml_str(factory, &xml).map_err(|e| json_located_error(e, &spans))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{testing::TreeTestExt, NodeStatus, TreeNode};
+
+    use super::*;
+
+    #[test]
+    fn test_json_tree() {
+        let json = r#"{
+            "main_tree_to_execute": "main",
+            "trees": {
+                "main": {
+                    "type": "Sequence",
+                    "children": [
+                        { "type": "SetBlackboard", "ports": { "value": 42, "output_key": "answer" } },
+                        { "type": "SubTree", "ports": { "ID": "dock", "_autoremap": true } }
+                    ]
+                },
+                "dock": {
+                    "type": "SetBlackboard",
+                    "ports": { "value": "<docked & charging>", "output_key": "state" }
+                }
+            }
+        }"#;
+
+        let mut node = create_bt_tree_from_json_str(&Factory::default(), json)
+            .unwrap()
+            .unwrap();
+        assert_eq!(node.tick(), NodeStatus::Success);
+        node.assert_blackboard_eq("answer", serde_json::json!("42"));
+        node.assert_blackboard_eq("state", serde_json::json!("<docked & charging>"));
+
+        let bare =
+            r#"{ "type": "SetBlackboard", "ports": { "value": true, "output_key": "bare" } }"#;
+        let mut node = create_bt_tree_from_json_str(&Factory::default(), bare)
+            .unwrap()
+            .unwrap();
+        assert_eq!(node.tick(), NodeStatus::Success);
+
+        let invalid = r#"{ "type": "Sleep msec=\"1\"" }"#;
+        assert!(create_bt_tree_from_json_str(&Factory::default(), invalid).is_err());
+    }
+
+    #[test]
+    fn test_json_tree_order() {
+        let json = r#"{
+            "trees": {
+                "patrol": { "type": "SubTree", "ports": { "ID": "dock", "_autoremap": true } },
+                "dock": { "type": "SetBlackboard", "ports": { "value": "docked", "output_key": "state" } }
+            }
+        }"#;
+
+        // the first tree runs, not the first ID in sorted order
+        let mut node = create_bt_tree_from_json_str(&Factory::default(), json)
+            .unwrap()
+            .unwrap();
+        assert_eq!(node.tick(), NodeStatus::Success);
+        node.assert_blackboard_eq("state", serde_json::json!("docked"));
+
+        let duplicate = r#"{ "trees": { "a": { "type": "Sleep" }, "a": { "type": "Sleep" } } }"#;
+        let err = json_tree_to_xml(duplicate).err().unwrap();
+        assert!(err.to_string().contains("duplicate tree: a"), "{err}");
+    }
+
+    #[test]
+    fn test_json_tree_error_pointer() {
+        let json = r#"{
+            "trees": {
+                "main": {
+                    "type": "Sequence",
+                    "children": [
+                        { "type": "Sleep", "ports": { "msec": 10 } },
+                        { "type": "SubTree", "ports": { "ID": "dock/base" } }
+                    ]
+                },
+                "dock/base": {
+                    "type": "SetBlackboard",
+                    "ports": { "value": "docked", "output_key": "state", "speed": 1 }
+                }
+            }
+        }"#;
+
+        let Err(BtError::JsonNode { pointer, .. }) =
+            create_bt_tree_from_json_str(&Factory::default(), json)
+        else {
+            panic!("error not located");
+        };
+        assert_eq!(pointer, "/trees/dock~1base");
+
+        let json = json.replace(r#""msec": 10"#, r#""msec": 10, "speed": 1"#);
+        let Err(BtError::JsonNode { pointer, .. }) =
+            create_bt_tree_from_json_str(&Factory::default(), &json)
+        else {
+            panic!("error not located");
+        };
+        assert_eq!(pointer, "/trees/main/children/0");
+    }
+}
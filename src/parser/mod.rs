@@ -1 +1,3 @@
+pub mod json;
+pub mod validation;
 pub mod xml;
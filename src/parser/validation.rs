@@ -0,0 +1,154 @@
+use crate::{node::action::ActionNodeImpl, node::DataProxy, NodeStatus};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationIssue {
+    UnknownNode {
+        path: String,
+        type_name: String,
+    },
+    UnresolvedSubTree {
+        path: String,
+        id: String,
+    },
+    MalformedPort {
+        path: String,
+        key: String,
+        value: String,
+    },
+    UnknownPort {
+        path: String,
+        type_name: String,
+        key: String,
+    },
+    UndefinedVariable {
+        path: String,
+        key: String,
+        name: String,
+    },
+    /// Attribute the document's TreeNodesModel declares no port for
+    ModelUnknownPort {
+        path: String,
+        type_name: String,
+        key: String,
+    },
+    /// Literal value not readable as the port type of the TreeNodesModel
+    ModelTypeMismatch {
+        path: String,
+        key: String,
+        value: String,
+        port_type: String,
+    },
+}
+
+impl std::fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationIssue::UnknownNode { path, type_name } => {
+                write!(f, "unknown node type: path= {path} type= {type_name}")
+            }
+            ValidationIssue::UnresolvedSubTree { path, id } => {
+                write!(f, "unresolved SubTree: path= {path} ID= {id}")
+            }
+            ValidationIssue::MalformedPort { path, key, value } => {
+                write!(f, "malformed port: path= {path} key= {key} value= {value}")
+            }
+            ValidationIssue::UnknownPort {
+                path,
+                type_name,
+                key,
+            } => {
+                write!(f, "unknown port: path= {path} type= {type_name} key= {key}")
+            }
+            ValidationIssue::UndefinedVariable { path, key, name } => {
+                write!(
+                    f,
+                    "undefined variable: path= {path} key= {key} name= {name}"
+                )
+            }
+            ValidationIssue::ModelUnknownPort {
+                path,
+                type_name,
+                key,
+            } => {
+                write!(
+                    f,
+                    "port not in TreeNodesModel: path= {path} type= {type_name} key= {key}"
+                )
+            }
+            ValidationIssue::ModelTypeMismatch {
+                path,
+                key,
+                value,
+                port_type,
+            } => {
+                write!(
+                    f,
+                    "port type mismatch with TreeNodesModel: path= {path} key= {key} value= {value} type= {port_type}"
+                )
+            }
+        }
+    }
+}
+
+/// Element of a BehaviorTree no node type is registered for, see
+/// [`crate::BtError::UnknownNodes`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownNode {
+    pub tree_id: String,
+    /// Element names from the tree's root element down to this one
+    pub path: String,
+    pub type_name: String,
+    /// 1-based line in `file`, or in the XML document when it's `None`
+    pub line: usize,
+    /// File the element was read from, see [`crate::BtError::Parse`]
+    pub file: Option<std::path::PathBuf>,
+}
+
+impl std::fmt::Display for UnknownNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "type= {} tree= {} path= {} line= {}",
+            self.type_name, self.tree_id, self.path, self.line
+        )?;
+
+        match &self.file {
+            Some(file) => write!(f, " file= {}", file.display()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Result of a dry run, see [`super::xml::validate_bt_tree_from_xml_str`].
+#[derive(Debug, Default, Clone)]
+pub struct ValidationReport {
+    pub node_count: usize,
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Port values are either literals or a single `{key}` reference.
+pub fn is_malformed_port_value(value: &str) -> bool {
+    let opened = value.starts_with('{');
+    let closed = value.ends_with('}');
+
+    if opened != closed {
+        return true;
+    }
+
+    opened && (value.len() < 3 || value[1..value.len() - 1].contains(['{', '}']))
+}
+
+/// Leaf used in place of real actions during a dry run.
+pub struct DryRunStub;
+
+impl ActionNodeImpl for DryRunStub {
+    fn tick_status(&mut self, _data_proxy: &mut DataProxy) -> NodeStatus {
+        NodeStatus::Success
+    }
+}
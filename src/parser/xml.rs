@@ -1,6 +1,9 @@
 use std::{
-    collections::{HashMap, VecDeque},
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    io::Read,
     ops::Range,
+    path::{Path, PathBuf},
     sync::{
         atomic::{AtomicU16, Ordering},
         Arc,
@@ -8,14 +11,25 @@ use std::{
 };
 
 use crate::{
-    factory::Factory,
-    node::{strip_ref_tag, Blackboard, DataProxy},
+    expr::{self, Expr},
+    factory::{Factory, NAMESPACE_SEPARATOR},
+    node::{
+        action::ActionWrapper,
+        composite::CompositeWrapper,
+        decorator::{DecoratorWrapper, Retry, NUM_ATTEMPTS, RETRY_DELAY_MSEC},
+        is_ref_key, strip_ref_tag, Blackboard, DataProxy, NodeConditions, SAME_NAME_REMAP,
+    },
     BtError, NodeWrapper, Result, TreeNodeWrapper,
 };
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use quick_xml::{
-    events::{attributes::Attributes, Event},
-    Reader,
+    events::{attributes::Attributes, BytesEnd, BytesStart, Event},
+    Reader, Writer,
+};
+use serde_json::Value;
+
+use super::validation::{
+    is_malformed_port_value, DryRunStub, UnknownNode, ValidationIssue, ValidationReport,
 };
 
 struct AttributesWrapper<'a> {
@@ -34,7 +48,7 @@ impl<'a> AttributesWrapper<'a> {
             let att = att?;
 
             if att.key.as_ref() == key.as_bytes() {
-                let s = std::str::from_utf8(att.value.as_ref())?.to_string();
+                let s = att.unescape_value()?.into_owned();
                 return Ok(Some(s));
             }
         }
@@ -49,7 +63,11 @@ impl<'a> AttributesWrapper<'a> {
             let att = att?;
 
             let key = std::str::from_utf8(att.key.as_ref())?.to_string();
-            let value = std::str::from_utf8(att.value.as_ref())?.to_string();
+            // namespace declarations aren't ports
+            if key == "xmlns" || key.starts_with("xmlns:") {
+                continue;
+            }
+            let value = att.unescape_value()?.into_owned();
 
             map.insert(key, value);
         }
@@ -58,365 +76,5341 @@ impl<'a> AttributesWrapper<'a> {
     }
 }
 
-// only the action nodes leaf nodes
-fn create_tree_node_recursively(
-    factory: &Factory,
-    mut path_folders: Vec<String>,
-    original_tree_str: &str,
-    check_str: &str,
-    tree_ranges: &HashMap<String, Range<usize>>,
-    bb: Arc<RwLock<Blackboard>>,
-    uid_generator: &AtomicU16,
-) -> Result<Option<TreeNodeWrapper>> {
-    tracing::trace!("input: {}", check_str);
+/// SubTree attribute isolating the subtree blackboard from its parent
+pub const SUBTREE_PRIVATE: &str = "_private";
+/// SubTree attribute remapping every key to the same-named parent entry
+pub const SUBTREE_AUTOREMAP: &str = "_autoremap";
+/// Comma separated list of tags, available on every node.
+pub const NODE_TAGS: &str = "_tags";
+/// Retry a failing node up to this many times, available on every node.
+pub const NODE_RETRIES: &str = "_retries";
+/// Wait between retries of [`NODE_RETRIES`].
+pub const NODE_BACKOFF_MSEC: &str = "_backoff_msec";
+/// Inline guard expressions, available on every node, see [`NodeConditions`].
+pub const NODE_FAILURE_IF: &str = "_failureIf";
+pub const NODE_SUCCESS_IF: &str = "_successIf";
+pub const NODE_SKIP_IF: &str = "_skipIf";
+pub const NODE_WHILE: &str = "_while";
 
-    tracing::trace!("input blackboard: {:?}", bb.read());
-    let mut reader = Reader::from_str(check_str);
+// `_` attributes the builder reads, any other becomes node metadata
+const BUILTIN_ATTRIBUTES: [&str; 5] = [
+    SUBTREE_PRIVATE,
+    SUBTREE_AUTOREMAP,
+    NODE_TAGS,
+    NODE_RETRIES,
+    NODE_BACKOFF_MSEC,
+];
 
-    let mut control_nodes = VecDeque::new();
+// node type of an element, the XML prefix of `<nav:MoveTo/>` is read as the
+// namespace of `nav.MoveTo`
+fn element_type_name(name: &[u8]) -> Result<Cow<'_, str>> {
+    let name = std::str::from_utf8(name)?;
 
-    loop {
-        let event = reader.read_event();
-        tracing::trace!("event: {event:?}");
-
-        match event {
-            Ok(Event::Start(e)) | Ok(Event::Empty(e)) => {
-                let name = e.name();
-                let element_name = std::str::from_utf8(name.as_ref())?;
-
-                let wrapper = AttributesWrapper::new(e.attributes());
-
-                if factory.composite_types().contains(element_name) {
-                    tracing::trace!("composite node");
-
-                    path_folders.push(element_name.to_string());
-
-                    let mut data_proxy = DataProxy::new(bb.clone());
-                    data_proxy.set_full_path(path_folders.join("/"));
-
-                    let Some(mut node) =
-                        factory.build_composite(element_name, data_proxy, wrapper.kv()?)
-                    else {
-                        tracing::warn!("can't create node: element_name= {element_name}");
-                        continue;
-                    };
-
-                    let uid = uid_generator.fetch_add(1, Ordering::SeqCst);
-                    node.data_proxy.set_uid(uid);
-
-                    control_nodes.push_front(node);
-                } else if factory.decorator_types().contains(element_name) {
-                    tracing::trace!("decorator node");
-
-                    let wrapper = AttributesWrapper::new(e.attributes());
-                    let kv = wrapper.kv()?;
-
-                    let (subtree_check_str, new_bb) = if element_name == "SubTree" {
-                        let tree_id = kv
-                            .get("ID")
-                            .ok_or_else(|| BtError::Raw("no ID found for SubTree".to_string()))?;
-
-                        let remappings: HashMap<_, _> = kv
-                            .clone()
-                            .into_iter()
-                            .filter_map(|(k, v)| {
-                                if k == "ID" {
-                                    None
-                                } else {
-                                    Some((k, strip_ref_tag(&v)))
-                                }
-                            })
-                            .collect();
-
-                        tracing::trace!("SubTree ID: {tree_id} remappings= {remappings:?} tree_ranges= {tree_ranges:?}");
-                        let mut subtree_bb = Blackboard::new_with_parent(&bb);
-                        subtree_bb.extend_parent_remappings(remappings);
-
-                        let range = tree_ranges.get(tree_id).cloned().ok_or_else(|| {
-                            BtError::Raw(format!("can't find range for tree: {tree_id}"))
-                        })?;
-                        (&original_tree_str[range], Arc::new(RwLock::new(subtree_bb)))
-                    } else {
-                        let range = reader.read_to_end(e.to_end().name())?;
-
-                        (&check_str[range], bb.clone())
-                    };
-
-                    let uid = uid_generator.fetch_add(1, Ordering::SeqCst);
-
-                    let mut subtree_path_folders = path_folders.clone();
-                    subtree_path_folders.push(element_name.to_string());
-
-                    let node = create_tree_node_recursively(
-                        factory,
-                        subtree_path_folders.clone(),
-                        original_tree_str,
-                        subtree_check_str,
-                        tree_ranges,
-                        new_bb,
-                        uid_generator,
-                    )?
-                    .ok_or_else(|| BtError::Raw("no subtree node created".to_string()))?;
-                    tracing::debug!("get node: {}", node.node_info());
-
-                    let mut data_proxy = DataProxy::new(bb.clone());
-                    data_proxy.set_full_path(subtree_path_folders.join("/"));
-
-                    let Some(mut decorator_node) =
-                        factory.build_decorator(element_name, data_proxy, kv, node)
-                    else {
-                        tracing::warn!("can't create decorator node: element_name= {element_name}");
-
-                        continue;
-                    };
-                    decorator_node.data_proxy.set_uid(uid);
-
-                    let node = TreeNodeWrapper::new(NodeWrapper::Decorator(decorator_node));
-                    if let Some(control_node) = control_nodes.front_mut() {
-                        control_node.add_child(node);
-                    } else {
-                        tracing::info!("return node: {}", node.node_info());
-                        return Ok(Some(node));
-                    }
-                } else {
-                    tracing::trace!("leaf node: {element_name}");
+    Ok(match name.split_once(':') {
+        Some((prefix, local)) => Cow::Owned(format!("{prefix}{NAMESPACE_SEPARATOR}{local}")),
+        None => Cow::Borrowed(name),
+    })
+}
 
-                    let mut data_proxy = DataProxy::new(bb.clone());
+// strips metadata attributes like `_description` from `kv`
+fn take_metadata(kv: &mut HashMap<String, String>) -> BTreeMap<String, String> {
+    let keys: Vec<_> = kv
+        .keys()
+        .filter(|key| key.starts_with('_') && !BUILTIN_ATTRIBUTES.contains(&key.as_str()))
+        .cloned()
+        .collect();
 
-                    let mut path_folers_leaf = path_folders.clone();
-                    path_folers_leaf.push(element_name.to_string());
+    keys.into_iter()
+        .filter_map(|key| kv.remove_entry(&key))
+        .collect()
+}
+/// Label of a node instance, shown in its path segment as `Type(name)`.
+/// Still passed as port to node types declaring a `name` port.
+pub const NODE_NAME: &str = "name";
 
-                    data_proxy.set_full_path(path_folers_leaf.join("/"));
+fn parse_tags(kv: &HashMap<String, String>) -> Vec<String> {
+    kv.get(NODE_TAGS)
+        .map(|v| {
+            v.split(',')
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
 
-                    let Some(mut node) =
-                        factory.build_action(element_name, data_proxy, wrapper.kv()?)
-                    else {
-                        tracing::warn!("can't create node: element_name= {element_name}");
+/// Replace every `${name}` in `value` with the config var or environment
+/// variable of that name, `$$` escapes a literal `$`.
+fn interpolate(
+    value: &str,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> std::result::Result<String, String> {
+    let mut res = String::with_capacity(value.len());
+    let mut rest = value;
 
-                        continue;
-                    };
+    while let Some(idx) = rest.find('$') {
+        res.push_str(&rest[..idx]);
+        rest = &rest[idx + 1..];
 
-                    let uid = uid_generator.fetch_add(1, Ordering::SeqCst);
-                    node.set_uid(uid);
+        if let Some(stripped) = rest.strip_prefix('$') {
+            res.push('$');
+            rest = stripped;
+        } else if let Some(stripped) = rest.strip_prefix('{') {
+            let Some(end) = stripped.find('}') else {
+                return Err(stripped.to_string());
+            };
 
-                    if let Some(control_node) = control_nodes.front_mut() {
-                        control_node.add_child(node);
-                    } else {
-                        tracing::debug!("return node: {}", node.node_info());
+            let name = &stripped[..end];
+            res.push_str(&lookup(name).ok_or_else(|| name.to_string())?);
+            rest = &stripped[end + 1..];
+        } else {
+            res.push('$');
+        }
+    }
+    res.push_str(rest);
 
-                        return Ok(Some(node));
-                    }
+    Ok(res)
+}
+
+struct BuildContext<'a> {
+    factory: &'a Factory,
+    uid_generator: AtomicU16,
+    // only set for dry runs, leaves are stubbed and issues collected instead of failing
+    report: Option<Mutex<ValidationReport>>,
+    // the document's TreeNodesModel, if any
+    model: Option<&'a TreeNodesModel>,
+    options: ParseOptions,
+}
+
+impl<'a> BuildContext<'a> {
+    fn new(factory: &'a Factory, doc: &'a TreeDocument) -> Self {
+        Self {
+            factory,
+            uid_generator: AtomicU16::new(0),
+            report: None,
+            model: doc.model.as_ref(),
+            options: ParseOptions::default(),
+        }
+    }
+
+    // errors of an element get its position in the document, unless they
+    // already carry one
+    fn locate(
+        &self,
+        e: BtError,
+        doc: &TreeDocument,
+        element: &Element,
+        path_folders: &[String],
+    ) -> BtError {
+        if matches!(e, BtError::Parse { .. } | BtError::LimitExceeded { .. }) {
+            return e;
+        }
+
+        let mut path = String::new();
+        for folder in path_folders {
+            path.push_str(folder);
+            path.push('/');
+        }
+        path.push_str(&element.name);
+
+        located_error(&doc.document, &doc.sources, element.offset, path, e)
+    }
+
+    fn next_uid(&self, path: &str) -> Result<u16> {
+        let max_nodes = self.factory.build_limits().max_nodes;
+
+        let uid = self.uid_generator.fetch_add(1, Ordering::SeqCst);
+        if uid as usize >= max_nodes {
+            return Err(BtError::LimitExceeded {
+                limit: "node count",
+                max: max_nodes,
+                path: path.to_string(),
+            });
+        }
+
+        Ok(uid)
+    }
+
+    // attributes with `${name}` resolved against config vars and the environment
+    fn kv(&self, element: &Element, path: &str) -> Result<HashMap<String, String>> {
+        let mut kv = element.attrs.clone();
+        self.take_text(element, path, &mut kv)?;
+
+        for (key, value) in kv.iter_mut() {
+            let res = interpolate(value, |name| {
+                self.factory
+                    .config_vars()
+                    .get(name)
+                    .cloned()
+                    .or_else(|| std::env::var(name).ok())
+            });
+
+            match res {
+                Ok(v) => *value = v,
+                Err(name) if self.is_dry_run() => {
+                    self.record_issue(ValidationIssue::UndefinedVariable {
+                        path: path.to_string(),
+                        key: key.clone(),
+                        name,
+                    });
+                }
+                Err(name) => {
+                    return Err(BtError::Raw(format!(
+                        "undefined variable: path= {path} key= {key} name= {name}"
+                    )));
                 }
             }
-            Ok(Event::End(e)) => {
-                let name = e.name();
-                let element_name = std::str::from_utf8(name.as_ref())?;
+        }
 
-                if factory.composite_types().contains(element_name) {
-                    path_folders.pop();
+        Ok(kv)
+    }
 
-                    if let Some(control_node) = control_nodes.pop_front() {
-                        let control_node_wrapper =
-                            TreeNodeWrapper::new(NodeWrapper::Composite(control_node));
+    // the element body as value of the type's text port
+    fn take_text(
+        &self,
+        element: &Element,
+        path: &str,
+        kv: &mut HashMap<String, String>,
+    ) -> Result<()> {
+        let text = element.text.trim();
+        if text.is_empty() {
+            return Ok(());
+        }
 
-                        if let Some(parent_control_node) = control_nodes.front_mut() {
-                            parent_control_node.add_child(control_node_wrapper);
-                        } else {
-                            return Ok(Some(control_node_wrapper));
-                        }
-                    } else {
-                        return Err(BtError::Raw(format!("unexpected end: {element_name}")));
-                    }
+        let Some(port) = self.factory.text_port(&element.name) else {
+            tracing::warn!("element text ignored: path= {path}");
+            return Ok(());
+        };
+
+        if kv.contains_key(port) {
+            return Err(BtError::Raw(format!(
+                "port set by both attribute and text: path= {path} key= {port}"
+            )));
+        }
+        kv.insert(port.to_string(), text.to_string());
+
+        Ok(())
+    }
+
+    // nodes with `_retries` get wrapped in a Retry decorator sharing their path
+    fn wrap_retries(&self, node: TreeNodeWrapper) -> Result<TreeNodeWrapper> {
+        let inner_proxy = node.data_proxy_ref();
+        let Some(retries) = inner_proxy.input_port(NODE_RETRIES) else {
+            return Ok(node);
+        };
+
+        let full_path = inner_proxy.full_path().to_string();
+        let mut data_proxy = DataProxy::new(inner_proxy.shared_blackboard().clone());
+        data_proxy.set_full_path(full_path.clone());
+        data_proxy.add_input(NUM_ATTEMPTS.to_string(), retries.to_string());
+        if let Some(backoff_msec) = inner_proxy.input_port(NODE_BACKOFF_MSEC) {
+            data_proxy.add_input(RETRY_DELAY_MSEC.to_string(), backoff_msec.to_string());
+        }
+        data_proxy.set_uid(self.next_uid(&full_path)?);
+
+        Ok(TreeNodeWrapper::new(NodeWrapper::Decorator(
+            DecoratorWrapper::new(data_proxy, Box::new(Retry::default()), node),
+        )))
+    }
+
+    // data proxy and remaining attributes of the element last in `path_folders`,
+    // kept out of `build_element_node` so its frame stays small
+    fn element_data_proxy(
+        &self,
+        bb: &Arc<RwLock<Blackboard>>,
+        element_name: &str,
+        path_folders: &mut [String],
+        element: &Element,
+    ) -> Result<(DataProxy, HashMap<String, String>)> {
+        let mut kv = self.kv(element, &path_folders.join("/"))?;
+        let name = self.take_name(element_name, path_folders, &mut kv)?;
+
+        let mut data_proxy = DataProxy::new(bb.clone());
+        data_proxy.set_full_path(path_folders.join("/"));
+        data_proxy.set_name(name);
+        data_proxy.set_conditions(self.take_conditions(data_proxy.full_path(), &mut kv)?);
+        data_proxy.set_metadata(take_metadata(&mut kv));
+        self.check_ports(data_proxy.full_path(), element_name, &kv)?;
+        data_proxy.set_tags(parse_tags(&kv));
+
+        Ok((data_proxy, kv))
+    }
+
+    // like `element_data_proxy`, node temporaries stay out of the recursive frame
+    fn build_leaf(
+        &self,
+        bb: &Arc<RwLock<Blackboard>>,
+        element_name: &str,
+        path_folders: &[String],
+        element: &Element,
+    ) -> Result<Option<TreeNodeWrapper>> {
+        let mut path_folders = path_folders.to_vec();
+        path_folders.push(element_name.to_string());
+        self.check_depth(&path_folders)?;
+
+        let (data_proxy, kv) =
+            self.element_data_proxy(bb, element_name, &mut path_folders, element)?;
+
+        let Some(mut node) = self.build_action(element_name, data_proxy, kv) else {
+            tracing::warn!("can't create node: element_name= {element_name}");
+
+            return Ok(None);
+        };
+
+        let uid = self.next_uid(node.data_proxy_ref().full_path())?;
+        node.set_uid(uid);
+
+        self.wrap_retries(node).map(Some)
+    }
+
+    fn build_composite_node(
+        &self,
+        bb: &Arc<RwLock<Blackboard>>,
+        element_name: &str,
+        path_folders: &mut [String],
+        element: &Element,
+    ) -> Result<Option<CompositeWrapper>> {
+        let (data_proxy, kv) = self.element_data_proxy(bb, element_name, path_folders, element)?;
+
+        let Some(mut node) = self.factory.build_composite(element_name, data_proxy, kv) else {
+            tracing::warn!("can't create node: element_name= {element_name}");
+
+            return Ok(None);
+        };
+
+        let uid = self.next_uid(node.data_proxy.full_path())?;
+        node.data_proxy.set_uid(uid);
+
+        Ok(Some(node))
+    }
+
+    fn build_decorator_node(
+        &self,
+        element_name: &str,
+        data_proxy: DataProxy,
+        kv: HashMap<String, String>,
+        inner_node: TreeNodeWrapper,
+        uid: u16,
+    ) -> Result<Option<TreeNodeWrapper>> {
+        let Some(mut decorator_node) =
+            self.factory
+                .build_decorator(element_name, data_proxy, kv, inner_node)
+        else {
+            tracing::warn!("can't create decorator node: element_name= {element_name}");
+
+            return Ok(None);
+        };
+        decorator_node.data_proxy.set_uid(uid);
+
+        let node = TreeNodeWrapper::new(NodeWrapper::Decorator(decorator_node));
+
+        self.wrap_retries(node).map(Some)
+    }
+
+    // strips the instance name from `kv` and appends it to the last segment
+    fn take_name(
+        &self,
+        type_name: &str,
+        path_folders: &mut [String],
+        kv: &mut HashMap<String, String>,
+    ) -> Result<Option<String>> {
+        let is_port = type_name != "SubTree"
+            && self
+                .factory
+                .provided_ports(type_name)
+                .is_some_and(|ports| ports.iter().any(|port| port.name == NODE_NAME));
+        let name = if is_port {
+            kv.get(NODE_NAME).cloned()
+        } else {
+            kv.remove(NODE_NAME)
+        };
+
+        let Some(name) = name else {
+            return Ok(None);
+        };
+
+        if name.is_empty() || name.contains(['/', '(', ')']) {
+            return Err(BtError::Raw(format!("invalid node name: {name}")));
+        }
+
+        if let Some(segment) = path_folders.last_mut() {
+            segment.push_str(&format!("({name})"));
+        }
+
+        Ok(Some(name))
+    }
+
+    // strips the guard attributes from `kv`
+    fn take_conditions(
+        &self,
+        path: &str,
+        kv: &mut HashMap<String, String>,
+    ) -> Result<NodeConditions> {
+        let mut take = |key: &str| -> Result<Option<Expr>> {
+            let Some(value) = kv.remove(key) else {
+                return Ok(None);
+            };
+
+            match expr::parse(&value) {
+                Ok(expr) => Ok(Some(expr)),
+                Err(_) if self.is_dry_run() => {
+                    self.record_issue(ValidationIssue::MalformedPort {
+                        path: path.to_string(),
+                        key: key.to_string(),
+                        value,
+                    });
+
+                    Ok(None)
                 }
+                Err(e) => Err(BtError::Raw(format!(
+                    "invalid condition: path= {path} key= {key} err= {e}"
+                ))),
             }
-            Ok(Event::Eof) => break,
-            _ => {}
+        };
+
+        Ok(NodeConditions {
+            failure_if: take(NODE_FAILURE_IF)?,
+            success_if: take(NODE_SUCCESS_IF)?,
+            skip_if: take(NODE_SKIP_IF)?,
+            while_cond: take(NODE_WHILE)?,
+        })
+    }
+
+    fn check_children(&self, node: &CompositeWrapper) -> Result<()> {
+        if node.child_nodes.is_empty() && !self.options.allow_empty_composites {
+            return Err(BtError::Raw(format!(
+                "empty composite: path= {}",
+                node.data_proxy.full_path()
+            )));
         }
+
+        Ok(())
     }
 
-    Ok(None)
-}
+    fn check_depth(&self, path_folders: &[String]) -> Result<()> {
+        let max_depth = self.factory.build_limits().max_depth;
 
-pub fn create_bt_tree_from_xml_str(factory: &Factory, s: &str) -> Result<Option<TreeNodeWrapper>> {
-    let mut reader = Reader::from_str(s);
-    reader.trim_text(true);
+        if path_folders.len() > max_depth {
+            return Err(BtError::LimitExceeded {
+                limit: "depth",
+                max: max_depth,
+                path: path_folders.join("/"),
+            });
+        }
+
+        Ok(())
+    }
+
+    fn is_dry_run(&self) -> bool {
+        self.report.is_some()
+    }
 
-    let (main_tree_id, root_range) = loop {
-        match reader.read_event() {
-            Ok(Event::Start(e)) if e.name().as_ref() == b"root" => {
-                let wrapper = AttributesWrapper::new(e.attributes());
-                let main_tree_id = wrapper.get_key("main_tree_to_execute")?;
+    fn record_issue(&self, issue: ValidationIssue) {
+        if let Some(report) = &self.report {
+            report.lock().issues.push(issue);
+        }
+    }
+
+    // attributes must be declared ports of the type, `_` prefixed ones are
+    // handled by the parser
+    fn check_ports(&self, path: &str, type_name: &str, kv: &HashMap<String, String>) -> Result<()> {
+        if let Some(ports) = self.factory.provided_ports(type_name) {
+            let mut unknown: Vec<_> = kv
+                .keys()
+                .filter(|key| !key.starts_with('_'))
+                .filter(|key| ports.iter().all(|port| &port.name != *key))
+                .collect();
+            unknown.sort();
+
+            for key in unknown {
+                if !self.is_dry_run() {
+                    if self.options.strict_unknown_ports {
+                        return Err(BtError::Raw(format!(
+                            "unknown port: path= {path} type= {type_name} key= {key}"
+                        )));
+                    }
 
-                let end = e.to_end().to_owned();
+                    tracing::warn!("unknown port: path= {path} type= {type_name} key= {key}");
+                    continue;
+                }
 
-                let trees_range = reader.read_to_end(end.name())?;
-                break (main_tree_id, trees_range);
+                self.record_issue(ValidationIssue::UnknownPort {
+                    path: path.to_string(),
+                    type_name: type_name.to_string(),
+                    key: key.clone(),
+                });
             }
-            Ok(Event::Eof) => {
-                return Err(crate::BtError::Raw("no root range found".to_string()));
+        }
+
+        self.check_model(path, type_name, kv);
+
+        if !self.is_dry_run() {
+            return Ok(());
+        }
+
+        for (key, value) in kv {
+            if is_malformed_port_value(value) {
+                self.record_issue(ValidationIssue::MalformedPort {
+                    path: path.to_string(),
+                    key: key.clone(),
+                    value: value.clone(),
+                });
             }
-            _ => {}
         }
-    };
 
-    let s = &s[root_range];
-    let mut reader = Reader::from_str(s);
-    let mut tree_ranges = HashMap::new();
+        Ok(())
+    }
 
-    loop {
-        match reader.read_event() {
-            Ok(Event::Start(e)) if e.name().as_ref() == b"BehaviorTree" => {
-                let wrapper = AttributesWrapper::new(e.attributes());
+    // drift between the embedded model and the trees is reported, not fatal
+    fn check_model(&self, path: &str, type_name: &str, kv: &HashMap<String, String>) {
+        let id = if type_name == "SubTree" {
+            kv.get("ID").map(String::as_str).unwrap_or(type_name)
+        } else {
+            type_name
+        };
+        let Some(ports) = self.model.and_then(|model| model.get(id)) else {
+            return;
+        };
 
-                let Some(id) = wrapper.get_key("ID")? else {
-                    return Err(crate::BtError::Raw(
-                        "no ID found in BehaviorTree element".to_string(),
-                    ));
-                };
+        let mut keys: Vec<_> = kv
+            .keys()
+            .filter(|key| !key.starts_with('_'))
+            .filter(|key| type_name != "SubTree" || *key != "ID")
+            .collect();
+        keys.sort();
 
-                let tree_range = reader.read_to_end(e.to_end().to_owned().name())?;
+        for key in keys {
+            let value = &kv[key];
+            let issue = match ports.iter().find(|port| &port.name == key) {
+                None => ValidationIssue::ModelUnknownPort {
+                    path: path.to_string(),
+                    type_name: id.to_string(),
+                    key: key.clone(),
+                },
+                Some(ModelPort {
+                    type_name: Some(port_type),
+                    ..
+                }) if !value.starts_with('{') && !matches_model_type(value, port_type) => {
+                    ValidationIssue::ModelTypeMismatch {
+                        path: path.to_string(),
+                        key: key.clone(),
+                        value: value.clone(),
+                        port_type: port_type.clone(),
+                    }
+                }
+                Some(_) => continue,
+            };
 
-                tree_ranges.insert(id, tree_range);
+            if self.is_dry_run() {
+                self.record_issue(issue);
+            } else {
+                tracing::warn!("TreeNodesModel mismatch: {issue}");
             }
-            Ok(Event::Eof) => break,
-            _ => {}
         }
     }
 
-    let main_tree = if let Some(main_tree_id) = main_tree_id {
-        tree_ranges.remove(&main_tree_id)
-    } else {
-        tree_ranges.drain().next().map(|a| a.1)
-    };
+    fn build_action(
+        &self,
+        type_name: &str,
+        data_proxy: DataProxy,
+        attrs: HashMap<String, String>,
+    ) -> Option<TreeNodeWrapper> {
+        if !self.is_dry_run() {
+            if self.factory.is_condition_registered(type_name) {
+                return self.factory.build_condition(type_name, data_proxy, attrs);
+            }
 
-    let Some(main_tree_range) = main_tree else {
-        return Err(BtError::Raw("no main bt tree found".to_string()));
-    };
+            return self.factory.build_action(type_name, data_proxy, attrs);
+        }
 
-    let bb = Blackboard::default();
+        if !self.factory.is_action_registered(type_name)
+            && !self.factory.is_condition_registered(type_name)
+        {
+            self.record_issue(ValidationIssue::UnknownNode {
+                path: data_proxy.full_path().to_string(),
+                type_name: type_name.to_string(),
+            });
+        }
 
-    // tracing::info!("initial input: {}", &s[106..150]);
+        let mut data_proxy = data_proxy;
+        for (key, value) in attrs {
+            data_proxy.add_input(key, value);
+        }
 
-    // let main_tree_range = 26..237;
+        Some(TreeNodeWrapper::new(NodeWrapper::Action(
+            ActionWrapper::new(data_proxy, Box::new(DryRunStub)),
+        )))
+    }
+}
 
-    let node = create_tree_node_recursively(
-        factory,
-        vec![],
-        s,
-        &s[main_tree_range],
-        &tree_ranges,
-        Arc::new(RwLock::new(bb)),
-        &AtomicU16::new(0),
-    )?;
+// branches of copy-on-write composites each get their own child scope
+fn element_blackboard(
+    parent: Option<&CompositeWrapper>,
+    bb: &Arc<RwLock<Blackboard>>,
+) -> Arc<RwLock<Blackboard>> {
+    match parent {
+        Some(parent) if parent.copy_on_write_children() => {
+            let parent_bb = parent.data_proxy.shared_blackboard();
 
-    Ok(node)
+            Arc::new(RwLock::new(Blackboard::new_with_parent(parent_bb)))
+        }
+        Some(parent) => parent.data_proxy.shared_blackboard().clone(),
+        None => bb.clone(),
+    }
 }
 
-#[cfg(test)]
-mod test {
-    use std::path::PathBuf;
-    use std::time::Duration;
+// kept out of `build_element_node`, a `Blackboard` in its frame adds up
+// with deeply nested SubTrees
+fn subtree_blackboard(
+    parent_bb: &Arc<RwLock<Blackboard>>,
+    remappings: HashMap<String, String>,
+    kv: &HashMap<String, String>,
+) -> Arc<RwLock<Blackboard>> {
+    let mut subtree_bb = Blackboard::new_with_parent(parent_bb);
 
-    use tracing::level_filters::LevelFilter;
-    use tracing_subscriber::prelude::*;
-    use tracing_subscriber::EnvFilter;
+    // set before `_autoremap` applies, literals stay in the subtree scope
+    for (key, value) in kv {
+        if key != "ID" && !key.starts_with('_') && !is_ref_key(value) {
+            subtree_bb.set(key.clone(), Value::String(value.clone()));
+        }
+    }
 
-    use crate::node::StateNotif;
-    use crate::TreeNode;
-    use crate::{factory::boxify_action, node::action::ActionNodeImpl, NodeStatus};
+    subtree_bb.extend_parent_remappings(remappings);
+    subtree_bb.set_private(kv.get(SUBTREE_PRIVATE).is_some_and(|v| v == "true"));
+    subtree_bb.set_autoremap(kv.get(SUBTREE_AUTOREMAP).is_some_and(|v| v == "true"));
 
-    use super::*;
+    Arc::new(RwLock::new(subtree_bb))
+}
 
-    fn assets_dir() -> PathBuf {
-        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-        d.push("assets");
+// where a range of an expanded document was copied from, it runs up to the
+// start of the next one
+#[derive(Debug, Clone)]
+struct SourceSpan {
+    start: usize,
+    // `None` for a document given as a string
+    file: Option<Arc<Path>>,
+    // the text the range was copied from and where in it the range starts
+    text: Arc<str>,
+    source_start: usize,
+}
 
-        d
+// file, 1-based line and column of `offset` in the expanded `document`
+fn source_location(
+    document: &str,
+    sources: &[SourceSpan],
+    offset: usize,
+) -> (Option<PathBuf>, usize, usize) {
+    let (file, text, offset) = match sources.iter().rev().find(|span| span.start <= offset) {
+        Some(span) => (
+            span.file.as_deref().map(Path::to_path_buf),
+            &*span.text,
+            span.source_start + offset - span.start,
+        ),
+        None => (None, document, offset),
+    };
+
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+
+    (
+        file,
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+fn located_error(
+    document: &str,
+    sources: &[SourceSpan],
+    offset: usize,
+    path: String,
+    source: BtError,
+) -> BtError {
+    let (file, line, column) = source_location(document, sources, offset);
+
+    BtError::Parse {
+        offset,
+        line,
+        column,
+        file,
+        path,
+        source: Box::new(source),
     }
+}
 
-    struct PrintBody;
+// builds `element` of `doc` and its descendants, `path_folders` is the path of
+// its parent
+fn build_element(
+    ctx: &BuildContext,
+    doc: &TreeDocument,
+    path_folders: &[String],
+    element: &Element,
+    bb: Arc<RwLock<Blackboard>>,
+) -> Result<Option<TreeNodeWrapper>> {
+    build_element_node(ctx, doc, path_folders, element, bb)
+        .map_err(|e| ctx.locate(e, doc, element, path_folders))
+}
 
-    impl ActionNodeImpl for PrintBody {
-        fn tick_status(&mut self, _data_proxy: &mut DataProxy) -> NodeStatus {
-            println!("body tick");
-            NodeStatus::Success
-        }
+fn build_element_node(
+    ctx: &BuildContext,
+    doc: &TreeDocument,
+    path_folders: &[String],
+    element: &Element,
+    bb: Arc<RwLock<Blackboard>>,
+) -> Result<Option<TreeNodeWrapper>> {
+    let factory = ctx.factory;
+    let element_name = element.name.as_str();
 
-        fn halt(&mut self) {
-            tracing::info!("halt print body action ========");
+    if factory.composite_types().contains(element_name) {
+        tracing::trace!("composite node");
+
+        let mut path_folders = path_folders.to_vec();
+        path_folders.push(element_name.to_string());
+        ctx.check_depth(&path_folders)?;
+
+        let Some(mut node) =
+            ctx.build_composite_node(&bb, element_name, &mut path_folders, element)?
+        else {
+            return Ok(None);
+        };
+
+        for child in &element.children {
+            let child_bb = element_blackboard(Some(&node), &bb);
+            if let Some(child) = build_element(ctx, doc, &path_folders, child, child_bb)? {
+                node.add_child(child);
+            }
         }
-    }
+        ctx.check_children(&node)?;
 
-    struct PrintArm;
+        ctx.wrap_retries(TreeNodeWrapper::new(NodeWrapper::Composite(node)))
+            .map(Some)
+    } else if factory.decorator_types().contains(element_name) {
+        tracing::trace!("decorator node");
 
-    impl ActionNodeImpl for PrintArm {
-        fn tick_status(&mut self, _data_proxy: &mut DataProxy) -> NodeStatus {
-            println!("arm tick");
-            NodeStatus::Success
+        let mut subtree_path_folders = path_folders.to_vec();
+        subtree_path_folders.push(element_name.to_string());
+        ctx.check_depth(&subtree_path_folders)?;
+
+        let (data_proxy, kv) =
+            ctx.element_data_proxy(&bb, element_name, &mut subtree_path_folders, element)?;
+        let subtree_path = data_proxy.full_path();
+
+        let (child_doc, child, child_bb) = if element_name == "SubTree" {
+            let tree_id = kv
+                .get("ID")
+                .ok_or_else(|| BtError::Raw("no ID found for SubTree".to_string()))?;
+
+            // unbraced values are constants of the subtree
+            let remappings: HashMap<_, _> = kv
+                .clone()
+                .into_iter()
+                .filter_map(|(k, v)| {
+                    if k == "ID" || k.starts_with('_') || !is_ref_key(&v) {
+                        None
+                    } else if v == SAME_NAME_REMAP {
+                        Some((k.clone(), k))
+                    } else {
+                        Some((k, strip_ref_tag(&v)))
+                    }
+                })
+                .collect();
+
+            tracing::trace!("SubTree ID: {tree_id} remappings= {remappings:?}");
+
+            let Some((tree_doc, tree)) = resolve_tree(ctx.factory, doc, tree_id) else {
+                if !ctx.is_dry_run() {
+                    return Err(BtError::Raw(format!("can't find tree: {tree_id}")));
+                }
+
+                ctx.record_issue(ValidationIssue::UnresolvedSubTree {
+                    path: subtree_path.to_string(),
+                    id: tree_id.to_string(),
+                });
+
+                return Ok(None);
+            };
+
+            (
+                tree_doc,
+                tree.children.first(),
+                subtree_blackboard(&bb, remappings, &kv),
+            )
+        } else {
+            (doc, element.children.first(), bb)
+        };
+
+        let uid = ctx.next_uid(subtree_path)?;
+
+        let node = match child {
+            Some(child) => build_element(ctx, child_doc, &subtree_path_folders, child, child_bb)?,
+            None => None,
         }
+        .ok_or_else(|| BtError::Raw("no subtree node created".to_string()))?;
+        tracing::debug!("get node: {}", node.node_info());
+
+        ctx.build_decorator_node(element_name, data_proxy, kv, node, uid)
+    } else {
+        tracing::trace!("leaf node: {element_name}");
+
+        ctx.build_leaf(&bb, element_name, path_folders, element)
     }
+}
 
-    const XML: &str = r#"
-    <?xml version="1.0" encoding="UTF-8"?>
-    <root BTCPP_format="4" main_tree_to_execute="main">
-        <BehaviorTree ID="main">
-            <Sequence>
-                <PrintBody body="body"/>
-                <PrintArm arm="left_arm"/>
-                <PrintArm arm="{arm}"/>
-                <Sequence>
-                    <PrintBody body="body"/>
-                    <PrintArm arm="arm"/>
-                    <Sequence>
-                        <PrintArm arm="{arm}"/>
-                        <ForceSuccess>
-                            <PrintBody body="{body}"/>
-                        </ForceSuccess>
-                        <SubTree ID="bbb"/>
-                    </Sequence>
-                </Sequence>
-            </Sequence>
-        </BehaviorTree>
-        <BehaviorTree ID="aaa">
-            <PrintBody body="body"/>
-        </BehaviorTree>
-        <BehaviorTree ID="bbb">
-            <Sequence>
-                <SubTree ID="aaa"/>
-                <PrintArm arm="arm"/>
-                <SubTree ID="aaa"/>
-            </Sequence>
-        </BehaviorTree>
-    </root>"#;
+/// Element describing node types and their ports for editors like Groot2,
+/// trees are checked against it when present
+pub const TREE_NODES_MODEL: &str = "TreeNodesModel";
 
-    #[tokio::test]
-    async fn test_parse() {
-        use tokio_stream::StreamExt;
+#[derive(Debug, Clone)]
+struct ModelPort {
+    name: String,
+    type_name: Option<String>,
+}
 
-        let fmt_layer = tracing_subscriber::fmt::Layer::new()
-            .with_file(true)
-            .with_line_number(true)
-            .with_thread_names(true);
+// node or SubTree ID to its ports
+type TreeNodesModel = HashMap<String, Vec<ModelPort>>;
 
-        let env_filter = EnvFilter::builder()
-            .with_default_directive(LevelFilter::INFO.into())
-            .from_env_lossy();
+fn parse_tree_nodes_model(element: &Element) -> TreeNodesModel {
+    let mut model = TreeNodesModel::new();
 
-        tracing_subscriber::registry()
-            .with(fmt_layer)
-            .with(env_filter)
-            .init();
+    for node in &element.children {
+        let Some(id) = node.attrs.get("ID") else {
+            continue;
+        };
+        let ports = model.entry(id.clone()).or_default();
 
-        let mut factory = Factory::default();
+        for port in &node.children {
+            if !matches!(
+                port.name.as_str(),
+                "input_port" | "output_port" | "inout_port"
+            ) {
+                continue;
+            }
+            let Some(name) = port.attrs.get("name") else {
+                continue;
+            };
+
+            ports.push(ModelPort {
+                name: name.clone(),
+                type_name: port.attrs.get("type").cloned(),
+            });
+        }
+    }
+
+    model
+}
+
+// whether a literal port value can be read as `type_name`, types the model
+// names but this crate has no parser for always pass
+fn matches_model_type(value: &str, type_name: &str) -> bool {
+    let type_name = type_name.trim().to_lowercase();
+
+    match type_name.as_str() {
+        "bool" => value.parse::<bool>().is_ok(),
+        "float" | "double" | "f32" | "f64" => value.parse::<f64>().is_ok(),
+        "u8" | "u16" | "u32" | "u64" | "usize" | "uint8_t" | "uint16_t" | "uint32_t"
+        | "uint64_t" | "unsigned" | "unsigned int" | "unsigned long" => {
+            value.parse::<u64>().is_ok()
+        }
+        "i8" | "i16" | "i32" | "i64" | "isize" | "int8_t" | "int16_t" | "int32_t" | "int64_t"
+        | "int" | "long" | "short" => value.parse::<i64>().is_ok(),
+        _ => true,
+    }
+}
+
+// element of the document, read in full before any node is built
+#[derive(Debug, Clone)]
+struct Element {
+    // node type, see `element_type_name`
+    name: String,
+    attrs: HashMap<String, String>,
+    children: Vec<Element>,
+    // text and CDATA content, see `Factory::set_text_port`
+    text: String,
+    // byte offset of the start tag in the document
+    offset: usize,
+}
+
+impl Element {
+    fn new(e: &BytesStart, offset: usize) -> Result<Self> {
+        Ok(Self {
+            name: element_type_name(e.name().as_ref())?.into_owned(),
+            attrs: AttributesWrapper::new(e.attributes()).kv()?,
+            children: vec![],
+            text: String::new(),
+            offset,
+        })
+    }
+}
+
+// top level elements of `s` with all their descendants
+fn parse_elements(s: &str, sources: &[SourceSpan]) -> Result<Vec<Element>> {
+    let mut reader = Reader::from_str(s);
+    // elements whose end tag wasn't read yet, innermost last
+    let mut open: Vec<Element> = vec![];
+    let mut elements = vec![];
+
+    loop {
+        let pos = reader.buffer_position();
+
+        let element = match reader.read_event()? {
+            Event::Start(e) => {
+                open.push(Element::new(&e, pos)?);
+                continue;
+            }
+            Event::Empty(e) => Element::new(&e, pos)?,
+            Event::End(e) => open.pop().ok_or_else(|| {
+                BtError::Raw(format!(
+                    "unexpected end: {}",
+                    String::from_utf8_lossy(e.name().as_ref())
+                ))
+            })?,
+            Event::Text(e) => {
+                if let Some(element) = open.last_mut() {
+                    element.text.push_str(&e.unescape()?);
+                }
+                continue;
+            }
+            Event::CData(e) => {
+                if let Some(element) = open.last_mut() {
+                    element.text.push_str(std::str::from_utf8(&e)?);
+                }
+                continue;
+            }
+            Event::Eof => break,
+            _ => continue,
+        };
+
+        match open.last_mut() {
+            Some(parent) => parent.children.push(element),
+            None => elements.push(element),
+        }
+    }
+
+    if let Some(element) = open.pop() {
+        return Err(located_error(
+            s,
+            sources,
+            element.offset,
+            element.name,
+            BtError::Raw("unexpected eof, element not closed".to_string()),
+        ));
+    }
+
+    Ok(elements)
+}
+
+/// Document parsed once, any of its BehaviorTrees can then be built on demand,
+/// e.g. a file serving as a library of behaviors.
+pub struct TreeDocument {
+    // the XML after includes were expanded, element offsets are relative to it
+    document: String,
+    // files the ranges of `document` were read from
+    sources: Vec<SourceSpan>,
+    main_tree_id: String,
+    // in document order
+    tree_ids: Vec<String>,
+    // BehaviorTree elements by ID, the main tree included
+    trees: HashMap<String, Element>,
+    model: Option<TreeNodesModel>,
+}
+
+impl TreeDocument {
+    /// Includes of `s` resolve against the current directory, see [`read_xml_file`].
+    pub fn from_xml_str(s: &str) -> Result<Self> {
+        let (document, sources) = expand_includes(s, None, Path::new("."), &mut vec![])?;
+
+        parse_document(document, sources)
+    }
+
+    /// Errors of elements read from included files are located in those files.
+    pub fn from_xml_file(path: impl AsRef<Path>) -> Result<Self> {
+        let (document, sources) = read_xml_source(path.as_ref())?;
+
+        parse_document(document, sources)
+    }
+
+    /// `main_tree_to_execute`, or the first tree of the document.
+    pub fn main_tree_id(&self) -> &str {
+        &self.main_tree_id
+    }
+
+    /// IDs of the BehaviorTrees in document order.
+    pub fn tree_ids(&self) -> impl Iterator<Item = &str> {
+        self.tree_ids.iter().map(String::as_str)
+    }
+
+    /// Build the tree with the given ID, its SubTrees resolve within the document.
+    pub fn create_tree(&self, factory: &Factory, id: &str) -> Result<Option<TreeNodeWrapper>> {
+        self.create_tree_with_blackboard(factory, id, Arc::default(), ParseOptions::default())
+    }
+
+    /// Like [`TreeDocument::create_tree`], with `bb` as the tree blackboard and
+    /// checked according to `options`. Only the tree and the SubTrees it reaches
+    /// are checked for unknown nodes.
+    pub fn create_tree_with_blackboard(
+        &self,
+        factory: &Factory,
+        id: &str,
+        bb: Arc<RwLock<Blackboard>>,
+        options: ParseOptions,
+    ) -> Result<Option<TreeNodeWrapper>> {
+        let Some(root) = self.tree_root(id)? else {
+            return Ok(None);
+        };
+
+        let unknown_nodes = find_unknown_nodes(factory, &reachable_trees(factory, self, id));
+        if !unknown_nodes.is_empty() {
+            if options.strict_unknown_nodes {
+                return Err(BtError::UnknownNodes(unknown_nodes));
+            }
+
+            for node in &unknown_nodes {
+                tracing::warn!("unknown node left out: {node}");
+            }
+        }
+
+        let mut ctx = BuildContext::new(factory, self);
+        ctx.options = options;
+
+        let mut node = None;
+        if factory.parallel_build() {
+            node = create_tree_node_parallel(&ctx, self, root, &bb)?;
+        }
+        if node.is_none() {
+            node = build_element(&ctx, self, &[], root, bb)?;
+        }
+
+        Ok(node.map(number_nodes))
+    }
+
+    // the root node element of a tree
+    fn tree_root(&self, id: &str) -> Result<Option<&Element>> {
+        let tree = self
+            .trees
+            .get(id)
+            .ok_or_else(|| BtError::Raw(format!("can't find tree: {id}")))?;
+
+        Ok(tree.children.first())
+    }
+}
+
+fn parse_document(s: String, sources: Vec<SourceSpan>) -> Result<TreeDocument> {
+    let Some(root) = parse_elements(&s, &sources)?
+        .into_iter()
+        .find(|element| element.name == "root")
+    else {
+        return Err(BtError::Raw("no root range found".to_string()));
+    };
+
+    let mut trees = HashMap::new();
+    let mut tree_ids = vec![];
+    let mut model = None;
+
+    for element in root.children {
+        match element.name.as_str() {
+            "BehaviorTree" => {
+                let Some(id) = element.attrs.get("ID").cloned() else {
+                    return Err(located_error(
+                        &s,
+                        &sources,
+                        element.offset,
+                        "BehaviorTree".to_string(),
+                        BtError::Raw("no ID found in BehaviorTree element".to_string()),
+                    ));
+                };
+
+                tree_ids.push(id.clone());
+                trees.insert(id, element);
+            }
+            // included files bring their own model
+            TREE_NODES_MODEL => model
+                .get_or_insert_with(HashMap::new)
+                .extend(parse_tree_nodes_model(&element)),
+            _ => {}
+        }
+    }
+
+    let main_tree_id = root
+        .attrs
+        .get("main_tree_to_execute")
+        .or_else(|| tree_ids.first())
+        .cloned();
+    let Some(main_tree_id) = main_tree_id.filter(|id| trees.contains_key(id)) else {
+        return Err(BtError::Raw("no main bt tree found".to_string()));
+    };
+
+    Ok(TreeDocument {
+        document: s,
+        sources,
+        main_tree_id,
+        tree_ids,
+        trees,
+        model,
+    })
+}
+
+// the BehaviorTree of a SubTree, looked up in the document of the SubTree
+// first, then in the trees registered with the factory
+fn resolve_tree<'a>(
+    factory: &'a Factory,
+    doc: &'a TreeDocument,
+    id: &str,
+) -> Option<(&'a TreeDocument, &'a Element)> {
+    if let Some(tree) = doc.trees.get(id) {
+        return Some((doc, tree));
+    }
+
+    let doc = factory.registered_tree(id)?;
+    doc.trees.get(id).map(|tree| (doc, tree))
+}
+
+// the tree `id` of `doc` and every tree its SubTrees reach, across documents
+// registered with the factory, in the order they're found
+fn reachable_trees<'a>(
+    factory: &'a Factory,
+    doc: &'a TreeDocument,
+    id: &'a str,
+) -> Vec<(&'a TreeDocument, &'a str, &'a Element)> {
+    let mut found: Vec<(&TreeDocument, &str, &Element)> = vec![];
+    let mut pending: Vec<_> = resolve_tree(factory, doc, id)
+        .map(|(doc, tree)| (doc, id, tree))
+        .into_iter()
+        .collect();
+
+    while let Some((current, tree_id, tree)) = pending.pop() {
+        let seen = found
+            .iter()
+            .any(|(known, known_id, _)| std::ptr::eq(*known, current) && *known_id == tree_id);
+        if seen {
+            continue;
+        }
+        found.push((current, tree_id, tree));
+
+        let mut subtrees = vec![];
+        let mut elements = vec![tree];
+        while let Some(element) = elements.pop() {
+            elements.extend(element.children.iter().rev());
+
+            let Some(id) = element
+                .attrs
+                .get("ID")
+                .filter(|_| element.name == "SubTree")
+            else {
+                continue;
+            };
+            if let Some((subtree_doc, subtree)) = resolve_tree(factory, current, id) {
+                subtrees.push((subtree_doc, id.as_str(), subtree));
+            }
+        }
+
+        // visited in document order
+        pending.extend(subtrees.into_iter().rev());
+    }
+
+    found
+}
+
+// every element of `trees` naming a node type the factory doesn't know
+fn find_unknown_nodes(
+    factory: &Factory,
+    trees: &[(&TreeDocument, &str, &Element)],
+) -> Vec<UnknownNode> {
+    let composite_types = factory.composite_types();
+    let decorator_types = factory.decorator_types();
+    let is_known = |type_name: &str| {
+        composite_types.contains(type_name)
+            || decorator_types.contains(type_name)
+            || factory.is_action_registered(type_name)
+            || factory.is_condition_registered(type_name)
+    };
+
+    let mut unknown_nodes = vec![];
+    for (doc, tree_id, tree) in trees {
+        // elements to visit with their parent path, in document order
+        let mut stack: Vec<_> = tree
+            .children
+            .iter()
+            .rev()
+            .map(|element| (element, String::new()))
+            .collect();
+
+        while let Some((element, parent_path)) = stack.pop() {
+            let path = if parent_path.is_empty() {
+                element.name.clone()
+            } else {
+                format!("{parent_path}/{}", element.name)
+            };
+
+            if !is_known(&element.name) {
+                let (file, line, _) = source_location(&doc.document, &doc.sources, element.offset);
+
+                unknown_nodes.push(UnknownNode {
+                    tree_id: tree_id.to_string(),
+                    path: path.clone(),
+                    type_name: element.name.clone(),
+                    line,
+                    file,
+                });
+            }
+
+            stack.extend(
+                element
+                    .children
+                    .iter()
+                    .rev()
+                    .map(|child| (child, path.clone())),
+            );
+        }
+    }
+
+    unknown_nodes
+}
+
+/// Node of a tree described with [`crate::bt`].
+#[doc(hidden)]
+pub struct NodeSpec {
+    pub type_name: &'static str,
+    pub ports: Vec<(&'static str, String)>,
+    pub children: Vec<NodeSpec>,
+}
+
+impl NodeSpec {
+    fn into_element(self) -> Result<Element> {
+        Ok(Element {
+            name: element_type_name(self.type_name.as_bytes())?.into_owned(),
+            attrs: self
+                .ports
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+            children: self
+                .children
+                .into_iter()
+                .map(NodeSpec::into_element)
+                .collect::<Result<_>>()?,
+            text: String::new(),
+            offset: 0,
+        })
+    }
+}
+
+/// Build the tree of [`crate::bt`], errors locate nodes by their element
+/// path only, there is no document for line and column.
+#[doc(hidden)]
+pub fn create_bt_tree_from_spec(
+    factory: &Factory,
+    root: NodeSpec,
+) -> Result<Option<TreeNodeWrapper>> {
+    const TREE_ID: &str = "main";
+
+    let tree = Element {
+        name: "BehaviorTree".to_string(),
+        attrs: HashMap::from([("ID".to_string(), TREE_ID.to_string())]),
+        children: vec![root.into_element()?],
+        text: String::new(),
+        offset: 0,
+    };
+
+    let doc = TreeDocument {
+        document: String::new(),
+        sources: vec![],
+        main_tree_id: TREE_ID.to_string(),
+        tree_ids: vec![TREE_ID.to_string()],
+        trees: HashMap::from([(TREE_ID.to_string(), tree)]),
+        model: None,
+    };
+
+    doc.create_tree(factory, TREE_ID)
+}
+
+/// Append ` key="value"` to a generated element, with `value` escaped.
+#[doc(hidden)]
+pub fn push_attribute(xml: &mut String, key: &str, value: &str) {
+    xml.push(' ');
+    xml.push_str(key);
+    xml.push_str("=\"");
+    xml.push_str(&quick_xml::escape::escape(value));
+    xml.push('"');
+}
+
+/// Element pulling the BehaviorTree definitions of another file into the
+/// document, e.g. `<include path="trees/dock.xml"/>`
+pub const INCLUDE: &str = "include";
+
+// `s` with every `<include path="..."/>` replaced by the content of the
+// included root and where each range of the result was read from. `file` is
+// the file of `s`, relative paths resolve against `base_dir`. `stack` holds
+// the files being expanded, to catch cycles
+fn expand_includes(
+    s: &str,
+    file: Option<&Path>,
+    base_dir: &Path,
+    stack: &mut Vec<PathBuf>,
+) -> Result<(String, Vec<SourceSpan>)> {
+    // each file is upgraded on its own, includes may mix formats
+    if let Some(upgraded) = upgrade_format_3(s)? {
+        return expand_includes(&upgraded, file, base_dir, stack);
+    }
+
+    let text: Arc<str> = Arc::from(s);
+    let file: Option<Arc<Path>> = file.map(Arc::from);
+    let copied_span = |start, source_start| SourceSpan {
+        start,
+        file: file.clone(),
+        text: text.clone(),
+        source_start,
+    };
+
+    let mut reader = Reader::from_str(s);
+    let mut expanded = String::new();
+    let mut sources = vec![];
+    let mut copied = 0;
+
+    loop {
+        let pos = reader.buffer_position();
+
+        let e = match reader.read_event()? {
+            Event::Start(e) if e.name().as_ref() == INCLUDE.as_bytes() => {
+                reader.read_to_end(e.to_end().name())?;
+                e
+            }
+            Event::Empty(e) if e.name().as_ref() == INCLUDE.as_bytes() => e,
+            Event::Eof => break,
+            _ => continue,
+        };
+
+        let Some(path) = AttributesWrapper::new(e.attributes()).get_key("path")? else {
+            return Err(located_error(
+                s,
+                &[copied_span(0, 0)],
+                pos,
+                INCLUDE.to_string(),
+                BtError::Raw("no path found in include element".to_string()),
+            ));
+        };
+
+        let path = base_dir.join(path);
+        let path = path.canonicalize().map_err(|e| {
+            BtError::Raw(format!(
+                "resolve include meet failure: path= {} err= {e}",
+                path.display()
+            ))
+        })?;
+
+        if stack.contains(&path) {
+            return Err(BtError::Raw(format!(
+                "include cycle: path= {}",
+                path.display()
+            )));
+        }
+
+        let (included, included_sources) = read_included(&path, stack)?;
+        let root = root_range(&included)?;
+
+        sources.push(copied_span(expanded.len(), copied));
+        expanded.push_str(&s[copied..pos]);
+
+        // spans of the included root, moved to where it's spliced
+        for (i, span) in included_sources.iter().enumerate() {
+            let end = included_sources
+                .get(i + 1)
+                .map_or(included.len(), |next| next.start);
+            let (start, end) = (span.start.max(root.start), end.min(root.end));
+
+            if start < end {
+                sources.push(SourceSpan {
+                    start: expanded.len() + start - root.start,
+                    source_start: span.source_start + start - span.start,
+                    ..span.clone()
+                });
+            }
+        }
+        expanded.push_str(&included[root]);
+
+        copied = reader.buffer_position();
+    }
+
+    sources.push(copied_span(expanded.len(), copied));
+    expanded.push_str(&s[copied..]);
+
+    Ok((expanded, sources))
+}
+
+fn read_included(path: &Path, stack: &mut Vec<PathBuf>) -> Result<(String, Vec<SourceSpan>)> {
+    let s = std::fs::read_to_string(path).map_err(|e| {
+        BtError::Raw(format!(
+            "read xml meet failure: path= {} err= {e}",
+            path.display()
+        ))
+    })?;
+
+    stack.push(path.to_path_buf());
+    let base_dir = path.parent().unwrap_or(Path::new("."));
+    let res = expand_includes(&s, Some(path), base_dir, stack);
+    stack.pop();
+
+    res
+}
+
+// range of everything between `<root>` and `</root>`
+fn root_range(s: &str) -> Result<Range<usize>> {
+    let mut reader = Reader::from_str(s);
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) if e.name().as_ref() == b"root" => {
+                return Ok(reader.read_to_end(e.to_end().name())?);
+            }
+            Event::Eof => return Err(BtError::Raw("no root range found".to_string())),
+            _ => {}
+        }
+    }
+}
+
+/// Root attribute with the BT.CPP format version of the document, trees with
+/// `BTCPP_format="3"` are upgraded to the current format before parsing.
+pub const BTCPP_FORMAT: &str = "BTCPP_format";
+
+// the document rewritten in format 4, `None` unless it declares format 3:
+// - `<Action ID="Foo"/>` and the other generic elements become `<Foo/>`
+// - `SequenceStar`, `RetryUntilSuccesful` and `Switch2`..`Switch6` are renamed
+// - `SubTree` port values are parent keys, they get wrapped in `{}`
+// - `SubTreePlus` becomes `SubTree`, its `__autoremap` becomes `_autoremap`
+// - `__shared_blackboard="true"` maps to `_autoremap="true"`
+fn upgrade_format_3(s: &str) -> Result<Option<String>> {
+    let mut reader = Reader::from_str(s);
+
+    let is_format_3 = loop {
+        match reader.read_event()? {
+            Event::Start(e) | Event::Empty(e) if e.name().as_ref() == b"root" => {
+                let format = AttributesWrapper::new(e.attributes()).get_key(BTCPP_FORMAT)?;
+                break format.as_deref() == Some("3");
+            }
+            Event::Eof => break false,
+            _ => {}
+        }
+    };
+
+    if !is_format_3 {
+        return Ok(None);
+    }
+
+    let mut reader = Reader::from_str(s);
+    let mut writer = Writer::new(Vec::new());
+    // renamed element names of the open elements, for their end tags
+    let mut names = vec![];
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) => {
+                let e = upgrade_element_3(&e)?;
+                names.push(String::from_utf8_lossy(e.name().as_ref()).into_owned());
+                writer.write_event(Event::Start(e))?;
+            }
+            Event::Empty(e) => {
+                writer.write_event(Event::Empty(upgrade_element_3(&e)?))?;
+            }
+            Event::End(_) => {
+                let name = names.pop().unwrap_or_default();
+                writer.write_event(Event::End(BytesEnd::new(name)))?;
+            }
+            Event::Eof => break,
+            e => writer.write_event(e)?,
+        }
+    }
+
+    String::from_utf8(writer.into_inner())
+        .map(Some)
+        .map_err(|e| BtError::Raw(format!("upgrade format 3 meet failure: err= {e}")))
+}
+
+fn upgrade_element_3<'a>(e: &BytesStart<'a>) -> Result<BytesStart<'a>> {
+    let name = std::str::from_utf8(e.name().into_inner())?;
+    let mut kv = vec![];
+    for att in e.attributes() {
+        let att = att?;
+        kv.push((
+            std::str::from_utf8(att.key.as_ref())?.to_string(),
+            att.unescape_value()?.into_owned(),
+        ));
+    }
+
+    let generic = matches!(name, "Action" | "Condition" | "Control" | "Decorator");
+    let name = match name {
+        _ if generic => kv
+            .iter()
+            .find(|(k, _)| k == "ID")
+            .map(|(_, v)| v.clone())
+            .ok_or_else(|| BtError::Raw(format!("no ID found in {name} element")))?,
+        "SequenceStar" => "SequenceWithMemory".to_string(),
+        "RetryUntilSuccesful" => "RetryUntilSuccessful".to_string(),
+        "Switch2" | "Switch3" | "Switch4" | "Switch5" | "Switch6" => "Switch".to_string(),
+        "SubTreePlus" => "SubTree".to_string(),
+        name => name.to_string(),
+    };
+    let is_plain_subtree = e.name().as_ref() == b"SubTree";
+
+    let mut upgraded = BytesStart::new(name.clone());
+    for (key, value) in kv {
+        let (key, value) = match key.as_str() {
+            "ID" if generic => continue,
+            BTCPP_FORMAT if name == "root" => (key, "4".to_string()),
+            "__shared_blackboard" | "__autoremap" => (SUBTREE_AUTOREMAP.to_string(), value),
+            "ID" | "name" => (key, value),
+            _ if is_plain_subtree && !key.starts_with('_') && !is_ref_key(&value) => {
+                (key, format!("{{{value}}}"))
+            }
+            _ => (key, value),
+        };
+
+        upgraded.push_attribute((key.as_str(), value.as_str()));
+    }
+
+    Ok(upgraded)
+}
+
+/// Read an XML file with its `<include>` elements expanded, relative include
+/// paths resolve against the directory of the including file. Building from
+/// the returned text locates errors in it, [`create_bt_tree_from_xml_file`]
+/// and [`TreeDocument::from_xml_file`] locate them in the included files.
+pub fn read_xml_file(path: impl AsRef<Path>) -> Result<String> {
+    read_xml_source(path.as_ref()).map(|(s, _)| s)
+}
+
+fn read_xml_source(path: &Path) -> Result<(String, Vec<SourceSpan>)> {
+    let path = path.canonicalize().map_err(|e| {
+        BtError::Raw(format!(
+            "resolve xml meet failure: path= {} err= {e}",
+            path.display()
+        ))
+    })?;
+
+    read_included(&path, &mut vec![])
+}
+
+/// Build the tree of an XML file, see [`read_xml_file`].
+pub fn create_bt_tree_from_xml_file(
+    factory: &Factory,
+    path: impl AsRef<Path>,
+) -> Result<Option<TreeNodeWrapper>> {
+    let doc = TreeDocument::from_xml_file(path)?;
+
+    doc.create_tree_with_blackboard(
+        factory,
+        doc.main_tree_id(),
+        Arc::default(),
+        ParseOptions::default(),
+    )
+}
+
+/// Build the tree of a document read from `reader`, e.g. a socket. The whole
+/// document is read before parsing, includes resolve against the current
+/// directory.
+pub fn create_bt_tree_from_reader(
+    factory: &Factory,
+    mut reader: impl Read,
+) -> Result<Option<TreeNodeWrapper>> {
+    let mut s = String::new();
+    reader
+        .read_to_string(&mut s)
+        .map_err(|e| BtError::Raw(format!("read xml meet failure: err= {e}")))?;
+
+    create_bt_tree_from_xml_str(factory, &s)
+}
+
+/// Async [`create_bt_tree_from_reader`], for services receiving trees over
+/// the network.
+#[cfg(feature = "rt-tokio")]
+pub async fn create_bt_tree_from_async_reader(
+    factory: &Factory,
+    mut reader: impl tokio::io::AsyncRead + Unpin,
+) -> Result<Option<TreeNodeWrapper>> {
+    use tokio::io::AsyncReadExt;
+
+    let mut s = String::new();
+    reader
+        .read_to_string(&mut s)
+        .await
+        .map_err(|e| BtError::Raw(format!("read xml meet failure: err= {e}")))?;
+
+    create_bt_tree_from_xml_str(factory, &s)
+}
+
+/// Includes of `s` resolve against the current directory, see [`read_xml_file`].
+pub fn create_bt_tree_from_xml_str(factory: &Factory, s: &str) -> Result<Option<TreeNodeWrapper>> {
+    create_bt_tree_from_xml_str_with_blackboard(factory, s, Arc::default())
+}
+
+/// Like [`create_bt_tree_from_xml_str`], with `bb` as the main tree blackboard,
+/// e.g. seeded with mission parameters before the first tick.
+pub fn create_bt_tree_from_xml_str_with_blackboard(
+    factory: &Factory,
+    s: &str,
+    bb: Arc<RwLock<Blackboard>>,
+) -> Result<Option<TreeNodeWrapper>> {
+    build_tree(factory, s, bb, ParseOptions::default())
+}
+
+/// How strictly a document is checked while building. The default fails on
+/// unknown node types and ports and accepts composites without children.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Fail on node types the factory doesn't know, otherwise they're logged
+    /// and left out of the tree.
+    pub strict_unknown_nodes: bool,
+    /// Fail on attributes that aren't declared ports, otherwise they're logged.
+    pub strict_unknown_ports: bool,
+    /// Accept composites without children, otherwise they fail the build.
+    pub allow_empty_composites: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            strict_unknown_nodes: true,
+            strict_unknown_ports: true,
+            allow_empty_composites: true,
+        }
+    }
+}
+
+/// Like [`create_bt_tree_from_xml_str`], checked according to `options`, e.g.
+/// lenient while developing and strict in production.
+pub fn create_bt_tree_from_xml_str_with_options(
+    factory: &Factory,
+    s: &str,
+    options: ParseOptions,
+) -> Result<Option<TreeNodeWrapper>> {
+    build_tree(factory, s, Arc::default(), options)
+}
+
+fn build_tree(
+    factory: &Factory,
+    s: &str,
+    bb: Arc<RwLock<Blackboard>>,
+    options: ParseOptions,
+) -> Result<Option<TreeNodeWrapper>> {
+    let doc = TreeDocument::from_xml_str(s)?;
+
+    doc.create_tree_with_blackboard(factory, &doc.main_tree_id, bb, options)
+}
+
+// returns `None` when the root isn't a composite, the caller then builds sequentially
+fn create_tree_node_parallel(
+    ctx: &BuildContext,
+    doc: &TreeDocument,
+    element: &Element,
+    bb: &Arc<RwLock<Blackboard>>,
+) -> Result<Option<TreeNodeWrapper>> {
+    if !ctx
+        .factory
+        .composite_types()
+        .contains(element.name.as_str())
+    {
+        return Ok(None);
+    }
+
+    let mut path_folders = vec![element.name.clone()];
+    let Some(mut cp) = ctx
+        .build_composite_node(bb, &element.name, &mut path_folders, element)
+        .map_err(|e| ctx.locate(e, doc, element, &[]))?
+    else {
+        return Ok(None);
+    };
+
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = element.children.len().div_ceil(threads).max(1);
+
+    let children = std::thread::scope(|scope| {
+        let handles: Vec<_> = element
+            .children
+            .chunks(chunk_size)
+            .map(|chunk| {
+                let path_folders = &path_folders;
+                let cp = &cp;
+
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|child| {
+                            let child_bb = element_blackboard(Some(cp), bb);
+                            build_element(ctx, doc, path_folders, child, child_bb)
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("tree builder thread panicked"))
+            .collect::<Result<Vec<_>>>()
+    })?;
+
+    for child in children.into_iter().flatten() {
+        cp.add_child(child);
+    }
+    ctx.check_children(&cp)
+        .map_err(|e| ctx.locate(e, doc, element, &[]))?;
+
+    ctx.wrap_retries(TreeNodeWrapper::new(NodeWrapper::Composite(cp)))
+        .map(Some)
+}
+
+// uids in pre-order, a `_retries` wrapper before the node it wraps, whatever
+// order the nodes were built in
+fn number_nodes(mut root: TreeNodeWrapper) -> TreeNodeWrapper {
+    let mut uid = 0;
+    root.apply_recursive_visitor_mut(&mut |node, _layer| {
+        node.set_uid(uid);
+        uid += 1;
+    });
+
+    root
+}
+
+/// Build the tree with all leaves stubbed out and report every problem found,
+/// without ticking any real action.
+pub fn validate_bt_tree_from_xml_str(factory: &Factory, s: &str) -> Result<ValidationReport> {
+    let doc = TreeDocument::from_xml_str(s)?;
+
+    let mut ctx = BuildContext::new(factory, &doc);
+    ctx.report = Some(Mutex::new(ValidationReport::default()));
+
+    let node = match doc.tree_root(&doc.main_tree_id)? {
+        Some(root) => build_element(
+            &ctx,
+            &doc,
+            &[],
+            root,
+            Arc::new(RwLock::new(Blackboard::default())),
+        )?,
+        None => None,
+    };
+
+    let mut report = ctx.report.take().unwrap_or_default().into_inner();
+
+    if let Some(node) = node {
+        node.apply_recursive_visitor(&mut |_node, _layer| {
+            report.node_count += 1;
+        });
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use tracing::level_filters::LevelFilter;
+    use tracing_subscriber::prelude::*;
+    use tracing_subscriber::EnvFilter;
+
+    use crate::node::StateNotif;
+    use crate::TreeNode;
+    use crate::{factory::boxify_action, node::action::ActionNodeImpl, NodeStatus};
+
+    use super::*;
+
+    fn assets_dir() -> PathBuf {
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("assets");
+
+        d
+    }
+
+    struct PrintBody;
+
+    impl ActionNodeImpl for PrintBody {
+        fn tick_status(&mut self, _data_proxy: &mut DataProxy) -> NodeStatus {
+            println!("body tick");
+            NodeStatus::Success
+        }
+
+        fn halt(&mut self) {
+            tracing::info!("halt print body action ========");
+        }
+    }
+
+    struct PrintArm;
+
+    impl ActionNodeImpl for PrintArm {
+        fn tick_status(&mut self, _data_proxy: &mut DataProxy) -> NodeStatus {
+            println!("arm tick");
+            NodeStatus::Success
+        }
+    }
+
+    const XML: &str = r#"
+    <?xml version="1.0" encoding="UTF-8"?>
+    <root BTCPP_format="4" main_tree_to_execute="main">
+        <BehaviorTree ID="main">
+            <Sequence>
+                <PrintBody body="body"/>
+                <PrintArm arm="left_arm"/>
+                <PrintArm arm="{arm}"/>
+                <Sequence>
+                    <PrintBody body="body"/>
+                    <PrintArm arm="arm"/>
+                    <Sequence>
+                        <PrintArm arm="{arm}"/>
+                        <ForceSuccess>
+                            <PrintBody body="{body}"/>
+                        </ForceSuccess>
+                        <SubTree ID="bbb"/>
+                    </Sequence>
+                </Sequence>
+            </Sequence>
+        </BehaviorTree>
+        <BehaviorTree ID="aaa">
+            <PrintBody body="body"/>
+        </BehaviorTree>
+        <BehaviorTree ID="bbb">
+            <Sequence>
+                <SubTree ID="aaa"/>
+                <PrintArm arm="arm"/>
+                <SubTree ID="aaa"/>
+            </Sequence>
+        </BehaviorTree>
+    </root>"#;
+
+    fn test_factory() -> Factory {
+        let mut factory = Factory::default();
+        factory.register_action_node_type(
+            "PrintBody".try_into().unwrap(),
+            boxify_action(|_, _| Ok(PrintBody)),
+        );
+        factory.register_action_node_type(
+            "PrintArm".try_into().unwrap(),
+            boxify_action(|_, _| Ok(PrintArm)),
+        );
+
+        factory
+    }
+
+    #[test]
+    fn test_parse_inline() {
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), XML)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(node.tick(), NodeStatus::Success);
+    }
+
+    #[test]
+    fn test_tree_test_ext() {
+        use crate::testing::TreeTestExt;
+
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <SetBlackboard value="42" output_key="the_answer"/>
+                    <Sleep msec="0"/>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(node.tick_until_completed(10), NodeStatus::Success);
+        node.assert_node_status("Sequence", NodeStatus::Success);
+        node.assert_node_status("Sequence/Sleep", NodeStatus::Idle);
+        node.assert_blackboard_eq("the_answer", serde_json::json!("42"));
+    }
+
+    #[test]
+    fn test_validate() {
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <PrintBody body="{body"/>
+                    <MissingAction/>
+                    <SubTree ID="missing"/>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        let report = validate_bt_tree_from_xml_str(&test_factory(), xml).unwrap();
+
+        assert_eq!(report.issues.len(), 3);
+        assert_eq!(report.node_count, 3);
+
+        let report = validate_bt_tree_from_xml_str(&test_factory(), XML).unwrap();
+        assert!(report.is_ok(), "{:?}", report.issues);
+    }
+
+    #[test]
+    fn test_provided_ports() {
+        use crate::node::port::PortDirection;
+
+        let factory = test_factory();
+        let ports = factory.provided_ports("Sleep").unwrap();
+        assert_eq!(ports[0].type_name, "u64");
+        assert_eq!(ports[0].direction, PortDirection::Input);
+        // actions without a declaration accept any attribute
+        assert!(factory.provided_ports("PrintBody").is_none());
+
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Sequence _tags="demo">
+                    <Sleep msec="10" mesc="10"/>
+                    <PrintBody body="hello" anything="goes"/>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        let err = create_bt_tree_from_xml_str(&factory, xml).err().unwrap();
+        assert!(err.to_string().contains("key= mesc"), "{err}");
+
+        let report = validate_bt_tree_from_xml_str(&factory, xml).unwrap();
+        assert_eq!(
+            report.issues,
+            [ValidationIssue::UnknownPort {
+                path: "Sequence/Sleep".to_string(),
+                type_name: "Sleep".to_string(),
+                key: "mesc".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_tree_nodes_model_check() {
+        let xml = r#"
+        <root BTCPP_format="4" main_tree_to_execute="main">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <Sleep msec="soon"/>
+                    <PrintBody name="print" body="{body}" color="red"/>
+                    <SubTree ID="sub" target="{goal}"/>
+                </Sequence>
+            </BehaviorTree>
+            <BehaviorTree ID="sub">
+                <PrintBody body="{target}"/>
+            </BehaviorTree>
+            <TreeNodesModel>
+                <Action ID="Sleep">
+                    <input_port name="msec" type="unsigned int"/>
+                </Action>
+                <Action ID="PrintBody">
+                    <input_port name="body" type="std::string"/>
+                </Action>
+                <SubTree ID="sub">
+                    <input_port name="goal"/>
+                </SubTree>
+            </TreeNodesModel>
+        </root>"#;
+
+        let report = validate_bt_tree_from_xml_str(&test_factory(), xml).unwrap();
+        assert_eq!(
+            report.issues,
+            [
+                ValidationIssue::ModelTypeMismatch {
+                    path: "Sequence/Sleep".to_string(),
+                    key: "msec".to_string(),
+                    value: "soon".to_string(),
+                    port_type: "unsigned int".to_string(),
+                },
+                ValidationIssue::ModelUnknownPort {
+                    path: "Sequence/PrintBody(print)".to_string(),
+                    type_name: "PrintBody".to_string(),
+                    key: "color".to_string(),
+                },
+                ValidationIssue::ModelUnknownPort {
+                    path: "Sequence/SubTree".to_string(),
+                    type_name: "sub".to_string(),
+                    key: "target".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tree_host_reload() {
+        use crate::host::TreeHost;
+        use crate::testing::TreeTestExt;
+
+        let tree_xml = |leaves: &str| {
+            format!(
+                r#"<root BTCPP_format="4"><BehaviorTree ID="main"><Sequence>{leaves}</Sequence></BehaviorTree></root>"#
+            )
+        };
+        let first = r#"<SetBlackboard value="1" output_key="a"/><Sleep msec="60000"/>"#;
+        let path = std::env::temp_dir().join(format!("host_reload_{}.xml", std::process::id()));
+        std::fs::write(&path, tree_xml(first)).unwrap();
+
+        let mut host = TreeHost::new(test_factory(), &path).unwrap();
+        host.tree()
+            .data_proxy_ref()
+            .blackboard()
+            .set_any("client".to_string(), 7u32);
+        assert_eq!(host.tick(), NodeStatus::Running);
+        assert!(!host.reload_if_changed().unwrap());
+
+        std::fs::write(
+            &path,
+            tree_xml(&format!(
+                r#"{first}<SetBlackboard value="2" output_key="b"/>"#
+            )),
+        )
+        .unwrap();
+        assert!(host.reload_if_changed().unwrap());
+
+        let statuses: Vec<_> = host
+            .tree()
+            .uid_map()
+            .keys()
+            .map(|uid| host.tree().status_handles()[uid].get())
+            .collect();
+        // the running Sequence and Sleep start over
+        assert_eq!(
+            statuses,
+            [
+                NodeStatus::Idle,
+                NodeStatus::Success,
+                NodeStatus::Idle,
+                NodeStatus::Idle
+            ]
+        );
+        host.tree()
+            .assert_blackboard_eq("a", serde_json::json!("1"));
+        let bb = host.tree().data_proxy_ref().shared_blackboard().clone();
+        assert_eq!(bb.read().get_any::<u32>("client").as_deref(), Some(&7));
+
+        // a broken file keeps the current tree
+        std::fs::write(&path, "<root>").unwrap();
+        assert!(host.reload_if_changed().is_err());
+        assert_eq!(host.tick(), NodeStatus::Running);
+        host.tree()
+            .assert_blackboard_eq("a", serde_json::json!("1"));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_export_tree_nodes_model() {
+        use crate::factory::FactoryManifest;
+
+        let mut factory = test_factory();
+        factory
+            .apply_manifest(
+                FactoryManifest::from_json_str(
+                    r#"{"aliases": {"Wait": "Sleep"}, "defaults": {"Wait": {"msec": "100"}}}"#,
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        factory.register_action_node_type(
+            "^Print.*$".try_into().unwrap(),
+            boxify_action(|_, _| Ok(PrintBody)),
+        );
+
+        let model = factory.export_tree_nodes_model();
+        assert!(model.starts_with("<TreeNodesModel>\n"));
+        assert!(model.contains(
+            "    <Action ID=\"Wait\">\n        <input_port name=\"msec\" type=\"u64\" default=\"100\"/>\n    </Action>\n"
+        ));
+        assert!(model.contains("    <Action ID=\"Sleep\">\n"));
+        assert!(model.contains("    <Action ID=\"PrintBody\"/>\n"));
+        assert!(model.contains("    <Control ID=\"Sequence\">\n"));
+        assert!(!model.contains("Print.*"));
+        assert!(!model.contains("ID=\"SubTree\""));
+    }
+
+    #[test]
+    fn test_generate_schema() {
+        let mut factory = test_factory();
+        factory.register_action_node_type(
+            "^Print.*$".try_into().unwrap(),
+            boxify_action(|_, _| Ok(PrintBody)),
+        );
+
+        let xsd = factory.generate_schema();
+
+        let mut reader = Reader::from_str(&xsd);
+        while !matches!(reader.read_event().unwrap(), Event::Eof) {}
+
+        assert!(xsd.contains("      <xs:element ref=\"Sequence\"/>\n"));
+        assert!(xsd.contains("      <xs:element ref=\"PrintBody\"/>\n"));
+        assert!(!xsd.contains("Print.*"));
+        assert!(xsd.contains(
+            "  <xs:element name=\"Script\">\n    <xs:complexType mixed=\"true\">\n      <xs:attribute name=\"code\" type=\"xs:string\"/>\n"
+        ));
+        // no declared ports, any attribute goes
+        assert!(xsd.contains(
+            "  <xs:element name=\"PrintBody\">\n    <xs:complexType>\n      <xs:attribute name=\"name\" type=\"xs:string\"/>\n      <xs:attributeGroup ref=\"nodeAttributes\"/>\n      <xs:anyAttribute processContents=\"skip\"/>\n"
+        ));
+    }
+
+    #[test]
+    fn test_port_default_values() {
+        use crate::node::port::{PortInfo, PortsList};
+
+        struct Greet;
+
+        impl ActionNodeImpl for Greet {
+            fn tick_status(&mut self, data_proxy: &mut DataProxy) -> NodeStatus {
+                let greeting = data_proxy.get_input::<String>("greeting").unwrap();
+                let name = data_proxy.get_input::<String>("name").unwrap();
+
+                data_proxy
+                    .set_output("message", format!("{greeting} {name}"))
+                    .into()
+            }
+
+            fn provided_ports() -> Option<PortsList> {
+                Some(vec![
+                    PortInfo::input::<String>("greeting").with_default("hello"),
+                    PortInfo::input::<String>("name").with_default("world"),
+                    PortInfo::output::<String>("message").with_default("ignored"),
+                ])
+            }
+        }
+
+        let mut factory = test_factory();
+        factory.register_action_node_type(
+            "^Greet$".try_into().unwrap(),
+            boxify_action(|_, _| Ok(Greet)),
+        );
+
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Greet name="bob" message="{message}"/>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&factory, xml).unwrap().unwrap();
+
+        assert_eq!(node.tick(), NodeStatus::Success);
+        assert_eq!(
+            node.data_proxy_ref().blackboard().get_entry("message"),
+            Some(serde_json::json!("hello bob"))
+        );
+    }
+
+    #[test]
+    fn test_node_name() {
+        let xml = r#"
+        <root BTCPP_format="4" main_tree_to_execute="main">
+            <BehaviorTree ID="main">
+                <Sequence name="mission">
+                    <SetBlackboard name="pick_arm" value="left_arm" output_key="arm"/>
+                    <SubTree ID="sub" name="dock"/>
+                </Sequence>
+            </BehaviorTree>
+            <BehaviorTree ID="sub">
+                <SetBlackboard value="{arm}" output_key="copied"/>
+            </BehaviorTree>
+        </root>"#;
+
+        let node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+
+        let entries: Vec<_> = node
+            .uid_map()
+            .into_values()
+            .map(|entry| (entry.full_path, entry.name, entry.type_name))
+            .collect();
+        assert_eq!(
+            entries,
+            [
+                (
+                    "Sequence(mission)".to_string(),
+                    Some("mission".to_string()),
+                    "Sequence".to_string()
+                ),
+                (
+                    "Sequence(mission)/SetBlackboard(pick_arm)".to_string(),
+                    Some("pick_arm".to_string()),
+                    "SetBlackboard".to_string()
+                ),
+                (
+                    "Sequence(mission)/SubTree(dock)".to_string(),
+                    Some("dock".to_string()),
+                    "SubTree".to_string()
+                ),
+                (
+                    "Sequence(mission)/SubTree(dock)/SetBlackboard".to_string(),
+                    None,
+                    "SetBlackboard".to_string()
+                ),
+            ]
+        );
+        assert!(node
+            .node_info()
+            .contains("path= Sequence(mission)/SubTree(dock)"));
+        assert!(node.dot_info().contains("\"1_SetBlackboard(pick_arm)\""));
+
+        let xml = xml.replace("pick_arm", "pick/arm");
+        let err = create_bt_tree_from_xml_str(&test_factory(), &xml)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("invalid node name"), "{err}");
+    }
+
+    #[test]
+    fn test_node_metadata() {
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Sequence _description="fetch the arm" _tags="demo">
+                    <SetBlackboard value="1" output_key="a" _comment="todo"/>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        let node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+
+        let data_proxy = node.data_proxy_ref();
+        assert_eq!(
+            data_proxy.metadata(),
+            &BTreeMap::from([("_description".to_string(), "fetch the arm".to_string())])
+        );
+        assert!(data_proxy.input_port("_description").is_none());
+        assert!(data_proxy.has_tag("demo"));
+
+        let entries = node.uid_map();
+        assert_eq!(entries[&1].metadata["_comment"], "todo");
+        assert!(node.node_info().contains("_description= fetch the arm"));
+    }
+
+    #[test]
+    fn test_format_3() {
+        use crate::testing::TreeTestExt;
+
+        let xml = r#"
+        <root BTCPP_format="3" main_tree_to_execute="main">
+            <BehaviorTree ID="main">
+                <SequenceStar>
+                    <Action ID="SetBlackboard" value="left_arm" output_key="arm"/>
+                    <RetryUntilSuccesful num_attempts="2">
+                        <SubTree ID="copy" source="arm"/>
+                    </RetryUntilSuccesful>
+                    <SubTreePlus ID="shared" __autoremap="true"/>
+                </SequenceStar>
+            </BehaviorTree>
+            <BehaviorTree ID="copy">
+                <SetBlackboard value="{source}" output_key="copied"/>
+            </BehaviorTree>
+            <BehaviorTree ID="shared">
+                <SetBlackboard value="{arm}" output_key="shared_arm"/>
+            </BehaviorTree>
+        </root>"#;
+
+        let upgraded = upgrade_format_3(xml).unwrap().unwrap();
+        assert!(upgraded.contains(r#"BTCPP_format="4""#));
+        assert!(upgraded.contains(r#"<SubTree ID="copy" source="{arm}"/>"#));
+        assert!(upgrade_format_3(XML).unwrap().is_none());
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+        assert_eq!(node.tick_until_completed(1), NodeStatus::Success);
+
+        let types: Vec<_> = node.uid_map().into_values().map(|e| e.type_name).collect();
+        assert_eq!(
+            types,
+            [
+                "SequenceWithMemory",
+                "SetBlackboard",
+                "RetryUntilSuccessful",
+                "SubTree",
+                "SetBlackboard",
+                "SubTree",
+                "SetBlackboard"
+            ]
+        );
+        node.assert_blackboard_eq("shared_arm", serde_json::json!("left_arm"));
+    }
+
+    #[test]
+    fn test_subtree_literal_params() {
+        use crate::testing::TreeTestExt;
+
+        let xml = r#"
+        <root BTCPP_format="4" main_tree_to_execute="main">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <SubTree ID="sub" speed="fast" result="{chosen}"/>
+                    <SubTree ID="sub" _autoremap="true" speed="slow"/>
+                </Sequence>
+            </BehaviorTree>
+            <BehaviorTree ID="sub">
+                <SetBlackboard value="{speed}" output_key="result"/>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+        assert_eq!(node.tick_until_completed(1), NodeStatus::Success);
+
+        node.assert_blackboard_eq("chosen", serde_json::json!("fast"));
+        // autoremapped writes reach the parent, the literal itself doesn't
+        node.assert_blackboard_eq("result", serde_json::json!("slow"));
+        assert!(node
+            .data_proxy_ref()
+            .blackboard()
+            .get_entry("speed")
+            .is_none());
+    }
+
+    #[test]
+    fn test_namespaced_node_types() {
+        use crate::factory::ActionRegex;
+        use crate::testing::TreeTestExt;
+
+        struct Mark(&'static str);
+
+        impl ActionNodeImpl for Mark {
+            fn tick_status(&mut self, data_proxy: &mut DataProxy) -> NodeStatus {
+                data_proxy
+                    .blackboard()
+                    .set(self.0.to_string(), serde_json::json!(true));
+                NodeStatus::Success
+            }
+        }
+
+        let mut factory = test_factory();
+        for namespace in ["nav", "arm"] {
+            let key = if namespace == "nav" {
+                "nav_ran"
+            } else {
+                "arm_ran"
+            };
+            factory.register_action_node_type(
+                ActionRegex::namespaced(namespace, "MoveTo").unwrap(),
+                boxify_action(move |_, _| Ok(Mark(key))),
+            );
+        }
+
+        let xml = r#"
+        <root BTCPP_format="4" xmlns:arm="urn:arm">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <nav.MoveTo/>
+                    <arm:MoveTo xmlns:arm="urn:arm"/>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&factory, xml).unwrap().unwrap();
+        assert_eq!(node.tick_until_completed(1), NodeStatus::Success);
+        node.assert_blackboard_eq("nav_ran", serde_json::json!(true));
+        node.assert_blackboard_eq("arm_ran", serde_json::json!(true));
+
+        let paths: Vec<_> = node.uid_map().into_values().map(|e| e.full_path).collect();
+        assert_eq!(
+            paths,
+            ["Sequence", "Sequence/nav.MoveTo", "Sequence/arm.MoveTo"]
+        );
+
+        let err = create_bt_tree_from_xml_str(&factory, &xml.replace("nav.MoveTo", "MoveTo"))
+            .err()
+            .unwrap();
+        assert!(matches!(err, BtError::UnknownNodes(_)), "{err}");
+
+        let model = factory.export_tree_nodes_model();
+        assert!(model.contains(r#"<Action ID="arm.MoveTo"/>"#), "{model}");
+
+        let mut node = crate::bt!(&factory, Sequence {
+            nav.MoveTo,
+            arm.MoveTo
+            SetBlackboard(value = 1, output_key = "done")
+        })
+        .unwrap()
+        .unwrap();
+        assert_eq!(node.tick_until_completed(1), NodeStatus::Success);
+        node.assert_blackboard_eq("done", serde_json::json!("1"));
+
+        let paths: Vec<_> = node.uid_map().into_values().map(|e| e.full_path).collect();
+        assert_eq!(
+            paths,
+            [
+                "Sequence",
+                "Sequence/nav.MoveTo",
+                "Sequence/arm.MoveTo",
+                "Sequence/SetBlackboard"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_same_name_remap() {
+        use crate::testing::TreeTestExt;
+
+        let xml = r#"
+        <root BTCPP_format="4" main_tree_to_execute="main">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <SetBlackboard value="left_arm" output_key="arm"/>
+                    <SubTree ID="sub" arm="{=}"/>
+                </Sequence>
+            </BehaviorTree>
+            <BehaviorTree ID="sub">
+                <SetBlackboard value="{arm}" output_key="copied"/>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+        assert_eq!(node.tick_until_completed(1), NodeStatus::Success);
+
+        let NodeWrapper::Composite(cp) = &node.node_wrapper else {
+            panic!("root should be a composite");
+        };
+        let NodeWrapper::Decorator(subtree) = &cp.child_nodes[1].node_wrapper else {
+            panic!("second child should be the SubTree");
+        };
+        assert_eq!(
+            subtree
+                .inner_node
+                .data_proxy_ref()
+                .blackboard()
+                .port_remappings()
+                .get("arm"),
+            Some(&"arm".to_string())
+        );
+    }
+
+    #[test]
+    fn test_private_subtree() {
+        use crate::testing::TreeTestExt;
+
+        let xml = r#"
+        <root BTCPP_format="4" main_tree_to_execute="main">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <SetBlackboard value="left_arm" output_key="arm"/>
+                    <SetBlackboard value="head" output_key="sensor"/>
+                    <SubTree ID="sub" _private="true" remapped_arm="{arm}"/>
+                </Sequence>
+            </BehaviorTree>
+            <BehaviorTree ID="sub">
+                <Sequence>
+                    <SetBlackboard value="{remapped_arm}" output_key="copied_arm"/>
+                    <SetBlackboard value="{sensor}" output_key="copied_sensor"/>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+
+        // the fallthrough read of `sensor` is blocked
+        assert_eq!(node.tick_until_completed(1), NodeStatus::Failure);
+    }
+
+    #[test]
+    fn test_subtree_autoremap() {
+        use crate::testing::TreeTestExt;
+
+        let xml = r#"
+        <root BTCPP_format="4" main_tree_to_execute="main">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <SetBlackboard value="head" output_key="sensor"/>
+                    <SubTree ID="sub" _private="true" _autoremap="true"/>
+                </Sequence>
+            </BehaviorTree>
+            <BehaviorTree ID="sub">
+                <SetBlackboard value="{sensor}" output_key="copied_sensor"/>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(node.tick_until_completed(1), NodeStatus::Success);
+        // the subtree write lands in the main tree blackboard
+        node.assert_blackboard_eq("copied_sensor", serde_json::json!("head"));
+        assert_eq!(node.data_proxy_ref().blackboard().len(), 2);
+    }
+
+    #[test]
+    fn test_subtree_output_port() {
+        use crate::testing::TreeTestExt;
+
+        let xml = r#"
+        <root BTCPP_format="4" main_tree_to_execute="main">
+            <BehaviorTree ID="main">
+                <SubTree ID="sub" result="{my_result}"/>
+            </BehaviorTree>
+            <BehaviorTree ID="sub">
+                <SetBlackboard value="done" output_key="{result}"/>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(node.tick_until_completed(1), NodeStatus::Success);
+        // written through the remapping, not into the subtree scope
+        node.assert_blackboard_eq("my_result", serde_json::json!("done"));
+        assert_eq!(node.data_proxy_ref().blackboard().keys(), ["my_result"]);
+    }
+
+    #[test]
+    fn test_blackboard_snapshot() {
+        use crate::node::BlackboardSnapshot;
+        use crate::testing::TreeTestExt;
+
+        let xml = r#"
+        <root BTCPP_format="4" main_tree_to_execute="main">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <SetBlackboard value="3" output_key="waypoint" type="int"/>
+                    <SubTree ID="sub" _private="true"/>
+                </Sequence>
+            </BehaviorTree>
+            <BehaviorTree ID="sub">
+                <SetBlackboard value="docked" output_key="state"/>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+        assert_eq!(node.tick_until_completed(1), NodeStatus::Success);
+
+        let saved = node.blackboard_snapshot().to_json_string().unwrap();
+        let snapshot = BlackboardSnapshot::from_json_str(&saved).unwrap();
+        assert_eq!(snapshot.scopes.len(), 2);
+        assert_eq!(
+            snapshot.scopes["Sequence/SubTree/SetBlackboard"]["state"],
+            serde_json::json!("docked")
+        );
+
+        // a restarted process builds the tree again before restoring
+        let restored = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+        restored.restore_blackboard(&snapshot).unwrap();
+        restored.assert_blackboard_eq("waypoint", serde_json::json!(3));
+        assert_eq!(restored.blackboard_snapshot(), snapshot);
+
+        let mut unknown = snapshot.clone();
+        unknown
+            .scopes
+            .insert("Sequence/Missing".to_string(), HashMap::new());
+        assert!(restored.restore_blackboard(&unknown).is_err());
+    }
+
+    #[test]
+    fn test_inject_blackboard() {
+        use crate::testing::TreeTestExt;
+
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <SetBlackboard value="{mission}" output_key="current"/>
+            </BehaviorTree>
+        </root>"#;
+
+        let bb = Arc::new(RwLock::new(Blackboard::default()));
+        bb.write()
+            .set("mission".to_string(), serde_json::json!("survey"));
+
+        let mut node =
+            create_bt_tree_from_xml_str_with_blackboard(&test_factory(), xml, bb.clone())
+                .unwrap()
+                .unwrap();
+
+        assert_eq!(node.tick(), NodeStatus::Success);
+        node.assert_blackboard_eq("current", serde_json::json!("survey"));
+        assert_eq!(bb.read().len(), 2);
+    }
+
+    #[test]
+    fn test_global_blackboard() {
+        use crate::testing::TreeTestExt;
+
+        let writer_xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <SetBlackboard value="docked" output_key="{@robot_state}"/>
+            </BehaviorTree>
+        </root>"#;
+        let reader_xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <SetBlackboard value="{robot_state}" output_key="seen_state"/>
+            </BehaviorTree>
+        </root>"#;
+
+        let factory = test_factory();
+        let build = |xml| {
+            let bb = Arc::new(RwLock::new(Blackboard::new_global_child()));
+            create_bt_tree_from_xml_str_with_blackboard(&factory, xml, bb)
+                .unwrap()
+                .unwrap()
+        };
+        let mut writer = build(writer_xml);
+        let mut reader = build(reader_xml);
+
+        assert_eq!(writer.tick(), NodeStatus::Success);
+        assert_eq!(reader.tick(), NodeStatus::Success);
+        reader.assert_blackboard_eq("seen_state", serde_json::json!("docked"));
+        assert_eq!(
+            Blackboard::global().read().get_entry("robot_state"),
+            Some(serde_json::json!("docked"))
+        );
+
+        // events and errors stay per tree
+        assert!(!writer
+            .data_proxy_ref()
+            .events()
+            .same_channel(&reader.data_proxy_ref().events()));
+        writer.data_proxy_ref().report_error("writer failed");
+        assert!(writer.data_proxy_ref().last_error().is_some());
+        assert!(reader.data_proxy_ref().last_error().is_none());
+    }
+
+    #[test]
+    fn test_root_key_prefix() {
+        use crate::testing::TreeTestExt;
+
+        let xml = r#"
+        <root BTCPP_format="4" main_tree_to_execute="main">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <SetBlackboard value="fast" output_key="mode"/>
+                    <SubTree ID="outer" _private="true"/>
+                </Sequence>
+            </BehaviorTree>
+            <BehaviorTree ID="outer">
+                <SubTree ID="inner" _private="true"/>
+            </BehaviorTree>
+            <BehaviorTree ID="inner">
+                <Sequence>
+                    <SetBlackboard value="{@mode}" output_key="{@seen_mode}"/>
+                    <SetBlackboard value="{mode}" output_key="local_mode"/>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+
+        // `{mode}` doesn't resolve through the private scopes
+        assert_eq!(node.tick_until_completed(1), NodeStatus::Failure);
+        node.assert_blackboard_eq("seen_mode", serde_json::json!("fast"));
+    }
+
+    #[test]
+    fn test_audit() {
+        use crate::node::audit::{AccessKind, ResolvedScope};
+
+        let xml = r#"
+        <root BTCPP_format="4" main_tree_to_execute="main">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <SetBlackboard value="left_arm" output_key="arm"/>
+                    <SubTree ID="sub" inner_arm="{arm}"/>
+                </Sequence>
+            </BehaviorTree>
+            <BehaviorTree ID="sub">
+                <SetBlackboard value="{inner_arm}" output_key="copied"/>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+        let audit = node.data_proxy_ref().blackboard().enable_audit(16);
+
+        assert_eq!(node.tick(), NodeStatus::Success);
+
+        let records = audit.dump();
+        assert_eq!(records[0].access, AccessKind::Set);
+        assert_eq!(records[0].uid, Some(1));
+
+        let subtree_get = records
+            .iter()
+            .find(|r| r.key == "inner_arm")
+            .expect("subtree read should be recorded");
+        assert_eq!(
+            subtree_get.scope,
+            ResolvedScope::Parent {
+                depth: 1,
+                key: "arm".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_copy_on_write_parallel() {
+        use crate::testing::TreeTestExt;
+
+        let run = |node: &mut TreeNodeWrapper| {
+            assert_eq!(node.tick(), NodeStatus::Running);
+            std::thread::sleep(Duration::from_millis(5));
+            node.tick()
+        };
+
+        let xml = |policy: &str| {
+            format!(
+                r#"
+                <root BTCPP_format="4">
+                    <BehaviorTree ID="main">
+                        <Parallel copy_on_write="true" conflict_policy="{policy}">
+                            <Sequence>
+                                <SetBlackboard value="first" output_key="target"/>
+                                <Sleep msec="1"/>
+                            </Sequence>
+                            <SetBlackboard value="second" output_key="target"/>
+                        </Parallel>
+                    </BehaviorTree>
+                </root>"#
+            )
+        };
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), &xml("first_wins"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(run(&mut node), NodeStatus::Success);
+        node.assert_blackboard_eq("target", serde_json::json!("second"));
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), &xml("last_wins"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(run(&mut node), NodeStatus::Success);
+        node.assert_blackboard_eq("target", serde_json::json!("first"));
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), &xml("fail"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(run(&mut node), NodeStatus::Failure);
+    }
+
+    #[test]
+    fn test_sequence_skip_failure() {
+        use crate::testing::TreeTestExt;
+
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Sequence on_child_failure="skip">
+                    <SetBlackboard value="{missing}" output_key="copied"/>
+                    <SetBlackboard value="done" output_key="checked"/>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(node.tick_until_completed(1), NodeStatus::Failure);
+        node.assert_blackboard_eq("checked", serde_json::json!("done"));
+    }
+
+    #[test]
+    fn test_parallel_child_results() {
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Parallel success_count="1" child_results="{results}">
+                    <Sleep msec="1000"/>
+                    <SetBlackboard value="done" output_key="checked"/>
+                </Parallel>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+        assert_eq!(node.tick(), NodeStatus::Success);
+
+        let results = node.data_proxy_ref().blackboard().get_entry("results");
+        assert_eq!(
+            results,
+            Some(serde_json::json!([
+                {"uid": 1, "path": "Parallel/Sleep", "status": "Halted"},
+                {"uid": 2, "path": "Parallel/SetBlackboard", "status": "Success"},
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parallel_child_results_remapped() {
+        use crate::testing::TreeTestExt;
+
+        let xml = r#"
+        <root BTCPP_format="4" main_tree_to_execute="main">
+            <BehaviorTree ID="main">
+                <SubTree ID="branches" results="{outcome}"/>
+            </BehaviorTree>
+            <BehaviorTree ID="branches">
+                <Parallel child_results="{results}">
+                    <SetBlackboard value="done" output_key="checked"/>
+                </Parallel>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(node.tick(), NodeStatus::Success);
+        node.assert_blackboard_eq(
+            "outcome",
+            serde_json::json!([
+                {"uid": 2, "path": "SubTree/Parallel/SetBlackboard", "status": "Success"},
+            ]),
+        );
+    }
+
+    #[test]
+    fn test_include() {
+        let mut include_dir = assets_dir();
+        include_dir.push("include");
+
+        let mut node = create_bt_tree_from_xml_file(&test_factory(), include_dir.join("main.xml"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(node.tick(), NodeStatus::Success);
+
+        let mut node_count = 0;
+        node.apply_recursive_visitor(&mut |_node, _layer| node_count += 1);
+        // Sequence, PrintBody, dock SubTree, its Sequence and PrintBody, stow_arm SubTree and PrintArm
+        assert_eq!(node_count, 7);
+
+        let err = create_bt_tree_from_xml_file(&test_factory(), include_dir.join("cycle.xml"))
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("include cycle"), "{err}");
+    }
+
+    #[test]
+    fn test_include_error_location() {
+        let include_dir = assets_dir().join("include");
+
+        let Err(BtError::Parse {
+            line, column, file, ..
+        }) = create_bt_tree_from_xml_file(&test_factory(), include_dir.join("broken.xml"))
+        else {
+            panic!("error not located");
+        };
+
+        // in the included file, not in the expanded document
+        assert_eq!(
+            file,
+            Some(
+                include_dir
+                    .join("trees/broken_arm.xml")
+                    .canonicalize()
+                    .unwrap()
+            )
+        );
+        assert_eq!((line, column), (6, 13));
+    }
+
+    #[test]
+    fn test_unknown_nodes() {
+        let xml = r#"
+        <root BTCPP_format="4" main_tree_to_execute="main">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <Sleep msec="1"/>
+                    <MoveBase goal="{goal}"/>
+                    <SubTree ID="sub"/>
+                </Sequence>
+            </BehaviorTree>
+            <BehaviorTree ID="sub">
+                <Fallback>
+                    <DetectObstacle/>
+                </Fallback>
+            </BehaviorTree>
+        </root>"#;
+
+        let Err(BtError::UnknownNodes(nodes)) = create_bt_tree_from_xml_str(&test_factory(), xml)
+        else {
+            panic!("unknown nodes not reported");
+        };
+
+        assert_eq!(
+            nodes,
+            vec![
+                UnknownNode {
+                    tree_id: "main".to_string(),
+                    path: "Sequence/MoveBase".to_string(),
+                    type_name: "MoveBase".to_string(),
+                    line: 6,
+                    file: None,
+                },
+                UnknownNode {
+                    tree_id: "sub".to_string(),
+                    path: "Fallback/DetectObstacle".to_string(),
+                    type_name: "DetectObstacle".to_string(),
+                    line: 12,
+                    file: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_create_from_reader() {
+        use crate::testing::TreeTestExt;
+
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <SetBlackboard value="1" output_key="a"/>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_reader(&test_factory(), xml.as_bytes())
+            .unwrap()
+            .unwrap();
+        assert_eq!(node.tick(), NodeStatus::Success);
+        node.assert_blackboard_eq("a", serde_json::json!("1"));
+
+        let err = create_bt_tree_from_reader(&test_factory(), [0xff, 0xfe].as_slice())
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("read xml meet failure"), "{err}");
+    }
+
+    #[cfg(feature = "rt-tokio")]
+    #[tokio::test]
+    async fn test_create_from_async_reader() {
+        let (mut tx, rx) = tokio::io::duplex(16);
+
+        tokio::spawn(async move {
+            use tokio::io::AsyncWriteExt;
+
+            tx.write_all(XML.as_bytes()).await.unwrap();
+        });
+
+        let mut node = create_bt_tree_from_async_reader(&test_factory(), rx)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(node.tick(), NodeStatus::Success);
+    }
+
+    #[test]
+    fn test_tree_document() {
+        use crate::testing::TreeTestExt;
+
+        let xml = r#"
+        <root BTCPP_format="4" main_tree_to_execute="dock">
+            <BehaviorTree ID="patrol">
+                <Sequence>
+                    <SetBlackboard value="patrol" output_key="mode"/>
+                    <SubTree ID="report" _autoremap="true"/>
+                </Sequence>
+            </BehaviorTree>
+            <BehaviorTree ID="dock">
+                <SetBlackboard value="dock" output_key="mode"/>
+            </BehaviorTree>
+            <BehaviorTree ID="report">
+                <SetBlackboard value="{mode}" output_key="reported"/>
+            </BehaviorTree>
+        </root>"#;
+
+        let doc = TreeDocument::from_xml_str(xml).unwrap();
+        assert_eq!(doc.main_tree_id(), "dock");
+        assert_eq!(
+            doc.tree_ids().collect::<Vec<_>>(),
+            vec!["patrol", "dock", "report"]
+        );
+
+        let factory = test_factory();
+        let mut patrol = doc.create_tree(&factory, "patrol").unwrap().unwrap();
+        assert_eq!(patrol.tick(), NodeStatus::Success);
+        patrol.assert_blackboard_eq("reported", serde_json::json!("patrol"));
+
+        // every build is a fresh tree
+        let mut dock = doc.create_tree(&factory, "dock").unwrap().unwrap();
+        assert_eq!(dock.tick(), NodeStatus::Success);
+        dock.assert_blackboard_eq("mode", serde_json::json!("dock"));
+        let mut dock = doc.create_tree(&factory, "dock").unwrap().unwrap();
+        assert_eq!(dock.tick(), NodeStatus::Success);
+
+        let err = doc.create_tree(&factory, "missing").err().unwrap();
+        assert!(
+            err.to_string().contains("can't find tree: missing"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_tree_document_unknown_nodes() {
+        let xml = r#"
+        <root BTCPP_format="4" main_tree_to_execute="dock">
+            <BehaviorTree ID="dock">
+                <SubTree ID="report"/>
+            </BehaviorTree>
+            <BehaviorTree ID="report">
+                <SetBlackboard value="dock" output_key="reported"/>
+            </BehaviorTree>
+            <BehaviorTree ID="draft">
+                <Sequence>
+                    <SubTree ID="report"/>
+                    <MoveBase/>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        let doc = TreeDocument::from_xml_str(xml).unwrap();
+        let mut factory = test_factory();
+        factory
+            .register_tree_from_text(
+                r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="unused">
+                <DetectObstacle/>
+            </BehaviorTree>
+        </root>"#,
+            )
+            .unwrap();
+
+        // trees the build doesn't reach aren't checked
+        let mut dock = doc.create_tree(&factory, "dock").unwrap().unwrap();
+        assert_eq!(dock.tick(), NodeStatus::Success);
+
+        let Err(BtError::UnknownNodes(nodes)) = doc.create_tree(&factory, "draft") else {
+            panic!("unknown nodes not reported");
+        };
+        assert_eq!(
+            nodes
+                .iter()
+                .map(|node| node.path.as_str())
+                .collect::<Vec<_>>(),
+            ["Sequence/MoveBase"]
+        );
+
+        let options = ParseOptions {
+            strict_unknown_nodes: false,
+            ..Default::default()
+        };
+        let mut draft = doc
+            .create_tree_with_blackboard(&factory, "draft", Arc::default(), options)
+            .unwrap()
+            .unwrap();
+        assert_eq!(draft.tick(), NodeStatus::Success);
+    }
+
+    #[test]
+    fn test_factory_tree_registry() {
+        use crate::testing::TreeTestExt;
+
+        let mut factory = test_factory();
+        factory
+            .register_tree_from_text(
+                r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="report">
+                <SetBlackboard value="{mode}" output_key="reported"/>
+            </BehaviorTree>
+            <BehaviorTree ID="broken">
+                <Sequence>
+                    <SetBlackboard value="1" output_key="a" speed="1"/>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#,
+            )
+            .unwrap();
+        factory
+            .register_tree_from_text(
+                r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="patrol">
+                <Sequence>
+                    <SetBlackboard value="patrol" output_key="mode"/>
+                    <SubTree ID="report" _autoremap="true"/>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#,
+            )
+            .unwrap();
+        assert_eq!(
+            factory.registered_tree_ids(),
+            vec!["broken", "patrol", "report"]
+        );
+
+        let mut node = factory
+            .create_tree("patrol", Arc::default())
+            .unwrap()
+            .unwrap();
+        assert_eq!(node.tick(), NodeStatus::Success);
+        node.assert_blackboard_eq("reported", serde_json::json!("patrol"));
+
+        // documents built by the factory see the registered trees too
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <SubTree ID="broken"/>
+            </BehaviorTree>
+        </root>"#;
+        let Err(BtError::Parse { line, path, .. }) = create_bt_tree_from_xml_str(&factory, xml)
+        else {
+            panic!("error not located");
+        };
+        // located in the registering document
+        assert_eq!((line, path.as_str()), (8, "SubTree/Sequence/SetBlackboard"));
+
+        let err = factory
+            .create_tree("missing", Arc::default())
+            .err()
+            .unwrap();
+        assert!(
+            err.to_string().contains("can't find tree: missing"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <SetBlackboard value="1" output_key="a" speed="1"/>
+                    <MoveBase/>
+                    <Fallback/>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        let factory = test_factory();
+        let lenient = ParseOptions {
+            strict_unknown_nodes: false,
+            strict_unknown_ports: false,
+            allow_empty_composites: true,
+        };
+        let node = create_bt_tree_from_xml_str_with_options(&factory, xml, lenient)
+            .unwrap()
+            .unwrap();
+        let NodeWrapper::Composite(cp) = &node.node_wrapper else {
+            panic!("root isn't a composite");
+        };
+        assert_eq!(cp.child_nodes.len(), 2);
+
+        assert!(matches!(
+            create_bt_tree_from_xml_str_with_options(
+                &factory,
+                xml,
+                ParseOptions {
+                    strict_unknown_nodes: true,
+                    ..lenient
+                }
+            ),
+            Err(BtError::UnknownNodes(_))
+        ));
+
+        let err = create_bt_tree_from_xml_str_with_options(
+            &factory,
+            xml,
+            ParseOptions {
+                strict_unknown_ports: true,
+                ..lenient
+            },
+        )
+        .err()
+        .unwrap();
+        assert!(err.to_string().contains("unknown port"), "{err}");
+
+        let err = create_bt_tree_from_xml_str_with_options(
+            &factory,
+            xml,
+            ParseOptions {
+                allow_empty_composites: false,
+                ..lenient
+            },
+        )
+        .err()
+        .unwrap();
+        assert!(err.to_string().contains("empty composite"), "{err}");
+    }
+
+    #[test]
+    fn test_error_location() {
+        let xml = r#"
+        <root BTCPP_format="4" main_tree_to_execute="main">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <SubTree ID="sub"/>
+                </Sequence>
+            </BehaviorTree>
+            <BehaviorTree ID="sub">
+                <Sequence>
+                    <PrintBody body="hello"/>
+                    <Sleep mesc="10"/>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        let Err(BtError::Parse {
+            line,
+            column,
+            path,
+            source,
+            ..
+        }) = create_bt_tree_from_xml_str(&test_factory(), xml)
+        else {
+            panic!("error location not reported");
+        };
+
+        assert_eq!((line, column), (11, 21));
+        assert_eq!(path, "Sequence/SubTree/Sequence/Sleep");
+        assert!(source.to_string().contains("key= mesc"), "{source}");
+    }
+
+    #[test]
+    fn test_build_limits() {
+        let xml = r#"
+        <root BTCPP_format="4" main_tree_to_execute="main">
+            <BehaviorTree ID="main">
+                <SubTree ID="loop"/>
+            </BehaviorTree>
+            <BehaviorTree ID="loop">
+                <SubTree ID="loop"/>
+            </BehaviorTree>
+        </root>"#;
+
+        let res = create_bt_tree_from_xml_str(&test_factory(), xml);
+        assert!(matches!(
+            res,
+            Err(BtError::LimitExceeded { limit: "depth", .. })
+        ));
+
+        let mut factory = test_factory();
+        factory.set_build_limits(crate::factory::BuildLimits {
+            max_depth: 16,
+            max_nodes: 4,
+        });
+        let res = create_bt_tree_from_xml_str(&factory, XML);
+        assert!(matches!(
+            res,
+            Err(BtError::LimitExceeded {
+                limit: "node count",
+                max: 4,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_uid_map() {
+        let node = create_bt_tree_from_xml_str(&test_factory(), XML)
+            .unwrap()
+            .unwrap();
+
+        let uid_map = node.uid_map();
+        assert_eq!(uid_map.len(), 18);
+        assert_eq!(uid_map[&0].full_path, "Sequence");
+        assert_eq!(uid_map[&1].type_name, "PrintBody");
+        assert_eq!(uid_map[&1].node_type, crate::NodeType::Action);
+
+        let json = serde_json::to_value(&uid_map).unwrap();
+        assert_eq!(json["1"]["full_path"], "Sequence/PrintBody");
+    }
+
+    #[test]
+    fn test_display() {
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <SetBlackboard value="42" output_key="the_answer"/>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+        node.tick();
+
+        let s = node.to_string();
+        let lines: Vec<_> = s.lines().collect();
+        assert_eq!(lines[0], "uid= 0 path= Sequence status= SUCCESS");
+        assert!(lines[1].starts_with("\tuid= 1 path= Sequence/SetBlackboard status= "));
+        assert!(lines[1].ends_with(" output_key= the_answer type= string value= 42"));
+    }
+
+    #[test]
+    fn test_tags() {
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Sequence _tags="motion">
+                    <SetBlackboard value="42" output_key="the_answer" _tags="nav, safety"/>
+                    <Inverter _tags="safety">
+                        <SetBlackboard value="1" output_key="other"/>
+                    </Inverter>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(node.tagged_uids("motion"), vec![0]);
+        assert_eq!(node.tagged_uids("safety"), vec![1, 2]);
+        assert_eq!(node.uid_map()[&1].tags, vec!["nav", "safety"]);
+
+        let observers = node.subscribe_tagged("safety");
+        assert_eq!(observers.keys().copied().collect::<Vec<_>>(), vec![1, 2]);
+
+        node.tick();
+        assert!(observers.values().all(|rx| rx.has_changed().unwrap()));
+
+        node.halt_tagged("motion");
+        assert_eq!(node.status(), NodeStatus::Idle);
+    }
+
+    #[test]
+    fn test_transition_hook() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <SetBlackboard value="42" output_key="the_answer" _tags="confirm"/>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+
+        let acked = Arc::new(AtomicBool::new(false));
+        let acked_c = acked.clone();
+        node.add_transition_hook(Arc::new(move |data_proxy, status| {
+            if data_proxy.has_tag("confirm")
+                && status == NodeStatus::Success
+                && !acked_c.load(Ordering::SeqCst)
+            {
+                NodeStatus::Running
+            } else {
+                status
+            }
+        }));
+
+        assert_eq!(node.tick(), NodeStatus::Running);
+        assert_eq!(node.tick(), NodeStatus::Running);
+
+        acked.store(true, Ordering::SeqCst);
+        assert_eq!(node.tick(), NodeStatus::Success);
+    }
+
+    #[cfg(feature = "rt-tokio")]
+    #[tokio::test]
+    async fn test_spawn_status_observer() {
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <SetBlackboard value="42" output_key="the_answer"/>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        node.spawn_status_observer(&crate::runtime::TokioRuntime, move |notif| {
+            let _ = tx.send(notif);
+        });
+
+        node.tick();
+
+        loop {
+            let notif = rx.recv().await.unwrap();
+            if notif.uid == 0 {
+                assert_eq!(notif.new_status, NodeStatus::Success);
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn test_interpolation() {
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <SetBlackboard value="${ROBOT_NAME}-$${x}" output_key="name"/>
+                    <SetBlackboard value="${CORNERSTONE_TEST_HOME}" output_key="home"/>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut factory = test_factory();
+        factory.set_config_var("ROBOT_NAME", "r2");
+        factory.set_config_var("CORNERSTONE_TEST_HOME", "dock");
+
+        let mut node = create_bt_tree_from_xml_str(&factory, xml).unwrap().unwrap();
+        assert_eq!(node.tick(), NodeStatus::Success);
+
+        let bb = node.data_proxy_ref().blackboard();
+        assert_eq!(bb.get_entry("name"), Some(serde_json::json!("r2-${x}")));
+        assert_eq!(bb.get_entry("home"), Some(serde_json::json!("dock")));
+        drop(bb);
+
+        let Err(err) = create_bt_tree_from_xml_str(&test_factory(), xml) else {
+            panic!("undefined variable should fail the build");
+        };
+        assert!(err.to_string().contains("name= ROBOT_NAME"));
+
+        let report = validate_bt_tree_from_xml_str(&test_factory(), xml).unwrap();
+        let undefined = report
+            .issues
+            .iter()
+            .filter(|issue| matches!(issue, ValidationIssue::UndefinedVariable { .. }))
+            .count();
+        assert_eq!(undefined, 2);
+    }
+
+    #[cfg(feature = "rt-tokio")]
+    #[tokio::test]
+    async fn test_tick_loop_shutdown() {
+        use crate::{runner::TickLoop, testing::TreeTestExt};
+
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <Sleep msec="10000"/>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+
+        let tick_loop =
+            TickLoop::new(Duration::from_millis(1)).drain_timeout(Duration::from_millis(5));
+        let status = tick_loop
+            .run(
+                &crate::runtime::TokioRuntime,
+                &mut node,
+                tokio::time::sleep(Duration::from_millis(5)),
+            )
+            .await;
+
+        assert_eq!(status, NodeStatus::Running);
+        assert_eq!(node.status(), NodeStatus::Idle);
+        assert_eq!(
+            node.find_node("Sequence/Sleep").unwrap().status(),
+            NodeStatus::Idle
+        );
+    }
+
+    #[test]
+    fn test_repeat_idle_child() {
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Repeat num_cycles="1">
+                    <Lazy/>
+                </Repeat>
+            </BehaviorTree>
+        </root>"#;
+
+        struct Lazy;
+
+        impl ActionNodeImpl for Lazy {
+            fn tick_status(&mut self, _data_proxy: &mut DataProxy) -> NodeStatus {
+                NodeStatus::Idle
+            }
+        }
+
+        let mut factory = test_factory();
+        factory.register_action_node_type(
+            "^Lazy$".try_into().unwrap(),
+            boxify_action(|_, _| Ok(Lazy)),
+        );
+
+        let mut node = create_bt_tree_from_xml_str(&factory, xml).unwrap().unwrap();
+        assert_eq!(node.tick(), NodeStatus::Failure);
+    }
+
+    #[test]
+    fn test_retries_attribute() {
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <Flaky _retries="2" _backoff_msec="1"/>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        struct Flaky(usize);
+
+        impl ActionNodeImpl for Flaky {
+            fn tick_status(&mut self, _data_proxy: &mut DataProxy) -> NodeStatus {
+                self.0 += 1;
+
+                if self.0 < 3 {
+                    NodeStatus::Failure
+                } else {
+                    NodeStatus::Success
+                }
+            }
+        }
+
+        let mut factory = test_factory();
+        factory.register_action_node_type(
+            "^Flaky$".try_into().unwrap(),
+            boxify_action(|_, _| Ok(Flaky(0))),
+        );
+
+        let mut node = create_bt_tree_from_xml_str(&factory, xml).unwrap().unwrap();
+        assert_eq!(node.uid_map()[&1].node_type, crate::NodeType::Decorator);
+
+        assert_eq!(node.tick(), NodeStatus::Running);
+
+        let mut status = NodeStatus::Running;
+        for _ in 0..100 {
+            std::thread::sleep(Duration::from_millis(2));
+            status = node.tick();
+            if status.is_completed() {
+                break;
+            }
+        }
+        assert_eq!(status, NodeStatus::Success);
+    }
+
+    #[test]
+    fn test_retry_exponential_backoff() {
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <RetryUntilSuccessful num_attempts="3" delay_msec="10" backoff_factor="2" max_delay_msec="25">
+                    <Flaky/>
+                </RetryUntilSuccessful>
+            </BehaviorTree>
+        </root>"#;
+
+        struct Flaky(usize);
+
+        impl ActionNodeImpl for Flaky {
+            fn tick_status(&mut self, _data_proxy: &mut DataProxy) -> NodeStatus {
+                self.0 += 1;
+
+                (self.0 > 3).into()
+            }
+        }
+
+        let mut factory = test_factory();
+        factory.register_action_node_type(
+            "^Flaky$".try_into().unwrap(),
+            boxify_action(|_, _| Ok(Flaky(0))),
+        );
+
+        let mut node = create_bt_tree_from_xml_str(&factory, xml).unwrap().unwrap();
+
+        let start = std::time::Instant::now();
+        let mut status = node.tick();
+        while status == NodeStatus::Running {
+            std::thread::sleep(Duration::from_millis(1));
+            status = node.tick();
+        }
+
+        assert_eq!(status, NodeStatus::Success);
+        // waits 10, 20 and then 25 instead of 40
+        assert!(start.elapsed() >= Duration::from_millis(55));
+    }
+
+    #[test]
+    fn test_retry_negative_attempts() {
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <RetryUntilSuccessful num_attempts="-1">
+                    <SetBlackboard value="1" output_key="ran"/>
+                </RetryUntilSuccessful>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(node.tick(), NodeStatus::Failure);
+        assert_eq!(node.data_proxy_ref().blackboard().get_entry("ran"), None);
+    }
+
+    #[test]
+    fn test_active_child() {
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Sequence active_child="{seq_child}">
+                    <Fallback active_child="{fallback_child}">
+                        <Inverter>
+                            <SetBlackboard value="1" output_key="a"/>
+                        </Inverter>
+                        <SetBlackboard value="2" output_key="b"/>
+                    </Fallback>
+                    <Sleep msec="10000"/>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+        assert_eq!(node.tick(), NodeStatus::Running);
+
+        let bb = node.data_proxy_ref().blackboard();
+        assert_eq!(
+            bb.get_entry("seq_child"),
+            Some(serde_json::json!({"index": 1, "path": "Sequence/Sleep"}))
+        );
+        assert_eq!(
+            bb.get_entry("fallback_child"),
+            Some(serde_json::json!({"index": 1, "path": "Sequence/Fallback/SetBlackboard"}))
+        );
+    }
+
+    #[test]
+    fn test_active_child_remapped() {
+        use crate::testing::TreeTestExt;
+
+        let xml = r#"
+        <root BTCPP_format="4" main_tree_to_execute="main">
+            <BehaviorTree ID="main">
+                <SubTree ID="pick" child="{picked}"/>
+            </BehaviorTree>
+            <BehaviorTree ID="pick">
+                <Fallback active_child="{child}">
+                    <Inverter>
+                        <SetBlackboard value="1" output_key="a"/>
+                    </Inverter>
+                    <SetBlackboard value="2" output_key="b"/>
+                </Fallback>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(node.tick(), NodeStatus::Success);
+        node.assert_blackboard_eq(
+            "picked",
+            serde_json::json!({"index": 1, "path": "SubTree/Fallback/SetBlackboard"}),
+        );
+    }
+
+    #[test]
+    fn test_status_handles() {
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <Sleep msec="10000"/>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+        let handles = node.status_handles();
+
+        let ticker = std::thread::spawn(move || {
+            node.tick();
+            node
+        });
+        let node = ticker.join().unwrap();
+
+        assert_eq!(handles[&0].get(), NodeStatus::Running);
+        assert_eq!(handles[&1].get(), NodeStatus::Running);
+
+        node.reset_status();
+        assert_eq!(handles[&0].get(), NodeStatus::Idle);
+    }
+
+    #[test]
+    fn test_parallel_build() {
+        let sequential = create_bt_tree_from_xml_str(&test_factory(), XML)
+            .unwrap()
+            .unwrap();
+
+        let mut factory = test_factory();
+        factory.set_parallel_build(true);
+        let mut parallel = create_bt_tree_from_xml_str(&factory, XML).unwrap().unwrap();
+
+        assert_eq!(parallel.uid_map(), sequential.uid_map());
+        assert_eq!(parallel.tick(), NodeStatus::Success);
+
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <PrintBody body="body" _retries="2"/>
+                    <Fallback _retries="2">
+                        <PrintArm arm="arm"/>
+                    </Fallback>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        let sequential = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+        let parallel = create_bt_tree_from_xml_str(&factory, xml).unwrap().unwrap();
+
+        let uid_map = sequential.uid_map();
+        assert_eq!(parallel.uid_map(), uid_map);
+        // the Retry wrappers come before the nodes they wrap
+        assert_eq!(uid_map[&1].node_type, crate::NodeType::Decorator);
+        assert_eq!(uid_map[&3].node_type, crate::NodeType::Decorator);
+    }
+
+    #[test]
+    fn test_factory_manifest() {
+        use crate::factory::FactoryManifest;
+
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Steps>
+                    <Remember output_key="a"/>
+                    <Remember output_key="b" value="override"/>
+                </Steps>
+            </BehaviorTree>
+        </root>"#;
+
+        let manifest = FactoryManifest::from_json_str(
+            r#"{
+                "aliases": { "Steps": "Sequence", "Remember": "SetBlackboard" },
+                "defaults": { "Remember": { "value": "default" } }
+            }"#,
+        )
+        .unwrap();
+
+        let mut factory = test_factory();
+        factory.apply_manifest(manifest).unwrap();
+
+        let mut node = create_bt_tree_from_xml_str(&factory, xml).unwrap().unwrap();
+        assert_eq!(node.tick(), NodeStatus::Success);
+
+        let bb = node.data_proxy_ref().blackboard();
+        assert_eq!(bb.get_entry("a"), Some(serde_json::json!("default")));
+        assert_eq!(bb.get_entry("b"), Some(serde_json::json!("override")));
+        drop(bb);
+
+        let unknown = FactoryManifest {
+            aliases: HashMap::from([("Foo".to_string(), "Bar".to_string())]),
+            ..Default::default()
+        };
+        assert!(factory.apply_manifest(unknown).is_err());
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_factory_manifest_toml() {
+        let manifest = crate::factory::FactoryManifest::from_toml_str(
+            r#"
+            [aliases]
+            Wait = "Sleep"
+
+            [defaults.Wait]
+            msec = "100"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(manifest.aliases["Wait"], "Sleep");
+        assert_eq!(manifest.defaults["Wait"]["msec"], "100");
+    }
+
+    #[test]
+    fn test_sequence_with_memory() {
+        use crate::testing::TreeTestExt;
+
+        struct FailOnce(bool);
+
+        impl ActionNodeImpl for FailOnce {
+            fn tick_status(&mut self, _data_proxy: &mut DataProxy) -> NodeStatus {
+                let failed = std::mem::replace(&mut self.0, true);
+                NodeStatus::from(failed)
+            }
+        }
+
+        let mut factory = test_factory();
+        factory.register_action_node_type(
+            "^FailOnce$".try_into().unwrap(),
+            boxify_action(|_, _| Ok(FailOnce(false))),
+        );
+
+        for (element, attrs, first_run) in [
+            ("SequenceWithMemory", "", "1"),
+            ("Sequence", r#"restart_on_failure="false""#, "1"),
+            ("Sequence", "", "2"),
+        ] {
+            let xml = format!(
+                r#"
+                <root BTCPP_format="4">
+                    <BehaviorTree ID="main">
+                        <{element} {attrs}>
+                            <SetBlackboard value="{{run}}" output_key="first"/>
+                            <FailOnce/>
+                        </{element}>
+                    </BehaviorTree>
+                </root>"#
+            );
+
+            let mut node = create_bt_tree_from_xml_str(&factory, &xml)
+                .unwrap()
+                .unwrap();
+            node.data_proxy_ref()
+                .blackboard()
+                .set("run".to_string(), "1".into());
+
+            assert_eq!(node.tick(), NodeStatus::Failure);
+            node.data_proxy_ref()
+                .blackboard()
+                .set("run".to_string(), "2".into());
+            assert_eq!(node.tick(), NodeStatus::Success);
+
+            node.assert_blackboard_eq("first", serde_json::json!(first_run));
+        }
+    }
+
+    #[test]
+    fn test_switch() {
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Switch variable="{mode}" case_1="dock" case_2="3" active_child="{branch}">
+                    <SetBlackboard value="docking" output_key="out"/>
+                    <SetBlackboard value="three" output_key="out"/>
+                    <SetBlackboard value="default" output_key="out"/>
+                </Switch>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+
+        for (mode, out, idx) in [
+            (serde_json::json!("dock"), "docking", 0),
+            (serde_json::json!(3), "three", 1),
+            (serde_json::json!("other"), "default", 2),
+        ] {
+            node.data_proxy_ref()
+                .blackboard()
+                .set("mode".to_string(), mode);
+            assert_eq!(node.tick(), NodeStatus::Success);
+
+            let bb = node.data_proxy_ref().blackboard();
+            assert_eq!(bb.get_entry("out"), Some(serde_json::json!(out)));
+            assert_eq!(bb.get_entry("branch").unwrap()["index"], idx);
+        }
+    }
+
+    #[test]
+    fn test_parallel_all() {
+        for (max_failures, expected) in [(0, NodeStatus::Failure), (1, NodeStatus::Success)] {
+            let xml = format!(
+                r#"
+                <root BTCPP_format="4">
+                    <BehaviorTree ID="main">
+                        <ParallelAll max_failures="{max_failures}">
+                            <ForceFailure>
+                                <SetBlackboard value="1" output_key="a"/>
+                            </ForceFailure>
+                            <Sleep msec="1"/>
+                            <SetBlackboard value="2" output_key="b"/>
+                        </ParallelAll>
+                    </BehaviorTree>
+                </root>"#
+            );
+
+            let mut node = create_bt_tree_from_xml_str(&test_factory(), &xml)
+                .unwrap()
+                .unwrap();
+
+            assert_eq!(node.tick(), NodeStatus::Running);
+            std::thread::sleep(Duration::from_millis(5));
+            assert_eq!(node.tick(), expected);
+
+            let bb = node.data_proxy_ref().blackboard();
+            assert_eq!(bb.get_entry("a"), Some(serde_json::json!("1")));
+            assert_eq!(bb.get_entry("b"), Some(serde_json::json!("2")));
+        }
+    }
+
+    #[test]
+    fn test_manual_selector() {
+        use crate::testing::TreeTestExt;
+
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <ManualSelector selected="{choice}">
+                    <Sleep msec="10000"/>
+                    <SetBlackboard value="manual" output_key="out"/>
+                </ManualSelector>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+        let bb = node.data_proxy_ref().shared_blackboard().clone();
+
+        assert_eq!(node.tick(), NodeStatus::Running);
+
+        bb.write().set("choice".to_string(), 0.into());
+        assert_eq!(node.tick(), NodeStatus::Running);
+        node.assert_node_status("ManualSelector/Sleep", NodeStatus::Running);
+
+        // an operator switches to the other branch from another thread
+        std::thread::spawn(move || bb.write().set("choice".to_string(), 1.into()))
+            .join()
+            .unwrap();
+        assert_eq!(node.tick(), NodeStatus::Success);
+        node.assert_node_status("ManualSelector/Sleep", NodeStatus::Idle);
+        node.assert_blackboard_eq("out", serde_json::json!("manual"));
+
+        // the command channel wins over the blackboard entry until cleared
+        let senders = node.selection_senders();
+        senders[&0].send(Some(0)).unwrap();
+        assert_eq!(node.tick(), NodeStatus::Running);
+        node.assert_node_status("ManualSelector/Sleep", NodeStatus::Running);
+
+        senders[&0].send(None).unwrap();
+        assert_eq!(node.tick(), NodeStatus::Success);
+        node.assert_node_status("ManualSelector/Sleep", NodeStatus::Idle);
+    }
+
+    #[test]
+    fn test_weighted_random_selector() {
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <WeightedRandomSelector weights="3;1;0" seed="42" active_child="{chosen}">
+                    <SetBlackboard value="a" output_key="out"/>
+                    <SetBlackboard value="b" output_key="out"/>
+                    <SetBlackboard value="c" output_key="out"/>
+                </WeightedRandomSelector>
+            </BehaviorTree>
+        </root>"#;
+
+        let run = || {
+            let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+                .unwrap()
+                .unwrap();
+
+            (0..50)
+                .map(|_| {
+                    assert_eq!(node.tick(), NodeStatus::Success);
+                    node.data_proxy_ref()
+                        .blackboard()
+                        .get_entry("chosen")
+                        .unwrap()["index"]
+                        .as_u64()
+                        .unwrap()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let choices = run();
+        assert_eq!(choices, run());
+        assert!(choices.contains(&0) && choices.contains(&1));
+        assert!(!choices.contains(&2));
+    }
+
+    #[test]
+    fn test_sequence_preempt() {
+        use crate::testing::TreeTestExt;
+
+        use crate::factory::boxify_condition;
+        use crate::node::condition::ConditionNodeImpl;
+
+        struct CheckBattery;
+
+        impl ConditionNodeImpl for CheckBattery {
+            fn tick_status(&mut self, data_proxy: &mut DataProxy) -> NodeStatus {
+                data_proxy.get_input::<bool>("ok").unwrap_or(false).into()
+            }
+        }
+
+        let mut factory = test_factory();
+        factory.register_condition_node_type(
+            "^CheckBattery$".try_into().unwrap(),
+            boxify_condition(|_, _| Ok(CheckBattery)),
+        );
+
+        // the action before the condition isn't re-ticked
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Sequence preempt="true">
+                    <Script code="ticks := ticks + 1"/>
+                    <CheckBattery ok="{battery_ok}"/>
+                    <Sleep msec="10000"/>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&factory, xml).unwrap().unwrap();
+        let bb = node.data_proxy_ref().shared_blackboard().clone();
+
+        bb.write().set("battery_ok".to_string(), true.into());
+        bb.write().set("ticks".to_string(), 0.into());
+        assert_eq!(node.tick(), NodeStatus::Running);
+        assert_eq!(node.tick(), NodeStatus::Running);
+        node.assert_node_status("Sequence/Sleep", NodeStatus::Running);
+        node.assert_blackboard_eq("ticks", serde_json::json!(1));
+
+        bb.write().set("battery_ok".to_string(), false.into());
+        assert_eq!(node.tick(), NodeStatus::Failure);
+        node.assert_node_status("Sequence/Sleep", NodeStatus::Idle);
+    }
+
+    #[test]
+    fn test_parallel_policy() {
+        for (attrs, expected) in [
+            (r#"success_policy="REQUIRE_ONE""#, NodeStatus::Success),
+            (
+                r#"success_policy="REQUIRE_ALL" failure_policy="REQUIRE_ONE""#,
+                NodeStatus::Failure,
+            ),
+            // numeric thresholds take precedence
+            (
+                r#"success_count="1" success_policy="REQUIRE_ALL""#,
+                NodeStatus::Success,
+            ),
+        ] {
+            let xml = format!(
+                r#"
+                <root BTCPP_format="4">
+                    <BehaviorTree ID="main">
+                        <Parallel {attrs}>
+                            <SetBlackboard value="1" output_key="a"/>
+                            <ForceFailure>
+                                <SetBlackboard value="2" output_key="b"/>
+                            </ForceFailure>
+                        </Parallel>
+                    </BehaviorTree>
+                </root>"#
+            );
+
+            let mut node = create_bt_tree_from_xml_str(&test_factory(), &xml)
+                .unwrap()
+                .unwrap();
+            assert_eq!(node.tick(), expected, "{attrs}");
+        }
+    }
+
+    #[cfg(feature = "rt-tokio")]
+    #[test]
+    fn test_concurrent_parallel() {
+        struct Blocking;
+
+        impl ActionNodeImpl for Blocking {
+            fn tick_status(&mut self, _data_proxy: &mut DataProxy) -> NodeStatus {
+                std::thread::sleep(Duration::from_millis(100));
+                NodeStatus::Success
+            }
+        }
+
+        let mut factory = test_factory();
+        factory.register_action_node_type(
+            "^Blocking$".try_into().unwrap(),
+            boxify_action(|_, _| Ok(Blocking)),
+        );
+
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Parallel concurrent="true">
+                    <Blocking/>
+                    <Blocking/>
+                    <Blocking/>
+                    <Blocking/>
+                </Parallel>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&factory, xml).unwrap().unwrap();
+
+        let start = std::time::Instant::now();
+        assert_eq!(node.tick(), NodeStatus::Success);
+        assert!(start.elapsed() < Duration::from_millis(350));
+    }
+
+    #[cfg(feature = "rt-tokio")]
+    #[tokio::test]
+    async fn test_concurrent_parallel_in_runtime() {
+        use std::thread::ThreadId;
+
+        struct Spin(Arc<parking_lot::Mutex<Vec<ThreadId>>>);
+
+        impl ActionNodeImpl for Spin {
+            fn tick_status(&mut self, _data_proxy: &mut DataProxy) -> NodeStatus {
+                self.0.lock().push(std::thread::current().id());
+                NodeStatus::Running
+            }
+        }
+
+        let threads = Arc::new(parking_lot::Mutex::new(vec![]));
+        let mut factory = test_factory();
+        factory.register_action_node_type("^Spin$".try_into().unwrap(), {
+            let threads = threads.clone();
+            boxify_action(move |_, _| Ok(Spin(threads.clone())))
+        });
+
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Parallel concurrent="true">
+                    <Spin/>
+                    <Spin/>
+                    <Spin/>
+                </Parallel>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&factory, xml).unwrap().unwrap();
+
+        // ticked on the blocking pool of the current-thread runtime
+        for _ in 0..3 {
+            assert_eq!(node.tick(), NodeStatus::Running);
+        }
+        let threads = threads.lock();
+        assert_eq!(threads.len(), 9);
+        assert!(!threads.contains(&std::thread::current().id()));
+    }
+
+    #[test]
+    fn test_parallel_max_concurrent() {
+        use crate::testing::TreeTestExt;
+
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Parallel max_concurrent="2">
+                    <Sleep msec="1"/>
+                    <Sleep msec="1"/>
+                    <SetBlackboard value="1" output_key="third"/>
+                </Parallel>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(node.tick(), NodeStatus::Running);
+        assert!(node
+            .data_proxy_ref()
+            .blackboard()
+            .get_entry("third")
+            .is_none());
+
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(node.tick(), NodeStatus::Running);
+        assert_eq!(node.tick(), NodeStatus::Success);
+        node.assert_blackboard_eq("third", serde_json::json!("1"));
+    }
+
+    #[test]
+    fn test_recovery_node() {
+        use crate::testing::TreeTestExt;
+
+        struct Navigate;
+
+        impl ActionNodeImpl for Navigate {
+            fn tick_status(&mut self, data_proxy: &mut DataProxy) -> NodeStatus {
+                data_proxy
+                    .get_input::<String>("cleared")
+                    .is_some_and(|v| v == "yes")
+                    .into()
+            }
+        }
+
+        let mut factory = test_factory();
+        factory.register_action_node_type(
+            "^Navigate$".try_into().unwrap(),
+            boxify_action(|_, _| Ok(Navigate)),
+        );
+
+        for (retries, expected) in [(0, NodeStatus::Failure), (2, NodeStatus::Success)] {
+            let xml = format!(
+                r#"
+                <root BTCPP_format="4">
+                    <BehaviorTree ID="main">
+                        <RecoveryNode number_of_retries="{retries}">
+                            <Navigate cleared="{{costmap}}"/>
+                            <SetBlackboard value="yes" output_key="costmap"/>
+                        </RecoveryNode>
+                    </BehaviorTree>
+                </root>"#
+            );
+
+            let mut node = create_bt_tree_from_xml_str(&factory, &xml)
+                .unwrap()
+                .unwrap();
+            assert_eq!(node.tick(), expected);
+
+            if retries == 0 {
+                assert!(node
+                    .data_proxy_ref()
+                    .blackboard()
+                    .get_entry("costmap")
+                    .is_none());
+            } else {
+                node.assert_blackboard_eq("costmap", serde_json::json!("yes"));
+            }
+        }
+    }
+
+    #[test]
+    fn test_precondition() {
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <SetBlackboard value="25" output_key="battery"/>
+                    <SetBlackboard value="auto" output_key="mode"/>
+                    <Precondition if="battery &gt; 20 &amp;&amp; mode == 'auto'">
+                        <SetBlackboard value="yes" output_key="charged"/>
+                    </Precondition>
+                    <Precondition if="battery * 2 &lt; 30" else="SUCCESS">
+                        <SetBlackboard value="yes" output_key="low"/>
+                    </Precondition>
+                    <Precondition if="{battery} &lt; 20">
+                        <SetBlackboard value="yes" output_key="unreachable"/>
+                    </Precondition>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        let factory = test_factory();
+        let mut node = create_bt_tree_from_xml_str(&factory, xml).unwrap().unwrap();
+
+        assert_eq!(node.tick(), NodeStatus::Failure);
+
+        let bb = node.data_proxy_ref().blackboard();
+        assert_eq!(bb.get_entry("charged"), Some(serde_json::json!("yes")));
+        assert!(bb.get_entry("low").is_none());
+        assert!(bb.get_entry("unreachable").is_none());
+    }
+
+    #[test]
+    fn test_node_conditions() {
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <SetBlackboard value="3" output_key="level"/>
+                    <SetBlackboard value="yes" output_key="skipped" _skipIf="level &gt; 2"/>
+                    <SetBlackboard value="yes" output_key="forced" _successIf="level == 3"/>
+                    <SetBlackboard value="yes" output_key="ran" _failureIf="level &lt; 3"/>
+                    <Sleep msec="100000" _while="level &lt; 5"/>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        let factory = test_factory();
+        let mut node = create_bt_tree_from_xml_str(&factory, xml).unwrap().unwrap();
+
+        assert_eq!(node.tick(), NodeStatus::Running);
+        {
+            let bb = node.data_proxy_ref().blackboard();
+            assert!(bb.get_entry("skipped").is_none());
+            assert!(bb.get_entry("forced").is_none());
+            assert_eq!(bb.get_entry("ran"), Some(serde_json::json!("yes")));
+        }
+        assert!(!node.to_string().contains("_skipIf"));
+
+        node.data_proxy_ref()
+            .blackboard()
+            .set("level".to_string(), serde_json::json!("7"));
+        assert_eq!(node.tick(), NodeStatus::Success);
+    }
+
+    #[test]
+    fn test_skipped_children() {
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <Fallback>
+                        <SetBlackboard value="1" output_key="a" _skipIf="true"/>
+                        <SetBlackboard value="2" output_key="b"/>
+                    </Fallback>
+                    <Parallel>
+                        <SetBlackboard value="3" output_key="c" _skipIf="true"/>
+                        <SetBlackboard value="4" output_key="d"/>
+                    </Parallel>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(node.tick(), NodeStatus::Success);
+        {
+            let bb = node.data_proxy_ref().blackboard();
+            assert!(bb.get_entry("a").is_none());
+            assert_eq!(bb.get_entry("b"), Some(serde_json::json!("2")));
+            assert!(bb.get_entry("c").is_none());
+            assert_eq!(bb.get_entry("d"), Some(serde_json::json!("4")));
+        }
+
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <SetBlackboard value="1" output_key="a" _skipIf="true"/>
+                    <SetBlackboard value="2" output_key="b" _while="false"/>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(node.tick(), NodeStatus::Skipped);
+    }
+
+    #[test]
+    fn test_loop_node_remapped_queue() {
+        use crate::testing::TreeTestExt;
+
+        let xml = r#"
+        <root BTCPP_format="4" main_tree_to_execute="main">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <PushToQueue queue="{items}" value="a"/>
+                    <PushToQueue queue="{items}" value="b"/>
+                    <SubTree ID="drain" queue="{items}" last="{last}"/>
+                </Sequence>
+            </BehaviorTree>
+            <BehaviorTree ID="drain">
+                <LoopNode queue="{queue}" value="{item}">
+                    <SetBlackboard value="{item}" output_key="last"/>
+                </LoopNode>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(node.tick_until_completed(10), NodeStatus::Success);
+        node.assert_blackboard_eq("last", serde_json::json!("b"));
+        // drained in the parent, not in a copy of the subtree scope
+        node.assert_blackboard_eq("items", serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_loop_node() {
+        use crate::testing::TreeTestExt;
+
+        struct Collect;
+
+        impl ActionNodeImpl for Collect {
+            fn tick_status(&mut self, data_proxy: &mut DataProxy) -> NodeStatus {
+                let Some(waypoint) = data_proxy.get_input::<String>("waypoint") else {
+                    return NodeStatus::Failure;
+                };
+
+                let mut bb = data_proxy.blackboard();
+                let visited = bb.get_entry("visited").unwrap_or(serde_json::json!(""));
+                let visited = format!("{}{waypoint}", visited.as_str().unwrap());
+                bb.set("visited".to_string(), serde_json::json!(visited));
+
+                NodeStatus::Success
+            }
+        }
+
+        let mut factory = test_factory();
+        factory.register_action_node_type(
+            "^Collect$".try_into().unwrap(),
+            boxify_action(|_, _| Ok(Collect)),
+        );
+
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <LoopNode queue="{waypoints}" value="{current}">
+                        <Collect waypoint="{current}"/>
+                    </LoopNode>
+                    <LoopNode queue="d;e">
+                        <Collect waypoint="x"/>
+                    </LoopNode>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&factory, xml).unwrap().unwrap();
+        node.data_proxy_ref()
+            .blackboard()
+            .set("waypoints".to_string(), serde_json::json!(["a", "b", "c"]));
+
+        let mut ticks = 1;
+        while node.tick() == NodeStatus::Running {
+            ticks += 1;
+        }
+
+        assert_eq!(ticks, 6);
+        node.assert_blackboard_eq("visited", serde_json::json!("abcxx"));
+        node.assert_blackboard_eq("waypoints", serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_repeat_forever() {
+        use crate::testing::TreeTestExt;
+
+        struct Count;
+
+        impl ActionNodeImpl for Count {
+            fn tick_status(&mut self, data_proxy: &mut DataProxy) -> NodeStatus {
+                let mut bb = data_proxy.blackboard();
+                let count = bb.get_entry("count").and_then(|v| v.as_u64()).unwrap_or(0);
+                bb.set("count".to_string(), serde_json::json!(count + 1));
+
+                NodeStatus::Success
+            }
+        }
+
+        let mut factory = test_factory();
+        factory.register_action_node_type(
+            "^Count$".try_into().unwrap(),
+            boxify_action(|_, _| Ok(Count)),
+        );
+
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Repeat num_cycles="{cycles}">
+                    <Count/>
+                </Repeat>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&factory, xml).unwrap().unwrap();
+        node.data_proxy_ref()
+            .blackboard()
+            .set("cycles".to_string(), serde_json::json!(-1));
+
+        for _ in 0..5 {
+            assert_eq!(node.tick(), NodeStatus::Running);
+        }
+        node.assert_blackboard_eq("count", serde_json::json!(5));
+
+        // lowering the count below the cycles already run completes the loop
+        node.data_proxy_ref()
+            .blackboard()
+            .set("cycles".to_string(), serde_json::json!(3));
+        assert_eq!(node.tick(), NodeStatus::Success);
+        node.assert_blackboard_eq("count", serde_json::json!(5));
+    }
+
+    #[test]
+    fn test_status_to_blackboard() {
+        use crate::testing::TreeTestExt;
+
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <StatusToBlackboard output_key="{sleep_status}">
+                    <Sleep msec="20"/>
+                </StatusToBlackboard>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(node.tick(), NodeStatus::Running);
+        node.assert_blackboard_eq("sleep_status", serde_json::json!("RUNNING"));
+
+        std::thread::sleep(Duration::from_millis(25));
+        assert_eq!(node.tick(), NodeStatus::Success);
+        node.assert_blackboard_eq("sleep_status", serde_json::json!("SUCCESS"));
+    }
+
+    #[test]
+    fn test_guard() {
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Guard condition="{door_open}">
+                    <Sleep msec="100000"/>
+                </Guard>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+        node.data_proxy_ref()
+            .blackboard()
+            .set("door_open".to_string(), serde_json::json!(true));
+
+        assert_eq!(node.tick(), NodeStatus::Running);
+        assert_eq!(node.tick(), NodeStatus::Running);
+
+        node.data_proxy_ref()
+            .blackboard()
+            .set("door_open".to_string(), serde_json::json!("false"));
+        assert_eq!(node.tick(), NodeStatus::Failure);
+
+        let NodeWrapper::Decorator(guard) = &node.node_wrapper else {
+            panic!("not a decorator");
+        };
+        assert_eq!(guard.inner_node.status(), NodeStatus::Idle);
+    }
+
+    #[test]
+    fn test_graceful_timeout() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        struct Move {
+            stopped: Arc<AtomicBool>,
+        }
+
+        impl ActionNodeImpl for Move {
+            fn tick_status(&mut self, _data_proxy: &mut DataProxy) -> NodeStatus {
+                NodeStatus::Running
+            }
+
+            fn halt(&mut self) {
+                self.stopped.store(false, Ordering::Release);
+
+                let stopped = self.stopped.clone();
+                std::thread::spawn(move || {
+                    std::thread::sleep(Duration::from_millis(30));
+                    stopped.store(true, Ordering::Release);
+                });
+            }
+
+            fn halt_pending(&self) -> bool {
+                !self.stopped.load(Ordering::Acquire)
+            }
+        }
+
+        let stopped = Arc::new(AtomicBool::new(true));
+        let mut factory = test_factory();
+        factory.register_action_node_type("^Move$".try_into().unwrap(), {
+            let stopped = stopped.clone();
+            boxify_action(move |_, _| {
+                Ok(Move {
+                    stopped: stopped.clone(),
+                })
+            })
+        });
+
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Timeout msec="10" grace_msec="1000">
+                    <Move/>
+                </Timeout>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&factory, xml).unwrap().unwrap();
+
+        assert_eq!(node.tick(), NodeStatus::Running);
+        std::thread::sleep(Duration::from_millis(15));
+
+        // halted, but still winding down
+        assert_eq!(node.tick(), NodeStatus::Running);
+        assert!(node.is_halting());
+
+        let mut status = NodeStatus::Running;
+        while status == NodeStatus::Running {
+            std::thread::sleep(Duration::from_millis(5));
+            status = node.tick();
+        }
+        assert_eq!(status, NodeStatus::Failure);
+        assert!(stopped.load(Ordering::Acquire));
+    }
+
+    #[test]
+    fn test_graceful_timeout_sync_halt() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        struct Move {
+            halts: Arc<AtomicUsize>,
+        }
+
+        impl ActionNodeImpl for Move {
+            fn tick_status(&mut self, _data_proxy: &mut DataProxy) -> NodeStatus {
+                NodeStatus::Running
+            }
+
+            fn halt(&mut self) {
+                self.halts.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let halts = Arc::new(AtomicUsize::new(0));
+        let mut factory = test_factory();
+        factory.register_action_node_type("^Move$".try_into().unwrap(), {
+            let halts = halts.clone();
+            boxify_action(move |_, _| {
+                Ok(Move {
+                    halts: halts.clone(),
+                })
+            })
+        });
+
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Timeout msec="10" grace_msec="1000">
+                    <Move/>
+                </Timeout>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&factory, xml).unwrap().unwrap();
+
+        assert_eq!(node.tick(), NodeStatus::Running);
+        std::thread::sleep(Duration::from_millis(15));
+
+        assert_eq!(node.tick(), NodeStatus::Failure);
+        assert_eq!(halts.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_tick_budget() {
+        struct Slow;
+
+        impl ActionNodeImpl for Slow {
+            fn tick_status(&mut self, _data_proxy: &mut DataProxy) -> NodeStatus {
+                std::thread::sleep(Duration::from_millis(5));
+
+                NodeStatus::Running
+            }
+        }
+
+        let mut factory = test_factory();
         factory.register_action_node_type(
-            "PrintBody".try_into().unwrap(),
-            boxify_action(|_, _| Ok(PrintBody)),
+            "^Slow$".try_into().unwrap(),
+            boxify_action(|_, _| Ok(Slow)),
+        );
+
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <TickBudget budget_usec="1000" max_overruns="2" overrun_key="{overrun}">
+                    <Slow/>
+                </TickBudget>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&factory, xml).unwrap().unwrap();
+
+        assert_eq!(node.tick(), NodeStatus::Running);
+        assert_eq!(node.tick(), NodeStatus::Failure);
+
+        let overrun = node
+            .data_proxy_ref()
+            .blackboard()
+            .get_entry("overrun")
+            .unwrap();
+        assert_eq!(overrun["consecutive"], 2);
+        assert!(overrun["elapsed_usec"].as_u64().unwrap() >= 5000);
+    }
+
+    #[test]
+    fn test_script() {
+        use crate::testing::TreeTestExt;
+
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <Script code="count := 0; battery := 40"/>
+                    <Repeat num_cycles="3">
+                        <Script code="count = count + 1; ok := battery &gt; 30"/>
+                    </Repeat>
+                    <Script code="missing = 1; count := 100"/>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(node.tick_until_completed(10), NodeStatus::Failure);
+        node.assert_blackboard_eq("count", serde_json::json!(3));
+        node.assert_blackboard_eq("ok", serde_json::json!(true));
+    }
+
+    #[test]
+    fn test_script_remapped_validated() {
+        use crate::node::ValidationPolicy;
+
+        let xml = r#"
+        <root BTCPP_format="4" main_tree_to_execute="main">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <Script code="battery := 40"/>
+                    <SubTree ID="worker" level="{battery}" count="{count}"/>
+                </Sequence>
+            </BehaviorTree>
+            <BehaviorTree ID="worker">
+                <Sequence>
+                    <Script code="level := 50"/>
+                    <Script code="count := 1; level := 120"/>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        let bb = Arc::new(RwLock::new(Blackboard::default()));
+        bb.write().register_validator(
+            "battery".to_string(),
+            ValidationPolicy::Reject,
+            Arc::new(|v| match v.as_i64() {
+                Some(level) if (0..=100).contains(&level) => Ok(()),
+                _ => Err("expect a level between 0 and 100".to_string()),
+            }),
+        );
+
+        let mut node =
+            create_bt_tree_from_xml_str_with_blackboard(&test_factory(), xml, bb.clone())
+                .unwrap()
+                .unwrap();
+
+        assert_eq!(node.tick(), NodeStatus::Failure);
+        assert_eq!(bb.read().get_entry("battery"), Some(serde_json::json!(50)));
+        assert_eq!(bb.read().get_entry("count"), None);
+    }
+
+    #[test]
+    fn test_script_body() {
+        use crate::testing::TreeTestExt;
+
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <Script>
+                        count := 0;
+                        battery := 40;
+                    </Script>
+                    <Script><![CDATA[
+                        count = count + 1;
+                        ok := battery > 30 && count < 2;
+                    ]]></Script>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(node.tick_until_completed(10), NodeStatus::Success);
+        node.assert_blackboard_eq("count", serde_json::json!(1));
+        node.assert_blackboard_eq("ok", serde_json::json!(true));
+
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Script code="count := 0">count := 1</Script>
+            </BehaviorTree>
+        </root>"#;
+
+        let err = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("both attribute and text"), "{err}");
+    }
+
+    #[test]
+    fn test_unset_blackboard() {
+        let xml = r#"
+        <root BTCPP_format="4" main_tree_to_execute="main">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <SetBlackboard value="left_arm" output_key="arm"/>
+                    <SetBlackboard value="head" output_key="sensor"/>
+                    <SubTree ID="sub" _private="true" inner_arm="{arm}"/>
+                </Sequence>
+            </BehaviorTree>
+            <BehaviorTree ID="sub">
+                <Sequence>
+                    <UnsetBlackboard key="{inner_arm}"/>
+                    <UnsetBlackboard key="sensor"/>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(node.tick(), NodeStatus::Success);
+
+        let bb = node.data_proxy_ref().blackboard();
+        // removed through the remapping, the private subtree can't reach `sensor`
+        assert!(bb.get_entry("arm").is_none());
+        assert_eq!(bb.get_entry("sensor"), Some(serde_json::json!("head")));
+    }
+
+    #[test]
+    fn test_queue_nodes() {
+        use crate::testing::TreeTestExt;
+
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <SetBlackboard value="kitchen" output_key="room"/>
+                    <PushToQueue queue="{waypoints}" value="door"/>
+                    <PushToQueue queue="{waypoints}" value="{room}"/>
+                    <PopFromQueue queue="{waypoints}" value="{next}"/>
+                    <QueueSize queue="{waypoints}" size="{left}"/>
+                    <PopFromQueue queue="{waypoints}" value="{last}"/>
+                    <PopFromQueue queue="{waypoints}" value="{none}"/>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+
+        // the final pop fails on the empty queue
+        assert_eq!(node.tick(), NodeStatus::Failure);
+        node.assert_blackboard_eq("next", serde_json::json!("door"));
+        node.assert_blackboard_eq("left", serde_json::json!(1));
+        node.assert_blackboard_eq("last", serde_json::json!("kitchen"));
+        node.assert_blackboard_eq("waypoints", serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_queue_nodes_remapped() {
+        use crate::testing::TreeTestExt;
+
+        let xml = r#"
+        <root BTCPP_format="4" main_tree_to_execute="main">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <PushToQueue queue="{tasks}" value="a"/>
+                    <SubTree ID="worker" q="{tasks}" first="{first}"/>
+                    <QueueSize queue="{tasks}" size="{left}"/>
+                </Sequence>
+            </BehaviorTree>
+            <BehaviorTree ID="worker">
+                <Sequence>
+                    <PushToQueue queue="{q}" value="b"/>
+                    <PopFromQueue queue="{q}" value="{first}"/>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(node.tick(), NodeStatus::Success);
+        node.assert_blackboard_eq("first", serde_json::json!("a"));
+        node.assert_blackboard_eq("left", serde_json::json!(1));
+        node.assert_blackboard_eq("tasks", serde_json::json!(["b"]));
+    }
+
+    #[test]
+    fn test_stateful_action() {
+        use crate::node::action::{StatefulAction, StatefulActionNodeImpl};
+
+        #[derive(Default)]
+        struct Phases(Arc<parking_lot::Mutex<Vec<&'static str>>>);
+
+        impl StatefulActionNodeImpl for Phases {
+            fn on_start(&mut self, _data_proxy: &mut DataProxy) -> NodeStatus {
+                self.0.lock().push("start");
+                NodeStatus::Running
+            }
+
+            fn on_running(&mut self, _data_proxy: &mut DataProxy) -> NodeStatus {
+                let mut phases = self.0.lock();
+                phases.push("running");
+
+                if phases.len() == 3 {
+                    NodeStatus::Success
+                } else {
+                    NodeStatus::Running
+                }
+            }
+
+            fn on_halted(&mut self) {
+                self.0.lock().push("halted");
+            }
+        }
+
+        let phases = Arc::new(parking_lot::Mutex::new(vec![]));
+        let mut factory = test_factory();
+        factory.register_action_node_type("^Phases$".try_into().unwrap(), {
+            let phases = phases.clone();
+            boxify_action(move |_, _| Ok(StatefulAction::new(Phases(phases.clone()))))
+        });
+
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Phases/>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&factory, xml).unwrap().unwrap();
+
+        assert_eq!(node.tick(), NodeStatus::Running);
+        assert_eq!(node.tick(), NodeStatus::Running);
+        assert_eq!(node.tick(), NodeStatus::Success);
+        assert_eq!(node.tick(), NodeStatus::Running);
+        node.halt();
+        // not running anymore, nothing to halt
+        node.halt();
+
+        assert_eq!(
+            *phases.lock(),
+            ["start", "running", "running", "start", "halted"]
+        );
+    }
+
+    #[test]
+    fn test_condition_node() {
+        use crate::factory::boxify_condition;
+        use crate::node::condition::ConditionNodeImpl;
+
+        struct Fixed(NodeStatus);
+
+        impl ConditionNodeImpl for Fixed {
+            fn tick_status(&mut self, _data_proxy: &mut DataProxy) -> NodeStatus {
+                self.0
+            }
+        }
+
+        let mut factory = test_factory();
+        factory.register_condition_node_type(
+            "^IsReady$".try_into().unwrap(),
+            boxify_condition(|_, _| Ok(Fixed(NodeStatus::Success))),
+        );
+        factory.register_condition_node_type(
+            "^IsBroken$".try_into().unwrap(),
+            boxify_condition(|_, _| Ok(Fixed(NodeStatus::Running))),
+        );
+
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <IsReady/>
+                    <IsBroken/>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&factory, xml).unwrap().unwrap();
+
+        assert_eq!(node.uid_map()[&1].node_type, crate::NodeType::Condition);
+        // a condition is never left running
+        assert_eq!(node.tick(), NodeStatus::Failure);
+    }
+
+    #[test]
+    fn test_async_action() {
+        use crate::node::action::{ActionFuture, AsyncAction, AsyncActionNodeImpl};
+        use tokio::sync::oneshot;
+
+        type Senders = Arc<parking_lot::Mutex<Vec<oneshot::Sender<bool>>>>;
+
+        struct WaitReply(Senders);
+
+        impl AsyncActionNodeImpl for WaitReply {
+            fn start(&mut self, _data_proxy: &mut DataProxy) -> ActionFuture {
+                let (tx, rx) = oneshot::channel();
+                self.0.lock().push(tx);
+
+                Box::pin(async move {
+                    match rx.await {
+                        Ok(true) => Ok(()),
+                        _ => Err(BtError::Raw("request rejected".to_string())),
+                    }
+                })
+            }
+        }
+
+        let senders = Senders::default();
+        let mut factory = test_factory();
+        factory.register_action_node_type("^WaitReply$".try_into().unwrap(), {
+            let senders = senders.clone();
+            boxify_action(move |_, _| Ok(AsyncAction::new(WaitReply(senders.clone()))))
+        });
+
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <WaitReply/>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&factory, xml).unwrap().unwrap();
+
+        assert_eq!(node.tick(), NodeStatus::Running);
+        assert_eq!(node.tick(), NodeStatus::Running);
+        senders.lock().pop().unwrap().send(true).unwrap();
+        // the future completes on the runtime, the next ticks pick it up
+        let status = loop {
+            match node.tick() {
+                NodeStatus::Running => std::thread::sleep(Duration::from_millis(1)),
+                status => break status,
+            }
+        };
+        assert_eq!(status, NodeStatus::Success);
+
+        // halting drops the pending future
+        assert_eq!(node.tick(), NodeStatus::Running);
+        node.halt();
+        assert!(senders.lock().pop().unwrap().is_closed());
+    }
+
+    #[cfg(feature = "rt-tokio")]
+    #[test]
+    fn test_async_action_tokio_timer() {
+        use crate::node::action::{ActionFuture, AsyncAction, AsyncActionNodeImpl};
+
+        struct Nap;
+
+        impl AsyncActionNodeImpl for Nap {
+            fn start(&mut self, _data_proxy: &mut DataProxy) -> ActionFuture {
+                Box::pin(async {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    Ok(())
+                })
+            }
+        }
+
+        let mut factory = test_factory();
+        factory.register_action_node_type(
+            "^Nap$".try_into().unwrap(),
+            boxify_action(|_, _| Ok(AsyncAction::new(Nap))),
         );
+
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Nap/>
+            </BehaviorTree>
+        </root>"#;
+
+        // ticked outside of any tokio runtime, the timer still fires
+        let mut node = create_bt_tree_from_xml_str(&factory, xml).unwrap().unwrap();
+        assert_eq!(node.tick(), NodeStatus::Running);
+
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(node.tick(), NodeStatus::Success);
+    }
+
+    #[test]
+    fn test_coroutine_action() {
+        use crate::{
+            node::action::{coroutine, yield_running},
+            testing::TreeTestExt,
+        };
+
+        let mut factory = test_factory();
         factory.register_action_node_type(
-            "PrintArm".try_into().unwrap(),
-            boxify_action(|_, _| Ok(PrintArm)),
+            "^Patrol$".try_into().unwrap(),
+            boxify_action(|_, _| {
+                Ok(coroutine(|data_proxy| {
+                    let bb = data_proxy.shared_blackboard().clone();
+
+                    Box::pin(async move {
+                        for waypoint in ["a", "b", "c"] {
+                            bb.write().set("at".to_string(), waypoint.into());
+                            yield_running().await;
+                        }
+
+                        Ok(())
+                    })
+                }))
+            }),
+        );
+
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Patrol/>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&factory, xml).unwrap().unwrap();
+        let at = |node: &TreeNodeWrapper| node.data_proxy_ref().blackboard().get_entry("at");
+
+        assert_eq!(node.tick(), NodeStatus::Running);
+        assert_eq!(at(&node), Some(serde_json::json!("a")));
+        assert_eq!(node.tick(), NodeStatus::Running);
+        assert_eq!(at(&node), Some(serde_json::json!("b")));
+
+        // restarts from the top after a halt
+        node.halt();
+        assert_eq!(node.tick(), NodeStatus::Running);
+        assert_eq!(at(&node), Some(serde_json::json!("a")));
+
+        assert_eq!(node.tick_until_completed(5), NodeStatus::Success);
+        assert_eq!(at(&node), Some(serde_json::json!("c")));
+    }
+
+    #[test]
+    fn test_typed_set_blackboard() {
+        use crate::testing::TreeTestExt;
+
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <SetBlackboard value="42" output_key="count" type="int"/>
+                    <SetBlackboard value="0.5" output_key="ratio" type="float"/>
+                    <SetBlackboard value="true" output_key="armed" type="bool"/>
+                    <SetBlackboard value='[{"x": 1}]' output_key="poses" type="json"/>
+                    <SetBlackboard value="[1, 2]" output_key="list" type="auto"/>
+                    <SetBlackboard value="7" output_key="text"/>
+                    <SetBlackboard value="nope" output_key="bad" type="int"/>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(node.tick(), NodeStatus::Failure);
+        node.assert_blackboard_eq("count", serde_json::json!(42));
+        node.assert_blackboard_eq("ratio", serde_json::json!(0.5));
+        node.assert_blackboard_eq("armed", serde_json::json!(true));
+        node.assert_blackboard_eq("poses", serde_json::json!([{"x": 1}]));
+        node.assert_blackboard_eq("list", serde_json::json!([1, 2]));
+        node.assert_blackboard_eq("text", serde_json::json!("7"));
+
+        // string and number entries convert on read
+        let mut data_proxy = DataProxy::new(node.data_proxy_ref().shared_blackboard().clone());
+        data_proxy.add_input("count".to_string(), "{count}".to_string());
+        data_proxy.add_input("text".to_string(), "{text}".to_string());
+        assert_eq!(
+            data_proxy.get_input::<String>("count"),
+            Some("42".to_string())
+        );
+        assert_eq!(data_proxy.get_input::<i64>("text"), Some(7));
+    }
+
+    #[cfg(all(unix, feature = "process"))]
+    #[test]
+    fn test_run_command() {
+        use crate::testing::TreeTestExt;
+
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Sequence>
+                    <RunCommand command="sh" args="-c;echo hello" stdout="{out}" exit_code="{code}"/>
+                    <RunCommand command="sh" args="-c;exit 3" exit_code="{failed_code}"/>
+                </Sequence>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+
+        let mut status = NodeStatus::Running;
+        while status == NodeStatus::Running {
+            std::thread::sleep(Duration::from_millis(5));
+            status = node.tick();
+        }
+
+        assert_eq!(status, NodeStatus::Failure);
+        node.assert_blackboard_eq("out", serde_json::json!("hello\n"));
+        node.assert_blackboard_eq("code", serde_json::json!(0));
+        node.assert_blackboard_eq("failed_code", serde_json::json!(3));
+
+        // halting kills the process
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <RunCommand command="sleep" args="100"/>
+            </BehaviorTree>
+        </root>"#;
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+        let start = std::time::Instant::now();
+        assert_eq!(node.tick(), NodeStatus::Running);
+        node.halt();
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_http_request() {
+        use std::io::{Read, Write};
+
+        use crate::testing::TreeTestExt;
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            for (stream, reply) in listener.incoming().zip(["201 Created", "404 Not Found"]) {
+                let mut stream = stream.unwrap();
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request).unwrap();
+
+                let response =
+                    format!("HTTP/1.1 {reply}\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok");
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+
+        let xml = format!(
+            r#"
+            <root BTCPP_format="4">
+                <BehaviorTree ID="main">
+                    <Sequence>
+                        <HttpRequest url="http://127.0.0.1:{port}/goal" method="post" body="x" response="{{reply}}" status_code="{{code}}"/>
+                        <HttpRequest url="http://127.0.0.1:{port}/missing" status_code="{{missing_code}}"/>
+                    </Sequence>
+                </BehaviorTree>
+            </root>"#
         );
 
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), &xml)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(node.tick(), NodeStatus::Running);
+
+        let mut status = NodeStatus::Running;
+        while status == NodeStatus::Running {
+            std::thread::sleep(Duration::from_millis(5));
+            status = node.tick();
+        }
+
+        assert_eq!(status, NodeStatus::Failure);
+        node.assert_blackboard_eq("reply", serde_json::json!("ok"));
+        node.assert_blackboard_eq("code", serde_json::json!(201));
+        node.assert_blackboard_eq("missing_code", serde_json::json!(404));
+    }
+
+    #[test]
+    fn test_events() {
+        use crate::testing::TreeTestExt;
+
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Parallel success_count="2">
+                    <WaitForEvent event="arrived" payload="{where}"/>
+                    <Sequence>
+                        <Sleep msec="20"/>
+                        <PublishEvent event="other"/>
+                        <PublishEvent event="arrived" payload="dock"/>
+                    </Sequence>
+                </Parallel>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+        let mut external = node.data_proxy_ref().events().subscribe();
+
+        assert_eq!(node.tick(), NodeStatus::Running);
+        std::thread::sleep(Duration::from_millis(25));
+        // published after the waiting branch was ticked in this round
+        assert_eq!(node.tick(), NodeStatus::Running);
+        assert_eq!(node.tick(), NodeStatus::Success);
+        node.assert_blackboard_eq("where", serde_json::json!("dock"));
+
+        assert_eq!(external.try_recv().unwrap().name, "other");
+        assert_eq!(external.try_recv().unwrap().name, "arrived");
+    }
+
+    #[test]
+    fn test_time_scale() {
+        let xml = r#"
+        <root BTCPP_format="4">
+            <BehaviorTree ID="main">
+                <Timeout msec="10000">
+                    <Sleep msec="1000"/>
+                </Timeout>
+            </BehaviorTree>
+        </root>"#;
+
+        let mut node = create_bt_tree_from_xml_str(&test_factory(), xml)
+            .unwrap()
+            .unwrap();
+        let clock = Arc::new(crate::clock::Clock::manual(100.0));
+        node.set_clock(clock.clone());
+
+        assert_eq!(node.tick(), NodeStatus::Running);
+        clock.advance(Duration::from_millis(9));
+        assert_eq!(node.tick(), NodeStatus::Running);
+        clock.advance(Duration::from_millis(1));
+        assert_eq!(node.tick(), NodeStatus::Success);
+    }
+
+    #[tokio::test]
+    async fn test_parse() {
+        use tokio_stream::StreamExt;
+
+        let fmt_layer = tracing_subscriber::fmt::Layer::new()
+            .with_file(true)
+            .with_line_number(true)
+            .with_thread_names(true);
+
+        let env_filter = EnvFilter::builder()
+            .with_default_directive(LevelFilter::INFO.into())
+            .from_env_lossy();
+
+        tracing_subscriber::registry()
+            .with(fmt_layer)
+            .with(env_filter)
+            .init();
+
+        let factory = test_factory();
+
         let mut xml_path = assets_dir();
         xml_path.push("full.xml");
 
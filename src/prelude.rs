@@ -0,0 +1,20 @@
+//! Common imports for implementing nodes and building trees.
+//!
+//! ```
+//! use cornerstone::prelude::*;
+//! ```
+
+pub use crate::{
+    factory::{boxify_action, Factory},
+    node::{
+        action::ActionNodeImpl, composite::CompositeNodeImpl, decorator::DecoratorNodeImpl,
+        Blackboard, DataProxy,
+    },
+    parser::json::create_bt_tree_from_json_str,
+    parser::xml::{
+        create_bt_tree_from_reader, create_bt_tree_from_xml_file, create_bt_tree_from_xml_str,
+        create_bt_tree_from_xml_str_with_blackboard, create_bt_tree_from_xml_str_with_options,
+        validate_bt_tree_from_xml_str, ParseOptions, TreeDocument,
+    },
+    BtError, NodeStatus, TreeNode, TreeNodeWrapper,
+};
@@ -0,0 +1,109 @@
+//! Tick loop driving a tree, with orderly shutdown.
+
+use std::{future::Future, pin::pin, task::Poll, time::Duration};
+
+use crate::{runtime::Runtime, NodeStatus, TreeNode, TreeNodeWrapper};
+
+pub struct TickLoop {
+    tick_interval: Duration,
+    drain_timeout: Duration,
+}
+
+impl TickLoop {
+    pub fn new(tick_interval: Duration) -> Self {
+        Self {
+            tick_interval,
+            drain_timeout: Duration::ZERO,
+        }
+    }
+
+    /// How long to keep ticking after shutdown was requested, giving running
+    /// nodes a chance to complete before the tree is halted.
+    pub fn drain_timeout(mut self, drain_timeout: Duration) -> Self {
+        self.drain_timeout = drain_timeout;
+        self
+    }
+
+    /// Tick `tree` until it completes or `shutdown` resolves, returns the
+    /// status of the last tick. On shutdown the tree is drained, then halted.
+    pub async fn run(
+        &self,
+        runtime: &dyn Runtime,
+        tree: &mut TreeNodeWrapper,
+        shutdown: impl Future<Output = ()>,
+    ) -> NodeStatus {
+        let mut shutdown = pin!(shutdown);
+
+        loop {
+            let status = tree.tick();
+            if status.is_completed() || status == NodeStatus::Skipped {
+                return status;
+            }
+
+            let mut sleep = runtime.sleep(self.tick_interval);
+            let stop = std::future::poll_fn(|cx| {
+                if shutdown.as_mut().poll(cx).is_ready() {
+                    Poll::Ready(true)
+                } else if sleep.as_mut().poll(cx).is_ready() {
+                    Poll::Ready(false)
+                } else {
+                    Poll::Pending
+                }
+            })
+            .await;
+
+            if stop {
+                tracing::info!(
+                    "shutdown requested, draining tree: {:?}",
+                    self.drain_timeout
+                );
+                return self.drain(runtime, tree).await;
+            }
+        }
+    }
+
+    async fn drain(&self, runtime: &dyn Runtime, tree: &mut TreeNodeWrapper) -> NodeStatus {
+        let deadline = std::time::Instant::now() + self.drain_timeout;
+
+        loop {
+            let status = tree.tick();
+            if status.is_completed() || status == NodeStatus::Skipped {
+                return status;
+            }
+
+            if std::time::Instant::now() >= deadline {
+                tracing::warn!("drain timeout, halting tree");
+                tree.halt_tree();
+
+                return status;
+            }
+
+            runtime.sleep(self.tick_interval).await;
+        }
+    }
+}
+
+/// Resolves on Ctrl-C, or SIGTERM on unix.
+#[cfg(feature = "signal")]
+pub async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = sigterm.recv() => {}
+                }
+            }
+            Err(e) => {
+                tracing::warn!("can't listen for SIGTERM: err= {e}");
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+}
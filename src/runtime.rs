@@ -0,0 +1,103 @@
+//! Minimal async runtime abstraction, so observers, async actions, timers and
+//! the tick loop work with either tokio (`rt-tokio`, default) or async-std
+//! (`rt-async-std`).
+
+use std::{future::Future, pin::Pin, sync::Arc, time::Duration};
+
+#[cfg(feature = "rt-tokio")]
+use once_cell::sync::Lazy;
+
+/// Channels of observers, tree events and async actions. They come from
+/// `tokio::sync`, which needs no tokio runtime and can be awaited from any
+/// executor, the rest of the crate uses them through this module.
+pub mod sync {
+    pub use tokio::sync::{broadcast, oneshot, watch};
+}
+
+pub type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+pub trait Runtime: Send + Sync + 'static {
+    fn spawn(&self, fut: BoxFuture);
+
+    /// Run blocking work, e.g. a node tick, on the runtime's blocking pool.
+    fn spawn_blocking(&self, f: Box<dyn FnOnce() + Send + 'static>);
+
+    fn sleep(&self, duration: Duration) -> BoxFuture;
+}
+
+/// Spawns onto the tokio runtime of the calling context, or onto a
+/// background runtime of its own when called outside of one.
+#[cfg(feature = "rt-tokio")]
+#[derive(Default, Debug, Clone, Copy)]
+pub struct TokioRuntime;
+
+#[cfg(feature = "rt-tokio")]
+impl Runtime for TokioRuntime {
+    fn spawn(&self, fut: BoxFuture) {
+        tokio_handle().spawn(fut);
+    }
+
+    fn spawn_blocking(&self, f: Box<dyn FnOnce() + Send + 'static>) {
+        tokio_handle().spawn_blocking(f);
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture {
+        // the timer registers with the runtime entered at creation
+        let _guard = tokio_handle().enter();
+
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+#[cfg(feature = "rt-tokio")]
+fn tokio_handle() -> tokio::runtime::Handle {
+    // driven by a thread of its own, for trees ticked outside of tokio
+    static FALLBACK: Lazy<tokio::runtime::Handle> = Lazy::new(|| {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .thread_name("cornerstone-runtime")
+            .build()
+            .expect("build fallback tokio runtime");
+        let handle = rt.handle().clone();
+
+        std::thread::Builder::new()
+            .name("cornerstone-runtime".to_string())
+            .spawn(move || rt.block_on(std::future::pending::<()>()))
+            .expect("spawn fallback tokio runtime thread");
+
+        handle
+    });
+
+    tokio::runtime::Handle::try_current().unwrap_or_else(|_| FALLBACK.clone())
+}
+
+#[cfg(feature = "rt-async-std")]
+#[derive(Default, Debug, Clone, Copy)]
+pub struct AsyncStdRuntime;
+
+#[cfg(feature = "rt-async-std")]
+impl Runtime for AsyncStdRuntime {
+    fn spawn(&self, fut: BoxFuture) {
+        async_std::task::spawn(fut);
+    }
+
+    fn spawn_blocking(&self, f: Box<dyn FnOnce() + Send + 'static>) {
+        async_std::task::spawn_blocking(f);
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture {
+        Box::pin(async_std::task::sleep(duration))
+    }
+}
+
+/// Runtime selected by features, tokio wins when both are enabled.
+pub fn default_runtime() -> Option<Arc<dyn Runtime>> {
+    #[cfg(feature = "rt-tokio")]
+    return Some(Arc::new(TokioRuntime));
+
+    #[cfg(all(feature = "rt-async-std", not(feature = "rt-tokio")))]
+    return Some(Arc::new(AsyncStdRuntime));
+
+    #[cfg(not(any(feature = "rt-tokio", feature = "rt-async-std")))]
+    None
+}
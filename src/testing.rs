@@ -0,0 +1,68 @@
+use serde_json::Value;
+
+use crate::{NodeStatus, NodeWrapper, TreeNode, TreeNodeWrapper};
+
+/// Assertion helpers to keep behavior tree tests short.
+pub trait TreeTestExt {
+    /// Tick until the tree completes, panics if it is still running after `max_ticks`.
+    fn tick_until_completed(&mut self, max_ticks: usize) -> NodeStatus;
+
+    /// Find the first node (depth first) whose full path equals `path`.
+    fn find_node(&self, path: &str) -> Option<&TreeNodeWrapper>;
+
+    fn assert_node_status(&self, path: &str, status: NodeStatus);
+
+    fn assert_blackboard_eq(&self, key: &str, value: Value);
+}
+
+impl TreeTestExt for TreeNodeWrapper {
+    fn tick_until_completed(&mut self, max_ticks: usize) -> NodeStatus {
+        for _ in 0..max_ticks {
+            let status = self.tick();
+
+            if status != NodeStatus::Running {
+                return status;
+            }
+        }
+
+        panic!("tree still running after {max_ticks} ticks");
+    }
+
+    fn find_node(&self, path: &str) -> Option<&TreeNodeWrapper> {
+        if self.data_proxy_ref().full_path() == path {
+            return Some(self);
+        }
+
+        match &self.node_wrapper {
+            NodeWrapper::Composite(cp) => cp
+                .child_nodes
+                .iter()
+                .find_map(|child| child.find_node(path)),
+            NodeWrapper::Decorator(dr) => dr.inner_node.find_node(path),
+            NodeWrapper::Action(_) | NodeWrapper::Condition(_) => None,
+        }
+    }
+
+    fn assert_node_status(&self, path: &str, status: NodeStatus) {
+        let Some(node) = self.find_node(path) else {
+            panic!("no node found: path= {path}");
+        };
+
+        assert_eq!(
+            node.status(),
+            status,
+            "unexpected status: path= {path} uid= {}",
+            node.uid()
+        );
+    }
+
+    fn assert_blackboard_eq(&self, key: &str, value: Value) {
+        let entry = self.data_proxy_ref().blackboard().get_entry(key);
+
+        assert_eq!(
+            entry,
+            Some(value),
+            "unexpected blackboard entry: key= {key}"
+        );
+    }
+}